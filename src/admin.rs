@@ -0,0 +1,411 @@
+use std::net::SocketAddr;
+
+use serde::Deserialize;
+use serde_json::json;
+
+#[cfg(feature = "admin-api")]
+use std::sync::Arc;
+
+#[cfg(feature = "admin-api")]
+use anyhow::{anyhow, Context};
+#[cfg(feature = "admin-api")]
+use log::{debug, info, warn};
+#[cfg(feature = "admin-api")]
+use ring::hmac;
+#[cfg(feature = "admin-api")]
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}, sync::RwLock};
+
+#[cfg(feature = "admin-api")]
+use crate::module::Module;
+
+/// Maximum size of the head of a request in bytes
+#[cfg(feature = "admin-api")]
+const MAX_HEAD_SIZE: usize = 8192;
+
+/// Maximum size of the body of a request in bytes
+#[cfg(feature = "admin-api")]
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Maximum number of seconds the timestamp of a signed request may differ from the current time
+#[cfg(feature = "admin-api")]
+const MAX_SIGNATURE_AGE: i64 = 300;
+
+/// Struct to hold the settings of the admin api, requires the 'admin-api' feature
+#[derive(Deserialize, Clone)]
+pub struct AdminApiSettings {
+    /// Address to serve the admin api on, e.g. '127.0.0.1:9091'
+    pub address: SocketAddr,
+    /// Bearer token required by every request, supports environment variables in the form of '${NAME}'
+    pub token: String,
+    /// Secret every request must be signed with using HMAC-SHA256, supports environment variables in the form of '${NAME}'
+    #[serde(default)]
+    pub signing_secret: Option<String>
+}
+
+/// Credentials every request to the admin api is checked against
+#[cfg(feature = "admin-api")]
+struct Credentials {
+    /// The expected bearer token
+    token: String,
+    /// The key requests must be signed with, if signatures are required
+    signing_key: Option<hmac::Key>
+}
+
+/// A request to the admin api
+pub struct AdminRequest {
+    /// The http method, e.g. 'GET'
+    pub method: String,
+    /// The path without the query, e.g. '/reactions'
+    pub path: String,
+    /// The body of the request
+    pub body: Vec<u8>
+}
+
+/// A response of the admin api
+pub struct AdminResponse {
+    /// The http status code
+    pub status: u16,
+    /// The json body of the response
+    pub body: serde_json::Value
+}
+
+impl AdminResponse {
+
+    ///
+    /// Create a successful response
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The http status code
+    /// * `body` - The json body
+    ///
+    /// # Returns
+    ///
+    /// The response
+    ///
+    pub fn ok(status: u16, body: serde_json::Value) -> Self {
+        Self { status, body }
+    }
+
+    ///
+    /// Create an error response
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The http status code
+    /// * `error` - The error message
+    ///
+    /// # Returns
+    ///
+    /// The response with a body of the form `{"error": "..."}`
+    ///
+    pub fn error(status: u16, error: impl Into<String>) -> Self {
+        Self { status, body: json!({ "error": error.into() }) }
+    }
+
+    ///
+    /// Create an error response listing several issues
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The http status code
+    /// * `error` - The error message
+    /// * `issues` - The issues causing the error
+    ///
+    /// # Returns
+    ///
+    /// The response with a body of the form `{"error": "...", "issues": ["..."]}`
+    ///
+    pub fn issues(status: u16, error: impl Into<String>, issues: &[anyhow::Error]) -> Self {
+        let issues = issues.iter().map(|issue| format!("{:#}", issue)).collect::<Vec<_>>();
+        Self { status, body: json!({ "error": error.into(), "issues": issues }) }
+    }
+
+}
+
+///
+/// Serve the admin api over http until the task is aborted
+///
+/// Every request is passed to the modules in order until one of them handles it.
+///
+/// # Arguments
+///
+/// * `settings` - The admin api settings
+/// * `modules` - The modules of the bot
+///
+/// # Errors
+///
+/// If the token cannot be expanded or the address cannot be bound
+///
+#[cfg(feature = "admin-api")]
+pub async fn serve(settings: AdminApiSettings, modules: Arc<RwLock<Vec<Box<dyn Module>>>>) -> Result<(), anyhow::Error> {
+    let token = crate::env::expand(&settings.token)
+        .context("failed to expand admin api token")?;
+    let signing_key = match &settings.signing_secret {
+        Some(secret) => {
+            let secret = crate::env::expand(secret)
+                .context("failed to expand admin api signing secret")?;
+            Some(hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()))
+        },
+        None => None
+    };
+    let credentials = Arc::new(Credentials { token, signing_key });
+    let listener = TcpListener::bind(settings.address).await
+        .context("failed to bind admin api address")?;
+    info!(target: "admin", "serving admin api on http://{}{}", settings.address,
+        if credentials.signing_key.is_some() { " with signed requests" } else { "" });
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!(target: "admin", "failed to accept connection: {:?}", e);
+                continue;
+            }
+        };
+
+        let credentials = credentials.clone();
+        let modules = modules.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &credentials, &modules).await {
+                debug!(target: "admin", "failed to respond to {}: {:?}", peer, e);
+            }
+        });
+    }
+}
+
+///
+/// Respond to a single http request
+///
+/// # Arguments
+///
+/// * `stream` - The connection
+/// * `credentials` - The credentials to check the request against
+/// * `modules` - The modules of the bot
+///
+/// # Errors
+///
+/// If the request cannot be read or the response cannot be written
+///
+#[cfg(feature = "admin-api")]
+async fn respond(mut stream: TcpStream, credentials: &Credentials, modules: &RwLock<Vec<Box<dyn Module>>>) -> Result<(), anyhow::Error> {
+    // read the request head
+    let mut request = Vec::new();
+    let mut buffer = [0; 4096];
+    let head_end = loop {
+        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            break Some(end);
+        }
+        if request.len() >= MAX_HEAD_SIZE {
+            break None;
+        }
+        let read = stream.read(&mut buffer).await
+            .context("failed to read request")?;
+        if read == 0 {
+            break None;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    };
+
+    let response = match head_end {
+        Some(end) => {
+            let head = String::from_utf8_lossy(&request[..end]).into_owned();
+            let body = request[end + 4..].to_vec();
+            handle(&mut stream, &head, body, credentials, modules).await?
+        },
+        None => AdminResponse::error(400, "malformed request")
+    };
+
+    let body = response.body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status, reason(response.status), body.len(), body
+    );
+    stream.write_all(response.as_bytes()).await
+        .context("failed to write response")?;
+    stream.shutdown().await
+        .context("failed to close connection")
+}
+
+///
+/// Authenticate a request, read its body and pass it to the modules
+///
+/// # Arguments
+///
+/// * `stream` - The connection
+/// * `head` - The request line and headers
+/// * `body` - The part of the body read with the head
+/// * `credentials` - The credentials to check the request against
+/// * `modules` - The modules of the bot
+///
+/// # Returns
+///
+/// The response to the request
+///
+/// # Errors
+///
+/// If the body cannot be read
+///
+#[cfg(feature = "admin-api")]
+async fn handle(stream: &mut TcpStream, head: &str, mut body: Vec<u8>, credentials: &Credentials, modules: &RwLock<Vec<Box<dyn Module>>>) -> Result<AdminResponse, anyhow::Error> {
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or("/");
+    let path = path.split_once('?').map_or(path, |(path, _)| path).to_string();
+    let header = |name: &str| head.split("\r\n").skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim());
+
+    // check the token before reading the body
+    let authorized = header("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| constant_time_eq(value.trim().as_bytes(), credentials.token.as_bytes()));
+    if !authorized {
+        warn!(target: "admin", "rejected unauthorized {} request to {}", method, path);
+        return Ok(AdminResponse::error(401, "missing or invalid bearer token"));
+    }
+
+    // read the rest of the body
+    let length = match header("Content-Length").map(str::parse::<usize>) {
+        Some(Ok(length)) => length,
+        Some(Err(_)) => return Ok(AdminResponse::error(400, "invalid content length")),
+        None => 0
+    };
+    if length > MAX_BODY_SIZE {
+        return Ok(AdminResponse::error(413, format!("body exceeds {} bytes", MAX_BODY_SIZE)));
+    }
+    let mut buffer = [0; 4096];
+    while body.len() < length {
+        let read = stream.read(&mut buffer).await
+            .context("failed to read request body")?;
+        if read == 0 {
+            return Ok(AdminResponse::error(400, "body is shorter than the content length"));
+        }
+        body.extend_from_slice(&buffer[..read]);
+    }
+    body.truncate(length);
+
+    // check the signature over the whole request
+    if let Some(key) = &credentials.signing_key {
+        if let Err(e) = verify_signature(key, &method, &path, &body, header("X-Signature-Timestamp"), header("X-Signature")) {
+            warn!(target: "admin", "rejected {} request to {} with an invalid signature: {}", method, path, e);
+            return Ok(AdminResponse::error(401, format!("invalid signature: {}", e)));
+        }
+    }
+
+    // pass the request to the modules
+    info!(target: "admin", "handling {} request to {}", method, path);
+    let request = AdminRequest { method, path, body };
+    for module in modules.write().await.iter_mut() {
+        if let Some(response) = module.handle_admin(&request).await {
+            return Ok(response);
+        }
+    }
+    Ok(AdminResponse::error(404, format!("no route for {} {}", request.method, request.path)))
+}
+
+///
+/// Verify the signature of a request
+///
+/// The signature is the hex encoded HMAC-SHA256 of the timestamp, method and path separated by
+/// newlines, followed by a newline and the body, e.g. "1700000000\nPUT\n/reactions\n[...]".
+///
+/// # Arguments
+///
+/// * `key` - The signing key
+/// * `method` - The http method
+/// * `path` - The path without the query
+/// * `body` - The body of the request
+/// * `timestamp` - The value of the 'X-Signature-Timestamp' header, unix seconds
+/// * `signature` - The value of the 'X-Signature' header, optionally prefixed with 'sha256='
+///
+/// # Errors
+///
+/// If a header is missing, the timestamp is too old or too far in the future or the signature doesn't match
+///
+#[cfg(feature = "admin-api")]
+fn verify_signature(key: &hmac::Key, method: &str, path: &str, body: &[u8], timestamp: Option<&str>, signature: Option<&str>) -> Result<(), anyhow::Error> {
+    let timestamp = timestamp.context("missing X-Signature-Timestamp header")?;
+    let signature = signature.context("missing X-Signature header")?;
+
+    // reject replayed requests
+    let signed_at = timestamp.parse::<i64>()
+        .context("timestamp is not in unix seconds")?;
+    if (chrono::Utc::now().timestamp() - signed_at).abs() > MAX_SIGNATURE_AGE {
+        return Err(anyhow!("timestamp is more than {} seconds off", MAX_SIGNATURE_AGE));
+    }
+
+    let signature = decode_hex(signature.strip_prefix("sha256=").unwrap_or(signature))
+        .context("signature is not hex encoded")?;
+    let mut message = format!("{}\n{}\n{}\n", timestamp, method, path).into_bytes();
+    message.extend_from_slice(body);
+    hmac::verify(key, &message, &signature)
+        .map_err(|_| anyhow!("signature doesn't match"))
+}
+
+///
+/// Decode a hex string
+///
+/// # Arguments
+///
+/// * `hex` - The hex string
+///
+/// # Returns
+///
+/// The decoded bytes or None if the string isn't valid hex
+///
+#[cfg(feature = "admin-api")]
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len()).step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+///
+/// Compare two byte strings without leaking the position of the first difference
+///
+/// # Arguments
+///
+/// * `a` - The first byte string
+/// * `b` - The second byte string
+///
+/// # Returns
+///
+/// Whether the byte strings are equal
+///
+#[cfg(feature = "admin-api")]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+///
+/// Get the reason phrase of a status code used by the admin api
+///
+/// # Arguments
+///
+/// * `status` - The http status code
+///
+/// # Returns
+///
+/// The reason phrase
+///
+#[cfg(feature = "admin-api")]
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error"
+    }
+}
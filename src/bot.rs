@@ -1,11 +1,15 @@
 
-use std::sync::Arc;
+use std::{collections::HashSet, sync::{Arc, Mutex}};
 
-use log::{info, warn};
-use serenity::{all::{Command, CommandInteraction, EventHandler, GatewayIntents, Interaction}, async_trait, Client};
-use tokio::sync::RwLock;
+use anyhow::Context;
+use log::{error, info, warn};
+use serenity::{all::{Command, CommandDataOption, CommandDataOptionValue, CommandInteraction, ComponentInteraction, CreateInteractionResponse, CreateInteractionResponseMessage, EventHandler, GatewayIntents, Interaction, ModalInteraction, UserId}, async_trait, Client};
+use tokio::sync::{RwLock, Semaphore};
 
-use crate::{module::{random::RandomModule, reaction::ReactionModule, status::StatusModule, Module}, Configuration};
+use crate::{module::{random::RandomModule, reaction::ReactionModule, status::StatusModule, CommandKeys, Module}, metrics, shutdown, store::{self, Store}, Configuration};
+
+/// Commands of each user being handled, keyed by the user and the key of the command
+type RunningCommands = Arc<Mutex<HashSet<(UserId, String)>>>;
 
 ///
 /// The bot struct
@@ -14,11 +18,119 @@ struct Bot {
     /// The configuration
     config: Arc<RwLock<Configuration>>,
     /// The modules
-    modules: Arc<RwLock<Vec<Box<dyn Module>>>>
+    modules: Arc<RwLock<Vec<Box<dyn Module>>>>,
+    /// Permits for interactions being handled or waiting to be handled, bounding the queue depth
+    /// as the modules handle one interaction at a time
+    in_flight: Arc<Semaphore>,
+    /// Commands of each user being handled or waiting to be handled, if duplicates are rejected
+    running: Option<RunningCommands>,
+    /// Keys the commands of the modules are tracked by while running, collected when the bot is ready
+    command_keys: Mutex<Vec<CommandKeys>>
+}
+
+/// Response to interactions above the in-flight limit
+const BUSY_RESPONSE: &str = "I'm a little busy right now, please try again in a moment!";
+
+/// Response to a command that is already being handled for the user
+const DUPLICATE_RESPONSE: &str = "Hold on, I'm still working on your last command!";
+
+///
+/// A command of a user that is being handled, released when dropped
+///
+struct RunningCommand {
+    /// Commands of each user being handled
+    running: RunningCommands,
+    /// The user and the command
+    key: (UserId, String)
+}
+
+impl RunningCommand {
+
+    ///
+    /// Mark a command of a user as being handled
+    ///
+    /// # Arguments
+    ///
+    /// * `running` - Commands of each user being handled
+    /// * `keys` - Keys the commands of the modules are tracked by
+    /// * `command` - The command interaction
+    ///
+    /// # Returns
+    ///
+    /// The running command, or None if the same command of the user is already being handled
+    ///
+    fn start(running: &RunningCommands, keys: &[CommandKeys], command: &CommandInteraction) -> Option<Self> {
+        let key = (command.user.id, command_key(keys, command));
+        if !running.lock().unwrap_or_else(|e| e.into_inner()).insert(key.clone()) {
+            return None;
+        }
+        Some(Self { running: running.clone(), key })
+    }
+
+}
+
+///
+/// Get the key a command is tracked by while running
+///
+/// # Arguments
+///
+/// * `keys` - Keys the commands of the modules are tracked by
+/// * `command` - The command interaction
+///
+/// # Returns
+///
+/// The key the module maps the full path of the command to, or the full path itself
+///
+fn command_key(keys: &[CommandKeys], command: &CommandInteraction) -> String {
+    // the subcommands are part of the path, so different subcommands don't block each other
+    let mut path = command.data.name.clone();
+    let mut options: &[CommandDataOption] = &command.data.options;
+    while let Some((option, suboptions)) = options.first().and_then(|option| match &option.value {
+        CommandDataOptionValue::SubCommand(suboptions) | CommandDataOptionValue::SubCommandGroup(suboptions) => Some((option, suboptions)),
+        _ => None
+    }) {
+        path = format!("{} {}", path, option.name);
+        options = suboptions;
+    }
+
+    keys.iter()
+        .find_map(|keys| keys.read().unwrap_or_else(|e| e.into_inner()).get(&path).cloned())
+        .unwrap_or(path)
+}
+
+impl Drop for RunningCommand {
+    fn drop(&mut self) {
+        self.running.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.key);
+    }
 }
 
 impl Bot {
 
+    ///
+    /// Tell the user that the interaction can't be handled right now
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `interaction` - The interaction
+    ///
+    async fn respond_busy(&self, ctx: &serenity::client::Context, interaction: &Interaction) {
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(BUSY_RESPONSE)
+                .ephemeral(true)
+        );
+        let result = match interaction {
+            Interaction::Command(command) => command.create_response(&ctx.http, response).await,
+            Interaction::Component(component) => component.create_response(&ctx.http, response).await,
+            Interaction::Modal(modal) => modal.create_response(&ctx.http, response).await,
+            _ => Ok(())
+        };
+        if let Err(e) = result {
+            warn!(target: "bot", "failed to send busy response: {}", e);
+        }
+    }
+
     ///
     /// Try to handle a command with a module
     ///
@@ -44,6 +156,56 @@ impl Bot {
         }
     }
 
+    ///
+    /// Try to handle a component interaction with a module
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `component` - The component interaction
+    ///
+    async fn try_handle_component(&self, ctx: serenity::client::Context, component: ComponentInteraction) {
+        let mut modules = self.modules.write().await;
+
+        // find module that handles component
+        let module = modules.iter_mut().find(|module| module.handles_component(&component));
+
+        // handle component
+        if let Some(module) = module {
+            match module.handle_component(ctx, component).await {
+                Ok(_) => {},
+                Err(e) => warn!(target: "bot", "failed to handle component: {}", e)
+            };
+        } else {
+            warn!(target: "bot", "no module handles component: {}", component.data.custom_id);
+        }
+    }
+
+    ///
+    /// Try to handle a modal submission with a module
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `modal` - The modal interaction
+    ///
+    async fn try_handle_modal(&self, ctx: serenity::client::Context, modal: ModalInteraction) {
+        let mut modules = self.modules.write().await;
+
+        // find module that handles modal
+        let module = modules.iter_mut().find(|module| module.handles_modal(&modal));
+
+        // handle modal
+        if let Some(module) = module {
+            match module.handle_modal(ctx, modal).await {
+                Ok(_) => {},
+                Err(e) => warn!(target: "bot", "failed to handle modal: {}", e)
+            };
+        } else {
+            warn!(target: "bot", "no module handles modal: {}", modal.data.custom_id);
+        }
+    }
+
 }
 
 #[async_trait]
@@ -62,6 +224,9 @@ impl EventHandler for Bot {
             );
         }
 
+        // collect the keys of the commands, which the modules keep up to date
+        *self.command_keys.lock().unwrap_or_else(|e| e.into_inner()) = modules.iter().filter_map(|module| module.command_keys()).collect();
+
         // set commands
         Command::set_global_commands(&ctx.http, commands).await
         .expect("failed to set commands");
@@ -70,15 +235,225 @@ impl EventHandler for Bot {
     }
 
     async fn interaction_create(&self, ctx: serenity::all::Context, interaction: Interaction) {
-        if let Interaction::Command(command) = interaction {
-            self.try_handle_command(ctx, command).await;
+        // bound the number of interactions waiting for the modules, which handle them one at a time
+        // under the write lock, so this limits the queue depth rather than allowing parallel handling
+        let Ok(_permit) = self.in_flight.try_acquire() else {
+            warn!(target: "bot", "too many interactions in flight, rejecting interaction");
+            self.respond_busy(&ctx, &interaction).await;
+            return;
+        };
+
+        match interaction {
+            Interaction::Command(command) => {
+                // reject commands fired again before the first one was answered
+                let keys = self.command_keys.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                let running = match &self.running {
+                    Some(running) => match RunningCommand::start(running, &keys, &command) {
+                        Some(running) => Some(running),
+                        None => {
+                            info!(target: "bot", "rejecting duplicate command {} of @{}", command.data.name, command.user.name);
+                            let response = CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .content(DUPLICATE_RESPONSE)
+                                    .ephemeral(true)
+                            );
+                            if let Err(e) = command.create_response(&ctx.http, response).await {
+                                warn!(target: "bot", "failed to send duplicate response: {}", e);
+                            }
+                            return;
+                        }
+                    },
+                    None => None
+                };
+                self.try_handle_command(ctx, command).await;
+                drop(running);
+            },
+            Interaction::Component(component) => self.try_handle_component(ctx, component).await,
+            Interaction::Modal(modal) => self.try_handle_modal(ctx, modal).await,
+            _ => {}
+        }
+    }
+
+}
+
+///
+/// Builder for the bot
+///
+/// Modules are registered with [`BotBuilder::register_module`] and initialized
+/// in the order they were registered once the bot is connected to discord.
+///
+pub struct BotBuilder {
+    /// The configuration
+    config: Configuration,
+    /// The registered modules
+    modules: Vec<Box<dyn Module>>,
+    /// The store persisting shared state
+    store: Arc<dyn Store>
+}
+
+impl BotBuilder {
+
+    ///
+    /// Create a new bot builder without any modules
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration
+    ///
+    /// # Returns
+    ///
+    /// The bot builder
+    ///
+    /// # Errors
+    ///
+    /// An error if the store failed to open
+    ///
+    pub fn new(config: Configuration) -> Result<Self, anyhow::Error> {
+        let store = store::open(config.store.as_ref(), config.data_directory.as_deref())
+            .context("failed to open store")?;
+        Ok(Self { config, modules: vec![], store })
+    }
+
+    ///
+    /// Get the store persisting shared state, e.g. for creating modules
+    ///
+    /// # Returns
+    ///
+    /// The store
+    ///
+    pub fn store(&self) -> Arc<dyn Store> {
+        self.store.clone()
+    }
+
+    ///
+    /// Register a module
+    ///
+    /// # Arguments
+    ///
+    /// * `module` - The module to register
+    ///
+    /// # Returns
+    ///
+    /// The bot builder
+    ///
+    pub fn register_module(mut self, module: Box<dyn Module>) -> Self {
+        self.modules.push(module);
+        self
+    }
+
+    ///
+    /// Register the reaction, status and random modules
+    ///
+    /// # Returns
+    ///
+    /// The bot builder
+    ///
+    /// # Errors
+    ///
+    /// An error if a module failed to be created
+    ///
+    pub fn register_default_modules(self) -> Result<Self, anyhow::Error> {
+        info!(target: "bot", "creating reaction module");
+        let reaction_module = Box::new(ReactionModule::new(self.store())?);
+
+        info!(target: "bot", "creating status module");
+        let status_module = Box::new(StatusModule::new());
+
+        info!(target: "bot", "creating random module");
+        let random_module = Box::new(RandomModule::new());
+
+        Ok(self.register_module(reaction_module)
+            .register_module(status_module)
+            .register_module(random_module))
+    }
+
+    ///
+    /// Launch the bot
+    ///
+    /// # Returns
+    ///
+    /// Ok if the bot ran successfully
+    ///
+    /// # Errors
+    ///
+    /// An error if the bot failed to launch
+    ///
+    pub async fn launch(self) -> Result<(), anyhow::Error> {
+        info!(target: "bot", "launching bot with {} modules", self.modules.len());
+        let token = self.config.discord_token.clone();
+
+        // serve metrics
+        if let Some(settings) = self.config.metrics.clone() {
+            tokio::spawn(async move {
+                if let Err(e) = metrics::serve(settings).await {
+                    error!(target: "bot", "failed to serve metrics: {:?}", e);
+                }
+            });
+        }
+
+        let modules = Arc::new(RwLock::new(self.modules));
+
+        // serve admin api
+        #[cfg(feature = "admin-api")]
+        if let Some(settings) = self.config.admin_api.clone() {
+            let modules = modules.clone();
+            tokio::spawn(async move {
+                if let Err(e) = crate::admin::serve(settings, modules).await {
+                    error!(target: "bot", "failed to serve admin api: {:?}", e);
+                }
+            });
+        }
+        let bot = Bot {
+            in_flight: Arc::new(Semaphore::new(self.config.max_in_flight)),
+            running: self.config.reject_duplicate_commands.then(Arc::default),
+            command_keys: Mutex::default(),
+            config: Arc::new(RwLock::new(self.config)),
+            modules: modules.clone()
+        };
+
+        // launch bot
+        info!(target: "bot", "connecting to discord");
+        let mut client = Client::builder(token, GatewayIntents::empty())
+            .event_handler(bot)
+            .await
+            .expect("client failed to build");
+
+        // stop the bot on termination signals
+        let shard_manager = client.shard_manager.clone();
+        tokio::spawn(async move {
+            if let Err(e) = shutdown::wait_for_signal().await {
+                error!(target: "bot", "failed to wait for termination signal: {:?}", e);
+                return;
+            }
+
+            info!(target: "bot", "received termination signal, disconnecting from discord");
+            shard_manager.shutdown_all().await;
+        });
+
+        client.start()
+            .await
+            .expect("client failed to start");
+
+        // shut down modules
+        info!(target: "bot", "shutting down modules");
+        for module in modules.write().await.iter_mut() {
+            if let Err(e) = module.shutdown().await {
+                error!(target: "bot", "failed to shut down module: {:?}", e);
+            }
+        }
+
+        if let Err(e) = self.store.flush().await {
+            error!(target: "bot", "failed to flush store: {:?}", e);
         }
+
+        info!(target: "bot", "bot stopped");
+        Ok(())
     }
 
 }
 
 ///
-/// Launch the bot
+/// Launch the bot with the default modules
 ///
 /// # Arguments
 ///
@@ -93,39 +468,57 @@ impl EventHandler for Bot {
 /// An error if the bot failed to launch
 ///
 pub async fn launch_bot(config: Configuration) -> Result<(), anyhow::Error> {
-    info!(target: "bot", "launching bot");
-    let token = config.discord_token.clone();
-
-    // create modules
     info!(target: "bot", "creating modules");
-    let mut modules: Vec<Box<dyn Module>> = vec![];
+    BotBuilder::new(config)?
+        .register_default_modules()?
+        .launch()
+        .await
+}
 
-    info!(target: "bot", "creating reaction module");
-    let reaction_module = Box::new(ReactionModule::new()?);
-    modules.push(reaction_module);
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
 
-    info!(target: "bot", "creating status module");
-    let status_module = Box::new(StatusModule::new());
-    modules.push(status_module);
+    use crate::testing::CommandInteractionBuilder;
 
-    info!(target: "bot", "creating random module");
-    let random_module = Box::new(RandomModule::new());
-    modules.push(random_module);
+    use super::*;
 
-    let bot = Bot {
-        config: Arc::new(RwLock::new(config)),
-        modules: Arc::new(RwLock::new(modules))
-    };
+    /// Keys of a reaction usable with its alias and as a subcommand of a group
+    fn sample_keys() -> CommandKeys {
+        Arc::new(std::sync::RwLock::new(HashMap::from([
+            ("hug".to_string(), "hug".to_string()),
+            ("reactions hug".to_string(), "hug".to_string())
+        ])))
+    }
 
-    // launch bot
-    info!(target: "bot", "connecting to discord");
-    Client::builder(token, GatewayIntents::empty())
-        .event_handler(bot)
-        .await
-        .expect("client failed to build")
-        .start()
-        .await
-        .expect("client failed to start");
+    #[test]
+    fn tracks_commands_by_their_key_or_full_path() {
+        let keys = [sample_keys()];
+        let key = |command: CommandInteractionBuilder| command_key(&keys, &command.build().unwrap());
+
+        // reactions are tracked by name, whichever command they are used with
+        assert_eq!(key(CommandInteractionBuilder::new("hug")), "hug");
+        assert_eq!(key(CommandInteractionBuilder::new("reactions").subcommand("hug")), "hug");
+
+        // other commands are tracked by their full path
+        assert_eq!(key(CommandInteractionBuilder::new("reactions").subcommand("list")), "reactions list");
+        assert_eq!(key(CommandInteractionBuilder::new("reactions").group("server").subcommand("allow")), "reactions server allow");
+        assert_eq!(key(CommandInteractionBuilder::new("reactions").group("server").subcommand("deny")), "reactions server deny");
+    }
+
+    #[test]
+    fn rejects_the_same_reaction_of_a_user() {
+        let running = RunningCommands::default();
+        let keys = [sample_keys()];
+        let start = |command: CommandInteractionBuilder| RunningCommand::start(&running, &keys, &command.build().unwrap());
+
+        let first = start(CommandInteractionBuilder::new("hug"));
+        assert!(first.is_some());
+        assert!(start(CommandInteractionBuilder::new("reactions").subcommand("hug")).is_none());
+        assert!(start(CommandInteractionBuilder::new("hug").user(UserId::new(2))).is_some());
 
-    Ok(())
-}
\ No newline at end of file
+        // the reaction is released once it was handled
+        drop(first);
+        assert!(start(CommandInteractionBuilder::new("reactions").subcommand("hug")).is_some());
+    }
+}
@@ -43,3 +43,22 @@ pub fn rand() -> Colour {
     let index = rand::random::<usize>() % COLORS.len();
     COLORS[index]
 }
+
+///
+/// Parse a color from a hex string
+///
+/// # Arguments
+///
+/// * `hex` - The color in the form '#rrggbb' or 'rrggbb'
+///
+/// # Returns
+///
+/// The color or None if the string isn't a valid hex color
+///
+pub fn parse(hex: &str) -> Option<Colour> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok().map(Colour)
+}
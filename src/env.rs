@@ -0,0 +1,35 @@
+use anyhow::{anyhow, Context};
+
+///
+/// Expand environment variables in the form of '${NAME}' in a string
+///
+/// # Arguments
+///
+/// * `value` - The string to expand
+///
+/// # Returns
+///
+/// The expanded string
+///
+/// # Errors
+///
+/// If a variable is not set or a '${' is not closed
+///
+pub fn expand(value: &str) -> Result<String, anyhow::Error> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..].find('}')
+            .ok_or_else(|| anyhow!("unclosed '${{' in '{}'", value))?;
+        let name = &rest[start + 2..start + end];
+        let variable = std::env::var(name)
+            .with_context(|| format!("environment variable '{}' is not set", name))?;
+
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&variable);
+        rest = &rest[start + end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
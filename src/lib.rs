@@ -0,0 +1,288 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Context;
+
+use admin::AdminApiSettings;
+use metrics::MetricsSettings;
+use module::{CommandContexts, random::Random, reaction::{analytics::AnalyticsSettings, audit::AuditLogSettings, reaction_issues, seasonal::SeasonalTheme, DEFAULT_GROUP, BackendSettings, Reaction, ReactionSettings, ResponseProfile}};
+use serde::Deserialize;
+use serenity::all::UserId;
+use store::StoreSettings;
+
+pub mod admin;
+pub mod bot;
+pub mod color;
+pub mod env;
+pub mod metrics;
+pub mod migration;
+pub mod module;
+pub mod persistence;
+pub mod shutdown;
+pub mod store;
+pub mod task;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+pub const BOT_USER_AGENT: &str = "puppy-rs/3.0.0 (gh/PancakeTAS/puppy-rs)";
+
+/// Configuration file structure
+#[derive(Deserialize)]
+pub struct Configuration {
+    #[serde(default = "default_version")]
+    pub version: u64,
+    pub status_messages: Vec<String>,
+    pub random: Random,
+    pub reactions: Vec<Reaction>,
+    #[serde(default)]
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, HashMap<String, ResponseProfile>>,
+    #[serde(default)]
+    pub seasonal: Vec<SeasonalTheme>,
+    #[serde(default)]
+    pub command_contexts: CommandContexts,
+    #[serde(default)]
+    pub reaction_settings: ReactionSettings,
+    #[serde(default)]
+    pub backends: HashMap<String, BackendSettings>,
+    #[serde(default)]
+    pub analytics: Option<AnalyticsSettings>,
+    #[serde(default)]
+    pub audit_log: Option<AuditLogSettings>,
+    #[serde(default)]
+    pub data_directory: Option<PathBuf>,
+    #[serde(default)]
+    pub store: Option<StoreSettings>,
+    #[serde(default)]
+    pub metrics: Option<MetricsSettings>,
+    #[serde(default)]
+    pub admin_api: Option<AdminApiSettings>,
+    #[serde(default)]
+    pub owners: Vec<UserId>,
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+    #[serde(default)]
+    pub reject_duplicate_commands: bool,
+    #[serde(default)]
+    pub discord_token: String,
+    pub log_level: String,
+    #[serde(default)]
+    pub log_filters: HashMap<String, String>,
+    #[serde(default)]
+    pub environments: HashMap<String, Environment>
+}
+
+/// Named environment overriding the token and development settings of the configuration
+#[derive(Deserialize, Clone)]
+pub struct Environment {
+    /// Token of the bot to run as
+    #[serde(default)]
+    pub discord_token: String,
+    /// Log level overriding the configured log level
+    #[serde(default)]
+    pub log_level: Option<String>,
+    /// User ids of the bot owners overriding the configured owners
+    #[serde(default)]
+    pub owners: Option<Vec<UserId>>,
+    /// Directory persisting state overriding the configured data directory
+    #[serde(default)]
+    pub data_directory: Option<PathBuf>,
+    /// Response profile overriding the selected profile
+    #[serde(default)]
+    pub profile: Option<String>
+}
+
+impl Configuration {
+
+    ///
+    /// Parse a configuration file, upgrading older versions to the current one
+    ///
+    /// Older configurations aren't rewritten, the changes are only applied while parsing.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The contents of the configuration file
+    ///
+    /// # Returns
+    ///
+    /// The configuration and a description of every migrated change
+    ///
+    /// # Errors
+    ///
+    /// If the file isn't valid yaml, can't be migrated or doesn't match the configuration structure
+    ///
+    pub fn parse(source: &str) -> Result<(Self, Vec<String>), anyhow::Error> {
+        let mut value: serde_yml::Value = serde_yml::from_str(source)
+            .context("invalid yaml")?;
+        let changes = migration::migrate(&mut value)
+            .context("failed to migrate configuration")?;
+        let config = serde_yml::from_value(value)
+            .context("invalid configuration structure")?;
+        Ok((config, changes))
+    }
+
+    ///
+    /// Apply the settings of a named environment
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the environment
+    ///
+    /// # Errors
+    ///
+    /// If the environment doesn't exist or has no token
+    ///
+    pub fn select_environment(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        let environment = self.environments.get(name).cloned()
+            .ok_or_else(|| anyhow::anyhow!("environment '{}' does not exist", name))?;
+        if environment.discord_token.is_empty() {
+            return Err(anyhow::anyhow!("environment '{}' has no discord_token", name));
+        }
+
+        self.discord_token = environment.discord_token;
+        if let Some(log_level) = environment.log_level {
+            self.log_level = log_level;
+        }
+        if let Some(owners) = environment.owners {
+            self.owners = owners;
+        }
+        if let Some(data_directory) = environment.data_directory {
+            self.data_directory = Some(data_directory);
+        }
+        if let Some(profile) = environment.profile {
+            self.profile = Some(profile);
+        }
+        Ok(())
+    }
+
+    ///
+    /// Validate the configuration
+    ///
+    /// # Errors
+    ///
+    /// The first issue found by [`Configuration::issues`]
+    ///
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        match self.issues().into_iter().next() {
+            Some(issue) => Err(issue),
+            None => Ok(())
+        }
+    }
+
+    ///
+    /// Find all issues with the configuration
+    ///
+    /// Checks the command contexts, the status messages, the random commands, the token and environments, the admin api,
+    /// the reaction settings, the response profiles, every reaction, the seasonal themes, unknown backends and colliding command names.
+    ///
+    /// # Returns
+    ///
+    /// A list of all issues, empty if the configuration is valid
+    ///
+    pub fn issues(&self) -> Vec<anyhow::Error> {
+        let mut issues = Vec::new();
+
+        if let Err(e) = self.command_contexts.validate() {
+            issues.push(e.context("invalid command contexts"));
+        }
+
+        if self.status_messages.is_empty() {
+            issues.push(anyhow::anyhow!("status_messages is empty"));
+        }
+
+        if let Err(e) = self.random.validate() {
+            issues.push(e.context("invalid random commands"));
+        }
+
+        if self.discord_token.is_empty() && self.environments.is_empty() {
+            issues.push(anyhow::anyhow!("discord_token is not set"));
+        }
+        for (name, _) in self.environments.iter().filter(|(_, environment)| environment.discord_token.is_empty()) {
+            issues.push(anyhow::anyhow!("environment '{}' has no discord_token", name));
+        }
+
+        if self.max_in_flight == 0 {
+            issues.push(anyhow::anyhow!("max_in_flight must be at least 1"));
+        }
+
+        #[cfg(not(feature = "sqlite-store"))]
+        if matches!(self.store, Some(StoreSettings::Sqlite { .. })) {
+            issues.push(anyhow::anyhow!("the sqlite store requires the 'sqlite-store' feature"));
+        }
+
+        #[cfg(not(feature = "admin-api"))]
+        if self.admin_api.is_some() {
+            issues.push(anyhow::anyhow!("admin_api requires the 'admin-api' feature"));
+        }
+
+        if let Some(audit_log) = self.audit_log.as_ref().filter(|audit_log| !(0.0..=1.0).contains(&audit_log.sample_rate)) {
+            issues.push(anyhow::anyhow!("sample_rate of the audit log must be between 0.0 and 1.0, but is {}", audit_log.sample_rate));
+        }
+
+        if let Err(e) = self.reaction_settings.validate() {
+            issues.push(e.context("invalid reaction settings"));
+        }
+
+        if let Some(profile) = &self.profile {
+            if !self.profiles.contains_key(profile) {
+                issues.push(anyhow::anyhow!("selected profile '{}' does not exist", profile));
+            }
+        }
+        for (profile, responses) in &self.profiles {
+            for name in responses.keys().filter(|name| !self.reactions.iter().any(|r| &r.name == *name)) {
+                issues.push(anyhow::anyhow!("profile '{}' has responses for unknown reaction '{}'", profile, name));
+            }
+        }
+
+        for (id, _) in self.backends.iter().filter(|(_, settings)| settings.timeout == Some(0)) {
+            issues.push(anyhow::anyhow!("timeout of backend '{}' must be at least 1 second", id));
+        }
+        for (id, proxy) in self.backends.iter().filter_map(|(id, settings)| settings.proxy.as_ref().map(|proxy| (id, proxy))) {
+            if let Err(e) = reqwest::Url::parse(proxy) {
+                issues.push(anyhow::anyhow!("proxy of backend '{}' is not a valid url: {}", id, e));
+            }
+        }
+
+        for name in self.reaction_settings.warm_priority.iter().filter(|name| !self.reactions.iter().any(|r| &r.name == *name)) {
+            issues.push(anyhow::anyhow!("warm_priority contains unknown reaction '{}'", name));
+        }
+
+        for category in self.reaction_settings.category_colors.keys() {
+            if !self.reactions.iter().any(|r| r.category.as_deref().unwrap_or(DEFAULT_GROUP) == category) {
+                issues.push(anyhow::anyhow!("category_colors has a color for unknown category '{}'", category));
+            }
+        }
+
+        issues.extend(reaction_issues(&self.active_reactions(), &self.seasonal, &self.backends, &self.reaction_settings.alias_description));
+
+        issues
+    }
+
+    ///
+    /// Get the reactions with the responses of the selected profile
+    ///
+    /// # Returns
+    ///
+    /// The reactions, unchanged if no profile is selected
+    ///
+    pub fn active_reactions(&self) -> Vec<Reaction> {
+        let mut reactions = self.reactions.clone();
+        if let Some(profile) = self.profile.as_ref().and_then(|profile| self.profiles.get(profile)) {
+            for reaction in &mut reactions {
+                if let Some(responses) = profile.get(&reaction.name) {
+                    reaction.apply_profile(responses);
+                }
+            }
+        }
+        reactions
+    }
+
+}
+
+fn default_version() -> u64 {
+    migration::CONFIG_VERSION
+}
+
+fn default_max_in_flight() -> usize {
+    64
+}
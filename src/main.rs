@@ -1,28 +1,29 @@
-use std::{fs::File, path::PathBuf, str::FromStr};
+use std::{collections::HashMap, fs, path::PathBuf, str::FromStr};
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use clap::{Arg, ArgAction, Command};
 use colog::format::CologStyle;
 use colored::Colorize;
-use log::{Level, LevelFilter};
-use module::{random::Random, reaction::Reaction};
-use serde::Deserialize;
-
-pub mod bot;
-pub mod color;
-pub mod module;
-
-pub const BOT_USER_AGENT: &str = "puppy-rs/3.0.0 (gh/PancakeTAS/puppy-rs)";
-
-/// Configuration file structure
-#[derive(Deserialize)]
-pub struct Configuration {
-    pub status_messages: Vec<String>,
-    pub random: Random,
-    pub reactions: Vec<Reaction>,
-    pub discord_token: String,
-    pub log_level: String
-}
+use log::{warn, Level, LevelFilter};
+use puppy_rs::{bot, migration::CONFIG_VERSION, Configuration};
+
+/// Targets the bot logs under
+const LOG_TARGETS: &[&str] = &[
+    "module/reaction/backend/nekosbest",
+    "module/reaction/backend/otakugifs",
+    "module/reaction/backend/http",
+    "module/reaction/backend/avatar",
+    "module/reaction/backend/feed",
+    "module/reaction/backend",
+    "module/reaction/analytics",
+    "module/reaction",
+    "module/status",
+    "module/random",
+    "store",
+    "metrics",
+    "admin",
+    "bot"
+];
 
 pub struct CustomPrefixToken;
 
@@ -64,34 +65,155 @@ async fn main() -> Result<(), anyhow::Error> {
                 .action(ArgAction::Set)
                 .num_args(1)
                 .value_parser(clap::value_parser!(PathBuf))
+        )
+        .arg(
+            Arg::new("env")
+                .short('e')
+                .long("env")
+                .help("select a named environment of the configuration file")
+                .value_name("name")
+                .action(ArgAction::Set)
+                .num_args(1)
+        )
+        .arg(
+            Arg::new("check-config")
+                .long("check-config")
+                .help("validate the configuration file without connecting to discord")
+                .value_name("path")
+                .action(ArgAction::Set)
+                .num_args(1)
+                .value_parser(clap::value_parser!(PathBuf))
         );
 
     let matches = cmd.get_matches();
+    let environment = matches.get_one::<String>("env");
+
+    // validate the configuration only
+    if let Some(path) = matches.get_one::<PathBuf>("check-config") {
+        return check_config(path, environment.map(String::as_str));
+    }
 
     // read configuration file
     let default_path = PathBuf::from("config.yml");
     let config_path = matches.get_one::<PathBuf>("config").unwrap_or(&default_path);
-    let config_file = File::open(config_path)
+    let source = fs::read_to_string(config_path)
         .context("unable to open configuration file")?;
-    let config: Configuration = serde_yml::from_reader(config_file)
+    let (mut config, migrated) = Configuration::parse(&source)
         .context("unable to parse configuration file")?;
+    if let Some(environment) = environment {
+        config.select_environment(environment)
+            .context("unable to select environment")?;
+    }
+    config.validate()
+        .context("invalid configuration")?;
+    if config.discord_token.is_empty() {
+        return Err(anyhow!("discord_token is not set, select an environment with --env"));
+    }
 
     // initialize the logger
-    let level = if matches.get_flag("verbose") { LevelFilter::Trace } else { LevelFilter::from_str(&config.log_level).context("unable to parse log level")? };
-    colog::default_builder()
-        .filter_level(LevelFilter::Off) // don't filter anything by default
-        .filter_module("module/reaction/backend/nekosbest", level)
-        .filter_module("module/reaction/backend/otakugifs", level)
-        .filter_module("module/reaction/backend", level)
-        .filter_module("module/reaction", level)
-        .filter_module("module/status", level)
-        .filter_module("module/random", level)
-        .filter_module("bot", level)
-        .format(colog::formatter(CustomPrefixToken))
+    let verbose = matches.get_flag("verbose");
+    let level = if verbose { LevelFilter::Trace } else { LevelFilter::from_str(&config.log_level).context("unable to parse log level")? };
+    let filters = parse_log_filters(&config.log_filters)
+        .context("invalid log filters")?;
+    let mut builder = colog::default_builder();
+    builder.filter_level(LevelFilter::Off); // don't filter anything by default
+    for target in LOG_TARGETS {
+        // use the most specific filter matching the target
+        let filter = filters.iter()
+            .filter(|(name, _)| target == name || target.starts_with(&format!("{}/", name)))
+            .max_by_key(|(name, _)| name.len());
+        let level = match filter {
+            Some((_, filter)) if !verbose => *filter,
+            _ => level
+        };
+        builder.filter_module(target, level);
+    }
+    builder.format(colog::formatter(CustomPrefixToken))
         .init();
 
+    // report the changes needed to read an older configuration
+    for change in &migrated {
+        warn!(target: "bot", "migrated configuration from {}", change);
+    }
+    if !migrated.is_empty() {
+        warn!(target: "bot", "apply these changes and set 'version: {}' in the configuration file to silence these warnings", CONFIG_VERSION);
+    }
+
     // create the bot
     bot::launch_bot(config).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+///
+/// Validate a configuration file and print a report of all issues
+///
+/// # Arguments
+///
+/// * `path` - The path of the configuration file
+/// * `environment` - The environment to validate the configuration with, if any
+///
+/// # Errors
+///
+/// If the configuration can't be read or has issues
+///
+fn check_config(path: &PathBuf, environment: Option<&str>) -> Result<(), anyhow::Error> {
+    let source = fs::read_to_string(path)
+        .context("unable to open configuration file")?;
+    let (mut config, migrated) = Configuration::parse(&source)
+        .context("unable to parse configuration file")?;
+    for change in &migrated {
+        println!("{} migrated from {}", "!".yellow(), change);
+    }
+
+    let mut issues = Vec::new();
+    if let Some(environment) = environment {
+        if let Err(e) = config.select_environment(environment) {
+            issues.push(e);
+        }
+    }
+    issues.extend(config.issues());
+    if let Err(e) = LevelFilter::from_str(&config.log_level) {
+        issues.push(anyhow!("unable to parse log level '{}': {}", config.log_level, e));
+    }
+    if let Err(e) = parse_log_filters(&config.log_filters) {
+        issues.push(e.context("invalid log filters"));
+    }
+
+    if issues.is_empty() {
+        println!("{} {} is valid", "✓".green(), path.display());
+        return Ok(());
+    }
+
+    for issue in &issues {
+        println!("{} {:#}", "✗".red(), issue);
+    }
+    Err(anyhow!("found {} issues in {}", issues.len(), path.display()))
+}
+
+///
+/// Parse the per-target log filters
+///
+/// # Arguments
+///
+/// * `filters` - A map of log targets to log levels
+///
+/// # Returns
+///
+/// A list of log targets and their level filters
+///
+/// # Errors
+///
+/// If a target is unknown or a level can't be parsed
+///
+fn parse_log_filters(filters: &HashMap<String, String>) -> Result<Vec<(String, LevelFilter)>, anyhow::Error> {
+    filters.iter().map(|(target, level)| {
+        if !LOG_TARGETS.iter().any(|t| t == target || t.starts_with(&format!("{}/", target))) {
+            return Err(anyhow!("unknown log target '{}'", target));
+        }
+
+        let level = LevelFilter::from_str(level)
+            .with_context(|| format!("unable to parse log level '{}' of target '{}'", level, target))?;
+        Ok((target.clone(), level))
+    }).collect()
+}
@@ -0,0 +1,134 @@
+use std::{collections::BTreeMap, fmt::Write as _, net::SocketAddr, sync::Mutex};
+
+use anyhow::Context;
+use log::{debug, info, warn};
+use serde::Deserialize;
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}};
+
+/// Struct to hold the settings of the metrics endpoint
+#[derive(Deserialize, Clone)]
+pub struct MetricsSettings {
+    /// Address to serve the metrics on, e.g. '127.0.0.1:9090'
+    pub address: SocketAddr
+}
+
+/// Gauges by name and labels
+static GAUGES: Mutex<BTreeMap<String, BTreeMap<String, f64>>> = Mutex::new(BTreeMap::new());
+
+///
+/// Set the value of a gauge
+///
+/// # Arguments
+///
+/// * `name` - The name of the gauge
+/// * `labels` - The label names and values of the gauge
+/// * `value` - The new value of the gauge
+///
+pub fn set_gauge(name: &str, labels: &[(&str, &str)], value: f64) {
+    let labels = labels.iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, value.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let mut gauges = GAUGES.lock().unwrap_or_else(|e| e.into_inner());
+    gauges.entry(name.to_string()).or_default().insert(labels, value);
+}
+
+///
+/// Render all gauges in the prometheus text format
+///
+/// # Returns
+///
+/// The rendered gauges
+///
+pub fn render() -> String {
+    let gauges = GAUGES.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut output = String::new();
+    for (name, series) in gauges.iter() {
+        let _ = writeln!(output, "# TYPE {} gauge", name);
+        for (labels, value) in series {
+            if labels.is_empty() {
+                let _ = writeln!(output, "{} {}", name, value);
+            } else {
+                let _ = writeln!(output, "{}{{{}}} {}", name, labels, value);
+            }
+        }
+    }
+    output
+}
+
+///
+/// Serve the metrics over http until the task is aborted
+///
+/// # Arguments
+///
+/// * `settings` - The metrics settings
+///
+/// # Errors
+///
+/// If the address cannot be bound
+///
+pub async fn serve(settings: MetricsSettings) -> Result<(), anyhow::Error> {
+    let listener = TcpListener::bind(settings.address).await
+        .context("failed to bind metrics address")?;
+    info!(target: "metrics", "serving metrics on http://{}/metrics", settings.address);
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!(target: "metrics", "failed to accept connection: {:?}", e);
+                continue;
+            }
+        };
+
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream).await {
+                debug!(target: "metrics", "failed to respond to {}: {:?}", peer, e);
+            }
+        });
+    }
+}
+
+///
+/// Respond to a single http request
+///
+/// # Arguments
+///
+/// * `stream` - The connection
+///
+/// # Errors
+///
+/// If the request cannot be read or the response cannot be written
+///
+async fn respond(mut stream: TcpStream) -> Result<(), anyhow::Error> {
+    // read the request head
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        let read = stream.read(&mut buffer).await
+            .context("failed to read request")?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    // route the request
+    let request = String::from_utf8_lossy(&request);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let (status, body) = match path {
+        "/metrics" => ("200 OK", render()),
+        _ => ("404 Not Found", "not found\n".to_string())
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body
+    );
+    stream.write_all(response.as_bytes()).await
+        .context("failed to write response")?;
+    stream.shutdown().await
+        .context("failed to close connection")
+}
@@ -0,0 +1,192 @@
+use anyhow::{anyhow, Context};
+use serde_yml::Value;
+
+/// Version of the configuration schema this build reads
+pub const CONFIG_VERSION: u64 = 3;
+
+/// A migration upgrading a configuration by one version, returning a description of every change
+type Migration = fn(&mut Value) -> Vec<String>;
+
+/// Migrations by the version they upgrade from, starting at version 1
+const MIGRATIONS: &[Migration] = &[
+    migrate_alias_descriptions,
+    migrate_typing_indicator
+];
+
+///
+/// Upgrade a configuration to the current version
+///
+/// Configurations without a version are treated as version 1.
+///
+/// # Arguments
+///
+/// * `config` - The parsed configuration file
+///
+/// # Returns
+///
+/// A description of every migrated change, empty if the configuration is up to date
+///
+/// # Errors
+///
+/// If the configuration isn't a mapping or its version is invalid or newer than supported
+///
+pub fn migrate(config: &mut Value) -> Result<Vec<String>, anyhow::Error> {
+    let mapping = config.as_mapping_mut()
+        .context("configuration is not a mapping")?;
+    let version = match mapping.get("version") {
+        None => 1,
+        Some(version) => version.as_u64().filter(|version| *version >= 1)
+            .ok_or_else(|| anyhow!("configuration version must be a positive integer"))?
+    };
+    if version > CONFIG_VERSION {
+        return Err(anyhow!("configuration version {} is newer than the supported version {}", version, CONFIG_VERSION));
+    }
+
+    let mut changes = Vec::new();
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        let from = from as u64 + 1;
+        changes.extend(migration(config).into_iter()
+            .map(|change| format!("version {} to {}: {}", from, from + 1, change)));
+    }
+
+    if let Some(mapping) = config.as_mapping_mut() {
+        mapping.insert(Value::from("version"), Value::from(CONFIG_VERSION));
+    }
+    Ok(changes)
+}
+
+///
+/// Point alias descriptions at the group command of their reaction
+///
+/// Version 2 sorts reactions into commands named after their category, so the
+/// '/reaction' in alias descriptions is replaced with the '{command}' placeholder.
+///
+/// # Arguments
+///
+/// * `config` - The configuration of version 1
+///
+/// # Returns
+///
+/// A description of every replaced alias description
+///
+fn migrate_alias_descriptions(config: &mut Value) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut replace = |description: Option<&mut Value>, location: String| {
+        if let Some(Value::String(description)) = description {
+            if description.contains("/reaction {name}") {
+                *description = description.replace("/reaction {name}", "/{command} {name}");
+                changes.push(format!("replaced '/reaction {{name}}' with '/{{command}} {{name}}' in {}", location));
+            }
+        }
+    };
+
+    replace(config.get_mut("reaction_settings").and_then(|settings| settings.get_mut("alias_description")),
+        "reaction_settings.alias_description".to_string());
+    if let Some(Value::Sequence(reactions)) = config.get_mut("reactions") {
+        for reaction in reactions {
+            let name = reaction.get("name").and_then(Value::as_str).unwrap_or("?").to_string();
+            replace(reaction.get_mut("alias_description"), format!("the alias_description of reaction '{}'", name));
+        }
+    }
+    changes
+}
+
+///
+/// Turn the typing indicator duration into a flag
+///
+/// Version 3 no longer waits before posting a reaction, as the wait held up every other interaction,
+/// so the duration in milliseconds became a flag enabling the indicator.
+///
+/// # Arguments
+///
+/// * `config` - The configuration of version 2
+///
+/// # Returns
+///
+/// A description of the replaced duration
+///
+fn migrate_typing_indicator(config: &mut Value) -> Vec<String> {
+    let indicator = config.get_mut("reaction_settings").and_then(|settings| settings.get_mut("typing_indicator"));
+    match indicator {
+        Some(indicator) if indicator.is_number() => {
+            let duration = indicator.as_f64().unwrap_or_default();
+            let enabled = duration > 0.0;
+            let change = format!("replaced the typing_indicator duration of {}ms with {} in reaction_settings", duration, enabled);
+            *indicator = Value::Bool(enabled);
+            vec![change]
+        },
+        _ => Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Value {
+        serde_yml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn migrates_alias_descriptions_from_version_1() {
+        let mut config = parse("
+reaction_settings:
+  alias_description: Alias of /reaction {name}
+reactions:
+  - name: hug
+    alias_description: Same as /reaction {name}
+  - name: pat
+    alias_description: Pat someone
+");
+        let changes = migrate_alias_descriptions(&mut config);
+        assert_eq!(changes, [
+            "replaced '/reaction {name}' with '/{command} {name}' in reaction_settings.alias_description",
+            "replaced '/reaction {name}' with '/{command} {name}' in the alias_description of reaction 'hug'"
+        ]);
+        assert_eq!(config["reaction_settings"]["alias_description"].as_str(), Some("Alias of /{command} {name}"));
+        assert_eq!(config["reactions"][0]["alias_description"].as_str(), Some("Same as /{command} {name}"));
+        assert_eq!(config["reactions"][1]["alias_description"].as_str(), Some("Pat someone"));
+    }
+
+    #[test]
+    fn migrates_typing_indicator_from_version_2() {
+        let mut config = parse("reaction_settings:\n  typing_indicator: 1500\n");
+        assert_eq!(migrate_typing_indicator(&mut config), ["replaced the typing_indicator duration of 1500ms with true in reaction_settings"]);
+        assert_eq!(config["reaction_settings"]["typing_indicator"], Value::Bool(true));
+
+        let mut config = parse("reaction_settings:\n  typing_indicator: 0\n");
+        migrate_typing_indicator(&mut config);
+        assert_eq!(config["reaction_settings"]["typing_indicator"], Value::Bool(false));
+
+        // flags and missing settings are left alone
+        let mut config = parse("reaction_settings:\n  typing_indicator: true\n");
+        assert!(migrate_typing_indicator(&mut config).is_empty());
+        assert!(migrate_typing_indicator(&mut parse("reaction_settings: {}\n")).is_empty());
+    }
+
+    #[test]
+    fn migrates_from_each_version() {
+        let yaml = "reaction_settings:\n  alias_description: /reaction {name}\n  typing_indicator: 500\n";
+
+        // configurations without a version run every migration
+        let mut config = parse(yaml);
+        assert_eq!(migrate(&mut config).unwrap().len(), 2);
+        assert_eq!(config["version"].as_u64(), Some(CONFIG_VERSION));
+
+        let mut config = parse(&format!("version: 2\n{}", yaml));
+        let changes = migrate(&mut config).unwrap();
+        assert_eq!(changes, ["version 2 to 3: replaced the typing_indicator duration of 500ms with true in reaction_settings"]);
+        assert_eq!(config["reaction_settings"]["alias_description"].as_str(), Some("/reaction {name}"));
+
+        let mut config = parse(&format!("version: {}\n{}", CONFIG_VERSION, yaml));
+        assert!(migrate(&mut config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_versions() {
+        assert!(migrate(&mut parse("version: 0\n")).is_err());
+        assert!(migrate(&mut parse("version: two\n")).is_err());
+        assert!(migrate(&mut parse(&format!("version: {}\n", CONFIG_VERSION + 1))).is_err());
+        assert!(migrate(&mut parse("- not a mapping\n")).is_err());
+    }
+}
@@ -1,12 +1,278 @@
-use serenity::{all::{CommandInteraction, Context, CreateCommand}, async_trait};
+use std::{collections::HashMap, sync::{Arc, RwLock}};
 
-use crate::Configuration;
+use anyhow::{anyhow, Context as _};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use serenity::{all::{CommandInteraction, ComponentInteraction, Context, CreateCommand, CreateInteractionResponse, CreateInteractionResponseMessage, InstallationContext, InteractionContext, ModalInteraction}, async_trait, http::HttpError};
+
+use crate::{admin::{AdminRequest, AdminResponse}, Configuration};
 
 pub mod random;
 pub mod reaction;
 pub mod status;
 
+/// Full paths of commands mapped to the key they are tracked by while running, shared with the bot
+pub type CommandKeys = Arc<RwLock<HashMap<String, String>>>;
+
+/// Where a command can be installed
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrationType {
+    /// Installed to a guild
+    Guild,
+    /// Installed to a user account
+    User
+}
+
+impl From<IntegrationType> for InstallationContext {
+    fn from(value: IntegrationType) -> Self {
+        match value {
+            IntegrationType::Guild => InstallationContext::Guild,
+            IntegrationType::User => InstallationContext::User
+        }
+    }
+}
+
+/// Where a command can be used
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageContext {
+    /// Within guilds
+    Guild,
+    /// Within direct messages with the bot
+    BotDm,
+    /// Within group chats and direct messages other than with the bot
+    PrivateChannel
+}
+
+impl From<UsageContext> for InteractionContext {
+    fn from(value: UsageContext) -> Self {
+        match value {
+            UsageContext::Guild => InteractionContext::Guild,
+            UsageContext::BotDm => InteractionContext::BotDm,
+            UsageContext::PrivateChannel => InteractionContext::PrivateChannel
+        }
+    }
+}
+
+/// Struct to hold where a command can be installed and used
+#[derive(Deserialize, Serialize, Clone)]
+pub struct CommandContexts {
+    /// Where the command can be installed
+    pub integration_types: Vec<IntegrationType>,
+    /// Where the command can be used
+    pub contexts: Vec<UsageContext>
+}
+
+impl Default for CommandContexts {
+    fn default() -> Self {
+        Self {
+            integration_types: vec![IntegrationType::User, IntegrationType::Guild],
+            contexts: vec![UsageContext::PrivateChannel, UsageContext::Guild, UsageContext::BotDm]
+        }
+    }
+}
+
+impl CommandContexts {
+
+    ///
+    /// Validate that the contexts are consistent
+    ///
+    /// # Errors
+    ///
+    /// If no integration type or context is given, or a context is unreachable with the given integration types
+    ///
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.integration_types.is_empty() {
+            return Err(anyhow!("no integration types"));
+        }
+
+        if self.contexts.is_empty() {
+            return Err(anyhow!("no contexts"));
+        }
+
+        // private channels can only be reached by user installations
+        if self.contexts.contains(&UsageContext::PrivateChannel) && !self.integration_types.contains(&IntegrationType::User) {
+            return Err(anyhow!("the 'private_channel' context requires the 'user' integration type"));
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Combine these contexts with another set of contexts
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other contexts
+    ///
+    /// # Returns
+    ///
+    /// Contexts allowing everything either set allows
+    ///
+    pub fn union(&self, other: &CommandContexts) -> CommandContexts {
+        let mut union = self.clone();
+        for integration_type in &other.integration_types {
+            if !union.integration_types.contains(integration_type) {
+                union.integration_types.push(*integration_type);
+            }
+        }
+        for context in &other.contexts {
+            if !union.contexts.contains(context) {
+                union.contexts.push(*context);
+            }
+        }
+        union
+    }
+
+    ///
+    /// Check if a command can be used in an interaction context
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context of the interaction, if known
+    ///
+    /// # Returns
+    ///
+    /// True if the command can be used in the context or the context is unknown
+    ///
+    pub fn allows(&self, context: Option<InteractionContext>) -> bool {
+        context.is_none_or(|context| self.contexts.iter().any(|c| InteractionContext::from(*c) == context))
+    }
+
+    ///
+    /// Apply the contexts to a command
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - The command creation request
+    ///
+    /// # Returns
+    ///
+    /// The command creation request with the contexts applied
+    ///
+    pub fn apply(&self, cmd: CreateCommand) -> CreateCommand {
+        cmd.integration_types(self.integration_types.iter().map(|i| (*i).into()).collect())
+            .contexts(self.contexts.iter().map(|c| (*c).into()).collect())
+    }
+
+}
+
+///
+/// Pick a random item from a slice
+///
+/// # Arguments
+///
+/// * `rng` - The random number generator
+/// * `items` - The items to pick from
+///
+/// # Returns
+///
+/// A random item or None if the slice is empty
+///
+pub fn pick_random<'a, T, R: Rng + ?Sized>(rng: &mut R, items: &'a [T]) -> Option<&'a T> {
+    if items.is_empty() {
+        return None;
+    }
+
+    items.get(rng.gen_range(0..items.len()))
+}
+
+///
+/// Pick a random item from a slice, preferring items with a higher weight
+///
+/// # Arguments
+///
+/// * `rng` - The random number generator
+/// * `items` - The items to pick from
+/// * `weight` - The weight of an item
+///
+/// # Returns
+///
+/// A random item or None if the slice is empty or no item has a positive weight
+///
+pub fn pick_weighted<'a, T, R: Rng + ?Sized>(rng: &mut R, items: &'a [T], weight: impl Fn(&T) -> f64) -> Option<&'a T> {
+    items.choose_weighted(rng, weight).ok()
+}
+
+///
+/// Respond to a command with a message only visible to the user
+///
+/// # Arguments
+///
+/// * `ctx` - The context
+/// * `cmd` - The command interaction
+/// * `content` - The content of the message
+///
+/// # Errors
+///
+/// If the response fails to send
+///
+pub async fn respond_ephemeral(ctx: &Context, cmd: &CommandInteraction, content: impl Into<String>) -> Result<(), anyhow::Error> {
+    cmd.create_response(&ctx.http, CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true)
+    )).await.context("failed to send response")
+}
+
+/// Discord error code when the bot can't access a channel
+const MISSING_ACCESS: isize = 50001;
+
+/// Discord error code when the bot lacks a permission
+const MISSING_PERMISSIONS: isize = 50013;
+
+///
+/// Check whether a request failed for a reason that may go away when retried
+///
+/// # Arguments
+///
+/// * `error` - The error of the request
+///
+/// # Returns
+///
+/// Whether the request failed on the network or discord answered with a server error
+///
+pub fn is_transient_error(error: &serenity::Error) -> bool {
+    match error {
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(response)) => response.status_code.is_server_error(),
+        serenity::Error::Http(HttpError::Request(_)) | serenity::Error::Io(_) => true,
+        _ => false
+    }
+}
+
+///
+/// Check whether a request failed because the bot lacks permissions
+///
+/// # Arguments
+///
+/// * `error` - The error of the request
+///
+/// # Returns
+///
+/// Whether discord rejected the request for missing access or permissions
+///
+pub fn is_permission_error(error: &serenity::Error) -> bool {
+    matches!(error, serenity::Error::Http(HttpError::UnsuccessfulRequest(response))
+        if matches!(response.error.code, MISSING_ACCESS | MISSING_PERMISSIONS))
+}
+
+///
 /// The module trait.
+///
+/// Modules can be implemented outside of this crate and registered with
+/// [`crate::bot::BotBuilder::register_module`]. The lifecycle of a module is:
+///
+/// 1. [`Module::init`] is called once the bot is connected to discord and returns the commands of the module
+/// 2. [`Module::handles`] is called for every command interaction to find the module responsible for it
+/// 3. [`Module::handle`] is called on the responsible module to handle the interaction
+/// 4. [`Module::handles_component`] and [`Module::handle_component`] do the same for message components,
+///    [`Module::handles_modal`] and [`Module::handle_modal`] for modal submissions
+/// 5. [`Module::handle_admin`] is called for requests to the admin api until a module handles them
+/// 6. [`Module::shutdown`] is called once the bot disconnects from discord
+///
+/// Modules are handled one at a time, so a module doesn't need to synchronize its own state.
+///
 #[async_trait]
 pub trait Module: Send + Sync {
 
@@ -36,6 +302,20 @@ pub trait Module: Send + Sync {
     ///
     fn handles(&self, cmd: &CommandInteraction) -> bool;
 
+    ///
+    /// Get the keys the commands of the module are tracked by while running
+    ///
+    /// Commands missing from the map are tracked by their full path, including subcommands.
+    /// The map is shared, so a module can update it whenever its commands change.
+    ///
+    /// # Returns
+    ///
+    /// The shared map of full command paths to their keys, or None if every command is tracked by its path
+    ///
+    fn command_keys(&self) -> Option<CommandKeys> {
+        None
+    }
+
     ///
     /// Handle the interaction
     ///
@@ -46,4 +326,84 @@ pub trait Module: Send + Sync {
     ///
     async fn handle(&mut self, ctx: Context, cmd: CommandInteraction) -> Result<(), anyhow::Error>;
 
+    ///
+    /// Check if the module handles the component interaction
+    ///
+    /// # Arguments
+    ///
+    /// * `component` - The component interaction
+    ///
+    /// # Returns
+    ///
+    /// True if the module handles the component interaction
+    ///
+    fn handles_component(&self, _component: &ComponentInteraction) -> bool {
+        false
+    }
+
+    ///
+    /// Handle the component interaction
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `component` - The component interaction
+    ///
+    async fn handle_component(&mut self, _ctx: Context, _component: ComponentInteraction) -> Result<(), anyhow::Error> {
+        Err(anyhow!("not implemented"))
+    }
+
+    ///
+    /// Check if the module handles the modal submission
+    ///
+    /// # Arguments
+    ///
+    /// * `modal` - The modal interaction
+    ///
+    /// # Returns
+    ///
+    /// True if the module handles the modal submission
+    ///
+    fn handles_modal(&self, _modal: &ModalInteraction) -> bool {
+        false
+    }
+
+    ///
+    /// Handle the modal submission
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `modal` - The modal interaction
+    ///
+    async fn handle_modal(&mut self, _ctx: Context, _modal: ModalInteraction) -> Result<(), anyhow::Error> {
+        Err(anyhow!("not implemented"))
+    }
+
+    ///
+    /// Handle a request to the admin api
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The authenticated request
+    ///
+    /// # Returns
+    ///
+    /// The response or None if the module doesn't handle the request
+    ///
+    async fn handle_admin(&mut self, _request: &AdminRequest) -> Option<AdminResponse> {
+        None
+    }
+
+    ///
+    /// Shut down the module, stopping background tasks and saving its state
+    ///
+    /// # Errors
+    ///
+    /// If the module fails to save its state
+    ///
+    async fn shutdown(&mut self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
 }
@@ -1,12 +1,12 @@
 use anyhow::{anyhow, Context};
 use log::{debug, info};
-use rand::{seq::SliceRandom, Rng};
+use rand::Rng;
 use serde::Deserialize;
-use serenity::{all::{CommandDataOptionValue, CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption, CreateInteractionResponseMessage, InstallationContext, InteractionContext}, async_trait};
+use serenity::{all::{CommandDataOptionValue, CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption, CreateInteractionResponseMessage}, async_trait};
 
 use crate::Configuration;
 
-use super::Module;
+use super::{pick_random, Module};
 
 /// Struct to hold the coinflip command info
 #[derive(Deserialize, Clone)]
@@ -52,6 +52,36 @@ pub struct Random {
     pub number: Number
 }
 
+impl Random {
+
+    ///
+    /// Validate the random command info
+    ///
+    /// # Errors
+    ///
+    /// If a list of responses is empty
+    ///
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let lists = [
+            ("coinflip.heads_responses", &self.coinflip.heads_responses),
+            ("coinflip.tails_responses", &self.coinflip.tails_responses),
+            ("dice.default_responses", &self.dice.default_responses),
+            ("dice.one_responses", &self.dice.one_responses),
+            ("dice.six_responses", &self.dice.six_responses),
+            ("number.default_responses", &self.number.default_responses)
+        ];
+
+        for (list, responses) in lists {
+            if responses.is_empty() {
+                return Err(anyhow!("random.{} is empty", list));
+            }
+        }
+
+        Ok(())
+    }
+
+}
+
 /// Random module
 pub struct RandomModule {
     /// Random command info
@@ -75,6 +105,12 @@ impl RandomModule {
 
 }
 
+impl Default for RandomModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[async_trait]
 impl Module for RandomModule {
 
@@ -86,10 +122,8 @@ impl Module for RandomModule {
         // create commands
         info!(target: "module/random", "creating random command");
         Ok(vec![
-            CreateCommand::new("random")
+            config.command_contexts.apply(CreateCommand::new("random"))
                 .description("Let random numbers decide your fate")
-                .integration_types(vec![InstallationContext::User, InstallationContext::Guild])
-                .contexts(vec![InteractionContext::PrivateChannel, InteractionContext::Guild, InteractionContext::BotDm])
                 .add_option(
                     CreateCommandOption::new(CommandOptionType::SubCommand, "coinflip", random.coinflip.description.clone())
                 )
@@ -113,16 +147,16 @@ impl Module for RandomModule {
 
         // get random command
         let random = self.random.as_ref().unwrap();
-        let options = cmd.data.options.get(0)
+        let options = cmd.data.options.first()
             .context("no subcommand")?;
 
         // get response
         let response = match options.name.as_str() {
             "coinflip" => {
                 if rand::random() {
-                    random.coinflip.heads_responses.choose(&mut rand::thread_rng())
+                    pick_random(&mut rand::thread_rng(), &random.coinflip.heads_responses)
                 } else {
-                    random.coinflip.tails_responses.choose(&mut rand::thread_rng())
+                    pick_random(&mut rand::thread_rng(), &random.coinflip.tails_responses)
                 }.context("no option")?.clone()
             },
             "dice" => {
@@ -130,19 +164,19 @@ impl Module for RandomModule {
                 match roll {
                     1 => {
                         if rand::random() {
-                            random.dice.one_responses.choose(&mut rand::thread_rng())
+                            pick_random(&mut rand::thread_rng(), &random.dice.one_responses)
                         } else {
-                            random.dice.default_responses.choose(&mut rand::thread_rng())
+                            pick_random(&mut rand::thread_rng(), &random.dice.default_responses)
                         }
                     },
                     6 => {
                         if rand::random() {
-                            random.dice.six_responses.choose(&mut rand::thread_rng())
+                            pick_random(&mut rand::thread_rng(), &random.dice.six_responses)
                         } else {
-                            random.dice.default_responses.choose(&mut rand::thread_rng())
+                            pick_random(&mut rand::thread_rng(), &random.dice.default_responses)
                         }
                     },
-                    _ => random.dice.default_responses.choose(&mut rand::thread_rng())
+                    _ => pick_random(&mut rand::thread_rng(), &random.dice.default_responses)
                 }.context("no option")?.clone().replace("{number}", roll.to_string().as_str())
             },
             "number" => {
@@ -151,19 +185,17 @@ impl Module for RandomModule {
                     _ => None
                 }.context("invalid subcommand")?;
 
-                let mut min = options.get(0).and_then(|opt| opt.value.as_i64())
+                let mut min = options.first().and_then(|opt| opt.value.as_i64())
                     .context("no min")?;
                 let mut max = options.get(1).and_then(|opt| opt.value.as_i64())
                     .context("no max")?;
 
                 if min >= max {
-                    let temp = min;
-                    min = max;
-                    max = temp;
+                    std::mem::swap(&mut min, &mut max);
                 }
 
                 let number = rand::thread_rng().gen_range(min..=max);
-                random.number.default_responses.choose(&mut rand::thread_rng())
+                pick_random(&mut rand::thread_rng(), &random.number.default_responses)
                     .context("no option")?
                     .replace("{number}", number.to_string().as_str())
             },
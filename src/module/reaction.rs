@@ -1,195 +1,3762 @@
+use std::{collections::{BTreeSet, HashMap, HashSet, VecDeque}, future::Future, path::PathBuf, sync::Arc, time::{Duration, Instant}};
+
 use anyhow::Context;
-use log::{debug, info, trace};
-use serde::Deserialize;
-use serenity::{all::{CommandDataOptionValue, CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption, CreateEmbed, CreateInteractionResponseMessage, InstallationContext, InteractionContext, UserId}, async_trait};
+use chrono::FixedOffset;
+use log::{debug, info, trace, warn};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use render::{render_response, NAME_FALLBACK};
+pub use render::Sanitization;
+use serde::{Deserialize, Serialize};
+use serenity::{all::{ApplicationId, CommandDataOption, CommandDataOptionValue, CommandDataResolved, CommandInteraction, CommandOptionType, ComponentInteraction, CreateActionRow, CreateAllowedMentions, CreateButton, CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage, CreateMessage, ChannelId, Colour, CreateAttachment, GuildId, Http, InteractionContext, InteractionId, Member, MessageId, Permissions, SkuId, User, UserId}, async_trait};
+
+#[cfg(feature = "entitlements")]
+use serenity::all::Entitlement;
 
-use crate::Configuration;
+use crate::{admin::{AdminRequest, AdminResponse}, persistence, store::Store, Configuration};
 
-use super::Module;
+use super::{is_permission_error, is_transient_error, pick_random, pick_weighted, respond_ephemeral, CommandContexts, CommandKeys, Module};
 
-mod backend;
+mod admin;
+pub mod analytics;
+pub mod audit;
+pub mod backend;
+pub mod cache;
+mod download;
+mod guild;
+mod pronouns;
+mod render;
+pub mod seasonal;
+mod utility;
+mod warmup;
 
 /// Struct to hold the reaction info
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Reaction {
     /// Name of the reaction
     pub name: String,
     /// Description of the reaction
     pub description: String,
+    /// Category of the reaction, naming the command the reaction is a subcommand of
+    #[serde(default)]
+    pub category: Option<String>,
     /// Whether the reaction should have an alias
     pub alias: bool,
+    /// Description of the alias command, overriding the default template
+    #[serde(default)]
+    pub alias_description: Option<String>,
     /// List of backends to fetch the reaction from
     pub backends: Vec<String>,
     /// List of responses when using the command as usual
-    pub default_responses: Vec<String>,
+    pub default_responses: Vec<Response>,
     /// List of responses when using the command on the bot
-    pub bot_responses: Vec<String>,
+    pub bot_responses: Vec<Response>,
     /// List of responses when using the command on yourself
-    pub self_responses: Vec<String>
+    pub self_responses: Vec<Response>,
+    /// List of responses replacing the default responses outside of guilds
+    #[serde(default)]
+    pub dm_responses: Vec<Response>,
+    /// List of responses of anonymous reactions, which don't reveal the user
+    #[serde(default)]
+    pub anonymous_responses: Vec<Response>,
+    /// Probability of showing a rare gif instead of a regular one (0.0 - 1.0)
+    #[serde(default)]
+    pub rare_probability: f64,
+    /// List of backends to fetch the rare reaction from
+    #[serde(default)]
+    pub rare_backends: Vec<String>,
+    /// List of responses when a rare gif is shown
+    #[serde(default)]
+    pub rare_responses: Vec<Response>,
+    /// Where the reaction can be installed and used, overriding the defaults
+    #[serde(default)]
+    pub contexts: Option<CommandContexts>,
+    /// Seconds a user has to wait between uses of the reaction, overriding the default
+    #[serde(default)]
+    pub cooldown: Option<u64>,
+    /// List of responses when the reaction is on cooldown
+    #[serde(default)]
+    pub cooldown_responses: Vec<String>,
+    /// Whether the target should be pinged, overriding the default
+    #[serde(default)]
+    pub ping_target: Option<bool>,
+    /// Whether the reaction shows nsfw gifs
+    #[serde(default)]
+    pub nsfw: bool,
+    /// How the response of the reaction is laid out
+    #[serde(default)]
+    pub style: ReactionStyle,
+    /// Number of cached gifs shown together as a gallery, if any
+    #[serde(default)]
+    pub gallery: Option<usize>,
+    /// Seconds the account of the user must exist for to use the reaction
+    #[serde(default)]
+    pub min_account_age: Option<u64>,
+    /// Seconds the user must be a member of the guild for to use the reaction, not checked outside of guilds
+    #[serde(default)]
+    pub min_member_age: Option<u64>,
+    /// SKU of the entitlement required to use the reaction, requires the 'entitlements' feature
+    #[serde(default)]
+    pub sku: Option<SkuId>,
+    /// Weight of the reaction when picking a random reaction with configured weights
+    #[serde(default = "default_weight")]
+    pub random_weight: f64
 }
 
-/// Reaction module
-pub struct ReactionModule {
-    /// List of reactions
-    reactions: Vec<Reaction>,
-    /// List of reaction names with aliases
-    aliases: Vec<String>,
-    /// Backend manager
-    backend_manager: backend::BackendManager
+/// A response of a reaction and how often it's picked
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(from = "ResponseEntry")]
+pub struct Response {
+    /// Template of the response
+    pub text: String,
+    /// Weight of the response relative to the other responses of its list
+    pub weight: f64
 }
 
-impl ReactionModule {
+/// A response in the configuration, either a plain template or a template with a weight
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ResponseEntry {
+    Plain(String),
+    Weighted {
+        text: String,
+        #[serde(default = "default_weight")]
+        weight: f64
+    }
+}
+
+impl From<ResponseEntry> for Response {
+    fn from(entry: ResponseEntry) -> Self {
+        match entry {
+            ResponseEntry::Plain(text) => Self { text, weight: default_weight() },
+            ResponseEntry::Weighted { text, weight } => Self { text, weight }
+        }
+    }
+}
+
+///
+/// Pick a random response from a list, respecting the weights of the responses
+///
+/// # Arguments
+///
+/// * `rng` - The random number generator
+/// * `responses` - The responses to pick from
+///
+/// # Returns
+///
+/// The template of a random response or None if the list is empty
+///
+fn pick_response<'a, R: Rng + ?Sized>(rng: &mut R, responses: &'a [Response]) -> Option<&'a str> {
+    pick_weighted(rng, responses, |response| response.weight).map(|response| response.text.as_str())
+}
+
+///
+/// Pick a random response from a list, avoiding the previous response if there are others
+///
+/// # Arguments
+///
+/// * `rng` - The random number generator
+/// * `responses` - The responses to pick from
+/// * `previous` - The template of the previous response, if any
+///
+/// # Returns
+///
+/// The template of a random response or None if the list is empty
+///
+fn pick_fresh_response<'a, R: Rng + ?Sized>(rng: &mut R, responses: &'a [Response], previous: Option<&str>) -> Option<&'a str> {
+    let others: Vec<&Response> = responses.iter()
+        .filter(|response| Some(response.text.as_str()) != previous)
+        .collect();
+    match pick_weighted(rng, &others, |response| response.weight) {
+        Some(response) => Some(response.text.as_str()),
+        None => pick_response(rng, responses)
+    }
+}
+
+/// Responses of a reaction replaced by a response profile
+#[derive(Deserialize, Clone, Default)]
+pub struct ResponseProfile {
+    /// List of responses when using the command as usual
+    #[serde(default)]
+    pub default_responses: Option<Vec<Response>>,
+    /// List of responses when using the command on the bot
+    #[serde(default)]
+    pub bot_responses: Option<Vec<Response>>,
+    /// List of responses when using the command on yourself
+    #[serde(default)]
+    pub self_responses: Option<Vec<Response>>,
+    /// List of responses replacing the default responses outside of guilds
+    #[serde(default)]
+    pub dm_responses: Option<Vec<Response>>,
+    /// List of responses of anonymous reactions, which don't reveal the user
+    #[serde(default)]
+    pub anonymous_responses: Option<Vec<Response>>,
+    /// List of responses when a rare gif is shown
+    #[serde(default)]
+    pub rare_responses: Option<Vec<Response>>,
+    /// List of responses when the reaction is on cooldown
+    #[serde(default)]
+    pub cooldown_responses: Option<Vec<String>>
+}
+
+impl Reaction {
 
     ///
-    /// Create a new reaction module
+    /// Replace the responses of the reaction with those of a response profile
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// A Result containing the reaction module
+    /// * `profile` - The responses of the profile, lists missing in the profile are kept
+    ///
+    pub fn apply_profile(&mut self, profile: &ResponseProfile) {
+        let lists = [
+            (&mut self.default_responses, &profile.default_responses),
+            (&mut self.bot_responses, &profile.bot_responses),
+            (&mut self.self_responses, &profile.self_responses),
+            (&mut self.dm_responses, &profile.dm_responses),
+            (&mut self.anonymous_responses, &profile.anonymous_responses),
+            (&mut self.rare_responses, &profile.rare_responses)
+        ];
+        for (responses, replacement) in lists {
+            if let Some(replacement) = replacement {
+                responses.clone_from(replacement);
+            }
+        }
+        if let Some(replacement) = &profile.cooldown_responses {
+            self.cooldown_responses.clone_from(replacement);
+        }
+    }
+
+    ///
+    /// Validate the reaction
     ///
     /// # Errors
     ///
-    /// If the backend manager fails to initialize
+    /// If the reaction has no backends or responses, a backend is missing its endpoint, the rare probability
+    /// is out of range, the contexts are invalid or an sku is required without the 'entitlements' feature
     ///
-    pub fn new() -> Result<Self, anyhow::Error> {
-        info!(target: "module/reaction", "creating reaction module");
-        let backend_manager = backend::BackendManager::new()?;
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let length = self.name.chars().count();
+        if !(1..=MAX_NAME_LENGTH).contains(&length) {
+            return Err(anyhow::anyhow!("name of reaction '{}' is {} characters long, but discord allows 1 to {}", self.name, length, MAX_NAME_LENGTH));
+        }
 
-        Ok(Self {
-            reactions: Vec::new(),
-            aliases: Vec::new(),
-            backend_manager
-        })
+        let length = self.description.chars().count();
+        if !(1..=MAX_DESCRIPTION_LENGTH).contains(&length) {
+            return Err(anyhow::anyhow!("description of reaction '{}' is {} characters long, but discord allows 1 to {}", self.name, length, MAX_DESCRIPTION_LENGTH));
+        }
+
+        if self.backends.is_empty() {
+            return Err(anyhow::anyhow!("reaction '{}' has no backends", self.name));
+        }
+
+        for (list, responses) in [("default_responses", &self.default_responses), ("bot_responses", &self.bot_responses), ("self_responses", &self.self_responses)] {
+            if responses.is_empty() {
+                return Err(anyhow::anyhow!("reaction '{}' has no {}", self.name, list));
+            }
+        }
+
+        for (list, responses) in [("default_responses", &self.default_responses), ("bot_responses", &self.bot_responses), ("self_responses", &self.self_responses), ("dm_responses", &self.dm_responses), ("anonymous_responses", &self.anonymous_responses), ("rare_responses", &self.rare_responses)] {
+            if responses.iter().any(|response| !response.weight.is_finite() || response.weight < 0.0) {
+                return Err(anyhow::anyhow!("{} of reaction '{}' have a negative or invalid weight", list, self.name));
+            }
+            if !responses.is_empty() && responses.iter().all(|response| response.weight == 0.0) {
+                return Err(anyhow::anyhow!("{} of reaction '{}' all have a weight of 0", list, self.name));
+            }
+        }
+
+        for backend in self.backends.iter().chain(&self.rare_backends) {
+            if !backend.split_once('/').is_some_and(|(id, endpoint)| !id.is_empty() && !endpoint.is_empty()) {
+                return Err(anyhow::anyhow!("backend '{}' of reaction '{}' is not in the format '<backend>/<endpoint>'", backend, self.name));
+            }
+        }
+
+        if !self.random_weight.is_finite() || self.random_weight < 0.0 {
+            return Err(anyhow::anyhow!("random_weight of reaction '{}' is negative or invalid", self.name));
+        }
+
+        if !(0.0..=1.0).contains(&self.rare_probability) {
+            return Err(anyhow::anyhow!("rare_probability of reaction '{}' is {}, but must be 0.0 to 1.0", self.name, self.rare_probability));
+        }
+
+        if let Some(size) = self.gallery.filter(|size| !(2..=MAX_GALLERY_SIZE).contains(size)) {
+            return Err(anyhow::anyhow!("gallery of reaction '{}' has {} gifs, but must have 2 to {}", self.name, size, MAX_GALLERY_SIZE));
+        }
+
+        if let Some(contexts) = &self.contexts {
+            contexts.validate()
+                .with_context(|| format!("invalid contexts for reaction '{}'", self.name))?;
+        }
+
+        #[cfg(not(feature = "entitlements"))]
+        if self.sku.is_some() {
+            return Err(anyhow::anyhow!("reaction '{}' requires an sku, which requires the 'entitlements' feature", self.name));
+        }
+
+        Ok(())
     }
 
 }
 
-#[async_trait]
-impl Module for ReactionModule {
+///
+/// Find all issues with a set of reactions
+///
+/// Checks every reaction, the seasonal themes and the reactions with their overlays applied,
+/// unknown backends and colliding command names.
+///
+/// # Arguments
+///
+/// * `reactions` - The reactions with the responses of the selected profile
+/// * `themes` - The seasonal themes
+/// * `backends` - The settings of each backend by id
+///
+/// # Returns
+///
+/// A list of all issues, empty if the reactions are valid
+///
+pub fn reaction_issues(reactions: &[Reaction], themes: &[seasonal::SeasonalTheme], backends: &HashMap<String, BackendSettings>, alias_description: &str) -> Vec<anyhow::Error> {
+    let mut issues = Vec::new();
 
-    async fn init(&mut self, _: &serenity::all::Context, config: &Configuration) -> Result<Vec<CreateCommand>, anyhow::Error> {
-        info!(target: "module/reaction", "initializing reaction module");
-        self.reactions = config.reactions.clone();
+    for reaction in reactions {
+        if let Err(e) = reaction.validate() {
+            issues.push(e);
+        }
+    }
 
-        // build cache
-        self.backend_manager.build_cache().await?;
+    // check the seasonal themes and the reactions with their overlays applied
+    let mut themed = Vec::new();
+    for theme in themes {
+        if let Err(e) = theme.validate() {
+            issues.push(e);
+        }
+        for (name, overlay) in &theme.reactions {
+            let Some(reaction) = reactions.iter().find(|r| &r.name == name) else {
+                issues.push(anyhow::anyhow!("seasonal theme '{}' has an overlay for unknown reaction '{}'", theme.name, name));
+                continue;
+            };
+            let mut reaction = reaction.clone();
+            overlay.apply(&mut reaction);
+            if let Err(e) = reaction.validate() {
+                issues.push(e.context(format!("invalid overlay of seasonal theme '{}'", theme.name)));
+            }
+            if overlay.backends.is_some() || overlay.rare_backends.is_some() {
+                themed.push(reaction);
+            }
+        }
+    }
 
-        // split reactions into commands of 25 options
-        let mut index = 0;
-        let mut commands: Vec<CreateCommand> = self.reactions.chunks(25).map(|batch| {
-            // create command name
-            index += 1;
-            let index_str = index.to_string();
-            let name = format!("reaction{}", if index > 1 { index_str.as_str() } else { "" });
+    // check that backends exist
+    let known = |id: &str| backend::BUILTIN_BACKENDS.contains(&id)
+        || backends.get(id).is_some_and(|settings| settings.registers_backend());
+    for reaction in reactions.iter().chain(&themed) {
+        for backend in reaction.backends.iter().chain(&reaction.rare_backends) {
+            if let Some((id, _)) = backend.split_once('/').filter(|(id, _)| !known(id)) {
+                issues.push(anyhow::anyhow!("reaction '{}' uses unknown backend '{}'", reaction.name, id));
+            }
+        }
+    }
 
-            // create command
-            info!(target: "module/reaction", "creating command '{}' with {} options", name, batch.len());
-            CreateCommand::new(name)
-                .description("React to someone with an animated gif.")
-                .integration_types(vec![InstallationContext::User, InstallationContext::Guild])
-                .contexts(vec![InteractionContext::PrivateChannel, InteractionContext::Guild, InteractionContext::BotDm])
-                .set_options(
-                    batch.iter().map(|i| {
-                        CreateCommandOption::new(CommandOptionType::SubCommand, &i.name, &i.description)
-                            .add_sub_option(CreateCommandOption::new(CommandOptionType::User, "user", "The target user.").required(true))
-                    }).collect()
-                )
-        }).collect();
+    // check for colliding command names
+    let mut names = HashSet::new();
+    for reaction in reactions {
+        if !names.insert(reaction.name.as_str()) {
+            issues.push(anyhow::anyhow!("reaction '{}' is defined more than once", reaction.name));
+        }
+        if reaction.alias && (reaction.name.starts_with("reaction") || reaction.name == "random" || reaction.name == pronouns::PRONOUNS_COMMAND) {
+            issues.push(anyhow::anyhow!("alias of reaction '{}' collides with a built-in command", reaction.name));
+        }
+    }
 
-        // create individual commands for reactions with alias
-        let mut aliases: Vec<String> = Vec::new();
-        self.reactions.iter().filter(|r| r.alias).for_each(|r| {
-            let description = format!("[Alias for /reaction {}] {}", r.name, r.description);
-            info!(target: "module/reaction", "creating alias command '{}'", r.name);
-            commands.push(CreateCommand::new(r.name.clone())
-                .description(description)
-                .integration_types(vec![InstallationContext::User, InstallationContext::Guild])
-                .contexts(vec![InteractionContext::PrivateChannel, InteractionContext::Guild, InteractionContext::BotDm])
-                .add_option(CreateCommandOption::new(CommandOptionType::User, "user", "The target user.").required(true))
-            );
-            aliases.push(r.name.clone());
-        });
+    // check the categories and the group commands they create
+    for category in reactions.iter().filter_map(|r| r.category.as_deref()).collect::<BTreeSet<_>>() {
+        if category.is_empty() || category.len() > MAX_CATEGORY_LENGTH
+            || !category.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_') {
+            issues.push(anyhow::anyhow!("category '{}' must be 1 to {} lowercase letters, digits, '-' or '_'", category, MAX_CATEGORY_LENGTH));
+        }
+        if category.starts_with(DEFAULT_GROUP) || category == "random" || category == pronouns::PRONOUNS_COMMAND {
+            issues.push(anyhow::anyhow!("category '{}' collides with a built-in command", category));
+        }
+    }
+    let groups = command_groups(reactions);
+    for (group, _) in &groups {
+        if reactions.iter().any(|r| r.alias && &r.name == group) {
+            issues.push(anyhow::anyhow!("alias of reaction '{}' collides with the command of a category", group));
+        }
+    }
 
-        self.aliases = aliases;
-        Ok(commands)
+    // check the generated descriptions of the aliases
+    for reaction in reactions.iter().filter(|r| r.alias) {
+        let length = render_alias_description(reaction, alias_description, &groups).chars().count();
+        if length > MAX_DESCRIPTION_LENGTH {
+            issues.push(anyhow::anyhow!("alias description of reaction '{}' is {} characters long, but discord allows at most {}",
+                reaction.name, length, MAX_DESCRIPTION_LENGTH));
+        }
     }
 
-    fn handles(&self, cmd: &CommandInteraction) -> bool {
-        cmd.data.name.starts_with("reaction") || self.aliases.contains(&cmd.data.name)
+    issues
+}
+
+///
+/// Render the description of the alias command of a reaction
+///
+/// # Arguments
+///
+/// * `reaction` - The reaction with an alias
+/// * `template` - The default template of alias descriptions
+/// * `groups` - The group commands of the reactions
+///
+/// # Returns
+///
+/// The description of the alias command
+///
+fn render_alias_description(reaction: &Reaction, template: &str, groups: &[(String, Vec<&Reaction>)]) -> String {
+    let template = reaction.alias_description.as_deref().unwrap_or(template);
+    let command = groups.iter().find(|(_, batch)| batch.iter().any(|member| member.name == reaction.name))
+        .map_or(DEFAULT_GROUP, |(name, _)| name.as_str());
+    render_response(template, &[("name", &reaction.name), ("description", &reaction.description), ("command", command)], Sanitization::Off)
+}
+
+///
+/// Split reactions into group commands by their category
+///
+/// Reactions without a category are grouped into `/reaction`, reactions of a category into a command named
+/// after the category. Groups with more than 25 reactions are split into numbered commands, e.g. `/reaction2`.
+///
+/// # Arguments
+///
+/// * `reactions` - The reactions
+///
+/// # Returns
+///
+/// The name and reactions of each group command, in the order the groups first appear in
+///
+fn command_groups(reactions: &[Reaction]) -> Vec<(String, Vec<&Reaction>)> {
+    let mut categories: Vec<(&str, Vec<&Reaction>)> = Vec::new();
+    for reaction in reactions {
+        let category = reaction.category.as_deref().unwrap_or(DEFAULT_GROUP);
+        match categories.iter_mut().find(|(name, _)| *name == category) {
+            Some((_, members)) => members.push(reaction),
+            None => categories.push((category, vec![reaction]))
+        }
     }
 
-    async fn handle(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
-        debug!(target: "module/reaction", "handling command {} executed by @{}", cmd.data.name, cmd.user.name);
+    categories.into_iter().flat_map(|(category, members)| {
+        members.chunks(MAX_SUBCOMMANDS).enumerate().map(|(index, batch)| {
+            let name = if index == 0 { category.to_string() } else { format!("{}{}", category, index + 1) };
+            (name, batch.to_vec())
+        }).collect::<Vec<_>>()
+    }).collect()
+}
 
-        // get requested reaction
-        let (options, reaction) = if cmd.data.name.starts_with("reaction") {
-            let subcommand = cmd.data.options.get(0)
-                .context("no subcommand")?;
-            let options = match &subcommand.value {
-                CommandDataOptionValue::SubCommand(o) => Some(o),
-                _ => None
-            }.context("invalid subcommand")?;
-            let reaction = self.reactions.iter().find(|r| r.name == subcommand.name)
-                .context("unknown reaction")?;
-            (options, reaction)
-        } else {
-            let reaction = self.reactions.iter().find(|r| r.name == cmd.data.name)
-                .context("unknown reaction")?;
-            (&cmd.data.options, reaction)
-        };
+/// Struct to hold the settings of a backend
+#[derive(Deserialize, Clone)]
+pub struct BackendSettings {
+    /// Whether gifs from this backend should show the source attribution footer
+    #[serde(default = "default_true")]
+    pub attribution: bool,
+    /// Maximum number of gifs in a pool of this backend, overriding the default
+    #[serde(default)]
+    pub max_pool_size: Option<usize>,
+    /// Minimum number of gifs in a healthy pool of this backend, overriding the default
+    #[serde(default)]
+    pub healthy_pool_size: Option<usize>,
+    /// Seconds a request of this backend may take before it fails, overriding the default
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// Url of a proxy gifs of this backend are embedded through, passing the gif url as the 'url' parameter
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Settings of a generic http backend with this id, if any
+    #[serde(default)]
+    pub http: Option<backend::http::HttpBackendSettings>,
+    /// Additional headers sent with every request, supporting '${NAME}' environment variables
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Dimensions gifs of this backend must have to be cached, if the backend exposes them
+    #[serde(default)]
+    pub dimensions: Option<backend::DimensionFilter>,
+    /// Settings of a backend reading gifs off an rss or atom feed with this id, if any
+    #[serde(default)]
+    pub feed: Option<backend::feed::FeedBackendSettings>,
+    /// Settings of a backend turning the avatar of the target into a gif with this id, if any
+    #[serde(default)]
+    pub avatar: Option<backend::avatar::AvatarBackendSettings>
+}
 
-        // get user and target
-        let user = cmd.user.id;
-        let target = options.get(0).and_then(|opt| opt.value.as_user_id())
-            .unwrap_or(UserId::new(cmd.application_id.get()));
+impl BackendSettings {
 
-        // pick random backend
-        let backend_info = reaction.backends.get(rand::random::<usize>() % reaction.backends.len())
-            .context("no backend")?;
-        let mut backend_info = backend_info.splitn(2, "/");
-        let (backend, endpoint): (&str, &str) = (backend_info.next().context("no backend")?, backend_info.next().context("no endpoint")?);
+    ///
+    /// Check whether the settings register a backend of their own
+    ///
+    /// # Returns
+    ///
+    /// Whether an http, feed or avatar backend is configured
+    ///
+    pub fn registers_backend(&self) -> bool {
+        self.http.is_some() || self.feed.is_some() || self.avatar.is_some()
+    }
 
-        info!(target: "module/reaction", "user @{} ran /reaction {} on <@{}>", cmd.user.name, reaction.name, target);
+}
 
-        // fetch reaction gif
-        let image_url = self.backend_manager.get_cached(backend, endpoint)
-            .context("no cached gif")?;
+/// Target of a reaction when no user is given
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DefaultTarget {
+    /// React to the bot
+    #[default]
+    Bot,
+    /// React to the user running the command
+    SelfUser,
+    /// Refuse to run the reaction
+    Error
+}
 
-        // build response
-        let message = if user == target {
-            reaction.self_responses.get(rand::random::<usize>() % reaction.self_responses.len())
-                .context("no self response")?
-        } else if target == UserId::new(cmd.application_id.get()) {
-            reaction.bot_responses.get(rand::random::<usize>() % reaction.bot_responses.len())
-                .context("no bot response")?
-        } else {
-            reaction.default_responses.get(rand::random::<usize>() % reaction.default_responses.len())
-                .context("no default response")?
-        };
+/// Who may be pinged by a reaction response
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MentionPolicy {
+    /// Only ping the target of the reaction
+    #[default]
+    Target,
+    /// Ping all users mentioned in the response
+    Users,
+    /// Never ping anyone
+    None
+}
+
+/// What to do when the only cached gif of a reaction was just shown
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NoVarietyBehavior {
+    /// Show the gif again
+    #[default]
+    Repeat,
+    /// Respond with the no variety response
+    Message
+}
 
-        let message = message.replace("{user}", format!("<@{}>", user.get()).as_str())
-            .replace("{target}", format!("<@{}>", target.get()).as_str())
-            + format!("\n-# From: {} • [Source](<{}>)", backend, image_url).as_str();
+/// Who has to have seen a gif for it to count as a repeat
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupScope {
+    /// Never avoid repeats
+    None,
+    /// Avoid the last gif shown in the channel
+    #[default]
+    Channel,
+    /// Avoid the last gif shown by the user in any channel
+    User,
+    /// Avoid the last gif shown anywhere
+    Global
+}
 
-        let color = crate::color::rand();
+/// What the last shown gif is remembered for, depending on the dedup scope
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum DedupKey {
+    Channel(ChannelId),
+    User(UserId),
+    Global
+}
 
-        // send response
-        trace!(target: "module/reaction", "sending response:\n{}\n{}", message, image_url);
-        let status = cmd.create_response(&ctx.http, serenity::all::CreateInteractionResponse::Message(
-            CreateInteractionResponseMessage::new()
-                .content(message)
-                .embed(CreateEmbed::new()
-                    .image(image_url)
-                    .color(color)
-                )
-        )).await;
+/// How the response of a reaction is laid out
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReactionStyle {
+    /// Show the gif as the embed image
+    #[default]
+    Gif,
+    /// Show the target as the embed author and the gif as a small thumbnail
+    Card
+}
+
+/// When the gif cache is warmed
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WarmupMode {
+    /// Warm all endpoints at startup
+    #[default]
+    Eager,
+    /// Warm only the warm reactions at startup and other endpoints on first use
+    Lazy
+}
+
+/// How a random reaction is picked
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RandomSelection {
+    /// Pick every reaction with the same chance
+    #[default]
+    Uniform,
+    /// Prefer reactions that were used more often
+    Popularity,
+    /// Prefer reactions with a higher random_weight
+    Weight
+}
+
+/// How the source of a gif is linked on reaction responses
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceStyle {
+    /// Link the source in the attribution line below the response
+    #[default]
+    Footer,
+    /// Link the source with a button below the response
+    Button
+}
+
+/// Where the branding is shown on reaction embeds
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BrandingPosition {
+    /// Show the branding as the author line above the gif
+    #[default]
+    Author,
+    /// Show the branding as the footer below the gif
+    Footer
+}
+
+/// Scaling of cooldowns in busy guilds
+#[derive(Deserialize, Clone)]
+pub struct CooldownScaling {
+    /// Seconds the uses of reactions in a guild are counted over
+    #[serde(default = "default_scaling_window")]
+    pub window: u64,
+    /// Number of uses in the window before cooldowns are scaled
+    pub threshold: usize,
+    /// Increase of the cooldown multiplier for every use above the threshold
+    pub per_use: f64,
+    /// Upper bound of the cooldown multiplier
+    #[serde(default = "default_max_multiplier")]
+    pub max_multiplier: f64
+}
+
+impl CooldownScaling {
+
+    ///
+    /// Validate the cooldown scaling
+    ///
+    /// # Errors
+    ///
+    /// If the window, the increase per use or the maximum multiplier is out of bounds
+    ///
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if !(1..=MAX_SCALING_WINDOW).contains(&self.window) {
+            return Err(anyhow::anyhow!("window must be between 1 and {} seconds", MAX_SCALING_WINDOW));
+        }
+
+        if !self.per_use.is_finite() || self.per_use < 0.0 {
+            return Err(anyhow::anyhow!("per_use must be a positive number"));
+        }
+
+        if !(1.0..=MAX_COOLDOWN_MULTIPLIER).contains(&self.max_multiplier) {
+            return Err(anyhow::anyhow!("max_multiplier must be between 1.0 and {}", MAX_COOLDOWN_MULTIPLIER));
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Get the cooldown multiplier for a number of recent uses
+    ///
+    /// # Arguments
+    ///
+    /// * `uses` - The number of uses in the window
+    ///
+    /// # Returns
+    ///
+    /// The multiplier, growing linearly above the threshold up to the maximum multiplier
+    ///
+    fn multiplier(&self, uses: usize) -> f64 {
+        let excess = uses.saturating_sub(self.threshold) as f64;
+        (1.0 + self.per_use * excess).min(self.max_multiplier)
+    }
+
+}
+
+/// Brand shown on every reaction embed
+#[derive(Deserialize, Clone)]
+pub struct Branding {
+    /// Name of the brand
+    pub name: String,
+    /// Url of the icon shown next to the name
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Where the branding is shown
+    #[serde(default)]
+    pub position: BrandingPosition
+}
+
+impl Branding {
+
+    ///
+    /// Apply the branding to an embed
+    ///
+    /// # Arguments
+    ///
+    /// * `embed` - The embed to brand
+    ///
+    /// # Returns
+    ///
+    /// The branded embed
+    ///
+    fn apply(&self, embed: CreateEmbed) -> CreateEmbed {
+        match self.position {
+            BrandingPosition::Author => {
+                let author = CreateEmbedAuthor::new(&self.name);
+                embed.author(match &self.icon {
+                    Some(icon) => author.icon_url(icon),
+                    None => author
+                })
+            },
+            BrandingPosition::Footer => {
+                let footer = CreateEmbedFooter::new(&self.name);
+                embed.footer(match &self.icon {
+                    Some(icon) => footer.icon_url(icon),
+                    None => footer
+                })
+            }
+        }
+    }
+
+}
+
+/// Struct to hold the settings of the reaction module
+#[derive(Deserialize, Clone)]
+pub struct ReactionSettings {
+    /// Target of a reaction when no user is given
+    #[serde(default)]
+    pub default_target: DefaultTarget,
+    /// Strategy for picking a gif from a pool
+    #[serde(default)]
+    pub selection_strategy: backend::SelectionStrategy,
+    /// Seconds a user has to wait between reporting broken gifs
+    #[serde(default = "default_report_cooldown")]
+    pub report_cooldown: u64,
+    /// Whether the owners should be notified about reported gifs
+    #[serde(default)]
+    pub notify_owners_on_report: bool,
+    /// Seconds a user has to wait between uses of a reaction
+    #[serde(default)]
+    pub cooldown: u64,
+    /// Response when a reaction without cooldown responses is on cooldown
+    #[serde(default = "default_cooldown_response")]
+    pub cooldown_response: String,
+    /// Scaling of cooldowns with the recent reaction volume of a guild, if any
+    #[serde(default)]
+    pub cooldown_scaling: Option<CooldownScaling>,
+    /// Number of cache refreshes running at the same time
+    #[serde(default = "default_refresh_workers")]
+    pub refresh_workers: usize,
+    /// Number of cache refreshes that can wait in the queue
+    #[serde(default = "default_refresh_queue_size")]
+    pub refresh_queue_size: usize,
+    /// Milliseconds each refresh worker waits after a refresh
+    #[serde(default = "default_refresh_delay")]
+    pub refresh_delay: u64,
+    /// Seconds between background refreshes of each endpoint, 0 to disable
+    #[serde(default)]
+    pub background_refresh: u64,
+    /// Fraction of the background refresh interval each refresh is randomly moved by, between 0 and 1
+    #[serde(default = "default_background_refresh_jitter")]
+    pub background_refresh_jitter: f64,
+    /// Url of a redis server to share the gif cache with other instances through
+    #[serde(default)]
+    pub shared_cache: Option<String>,
+    /// Whether the response is deferred and the typing indicator shown while it is prepared
+    #[serde(default)]
+    pub typing_indicator: bool,
+    /// Whether the target of a reaction should be pinged
+    #[serde(default = "default_true")]
+    pub ping_target: bool,
+    /// Who may be pinged by a reaction response, roles and everyone are never pinged
+    #[serde(default)]
+    pub allowed_mentions: MentionPolicy,
+    /// How rendered responses are sanitized
+    #[serde(default)]
+    pub sanitization: Sanitization,
+    /// Maximum number of gifs in a pool, evicting the least recently used gifs first
+    #[serde(default)]
+    pub max_pool_size: Option<usize>,
+    /// Minimum number of gifs in a healthy pool, below which a warning is logged
+    #[serde(default)]
+    pub healthy_pool_size: Option<usize>,
+    /// Estimated kilobytes all pools may take up together, trimming the largest pools first
+    #[serde(default)]
+    pub cache_memory_budget: Option<usize>,
+    /// Seconds a request of a backend may take before it fails, no timeout if not set
+    #[serde(default)]
+    pub backend_timeout: Option<u64>,
+    /// Whether reactions on the bot use the bot responses instead of the default responses
+    #[serde(default = "default_true")]
+    pub bot_responses: bool,
+    /// Whether reactions on other bots use the bot responses as well
+    #[serde(default)]
+    pub other_bot_responses: bool,
+    /// Whether reactions can be sent anonymously with the 'anonymous' option
+    #[serde(default)]
+    pub anonymous_reactions: bool,
+    /// Whether gifs and responses are picked with a random number generator seeded by the interaction id
+    #[serde(default)]
+    pub seed_from_interaction: bool,
+    /// Whether gifs of nsfw reactions are sent as spoiler attachments
+    #[serde(default)]
+    pub nsfw_spoiler: bool,
+    /// Maximum size in bytes of a gif downloaded for a spoiler attachment
+    #[serde(default = "default_max_download_size")]
+    pub max_download_size: usize,
+    /// Whether to avoid showing the last gif and the last response of a reaction again
+    #[serde(default)]
+    pub avoid_repeats: bool,
+    /// Who has to have seen a gif for it to count as a repeat
+    #[serde(default)]
+    pub dedup_scope: DedupScope,
+    /// Seconds after which a shown gif no longer counts as a repeat, forever if not set
+    #[serde(default)]
+    pub dedup_window: Option<u64>,
+    /// What to do when the only cached gif of a reaction was just shown
+    #[serde(default)]
+    pub no_variety: NoVarietyBehavior,
+    /// Response when the only cached gif of a reaction was just shown and repeats are not allowed
+    #[serde(default = "default_no_variety_response")]
+    pub no_variety_response: String,
+    /// Response when no gif is cached for a reaction, e.g. because all of its backends failed
+    #[serde(default = "default_unavailable_response")]
+    pub unavailable_response: String,
+    /// Description of alias commands, {name} is replaced with the name and {description} with the description of the reaction
+    #[serde(default = "default_alias_description")]
+    pub alias_description: String,
+    /// Response when a reaction requires an entitlement the user doesn't have
+    #[serde(default = "default_premium_response")]
+    pub premium_response: String,
+    /// When the gif cache is warmed
+    #[serde(default)]
+    pub warmup: WarmupMode,
+    /// Reactions warmed at startup in lazy warmup mode
+    #[serde(default)]
+    pub warm_reactions: Vec<String>,
+    /// Reactions warmed before all others at startup, in order
+    #[serde(default)]
+    pub warm_priority: Vec<String>,
+    /// Whether the remaining reactions are warmed in order of their persisted usage
+    #[serde(default)]
+    pub warm_priority_by_usage: bool,
+    /// Maximum number of gifs fetched while building the cache at startup, deferring the other endpoints, if any
+    #[serde(default)]
+    pub warm_budget: Option<usize>,
+    /// Format the gif cache is persisted in
+    #[serde(default)]
+    pub cache_format: cache::CacheFormat,
+    /// Number of warmed endpoints between progress logs during cache warmup, 0 to disable
+    #[serde(default = "default_warmup_progress_interval")]
+    pub warmup_progress_interval: usize,
+    /// Channel to report the progress of the cache warmup in
+    #[serde(default)]
+    pub warmup_channel: Option<ChannelId>,
+    /// Brand shown on every reaction embed, if any
+    #[serde(default)]
+    pub branding: Option<Branding>,
+    /// How the source of a gif is linked in the attribution
+    #[serde(default)]
+    pub source_style: SourceStyle,
+    /// How a random reaction is picked by /reactions random
+    #[serde(default)]
+    pub random_selection: RandomSelection,
+    /// Embed colors of reaction categories as hex strings like '#ffb6c1', 'reaction' for reactions without a category
+    #[serde(default)]
+    pub category_colors: HashMap<String, String>,
+    /// Snippets occasionally shown below reaction responses
+    #[serde(default)]
+    pub footer_facts: Vec<String>,
+    /// Probability of showing a footer fact below a reaction response (0.0 - 1.0)
+    #[serde(default = "default_footer_fact_probability")]
+    pub footer_fact_probability: f64,
+    /// Maximum number of users a reaction can target at once
+    #[serde(default = "default_max_targets")]
+    pub max_targets: usize,
+    /// Offset from UTC the dates of seasonal themes are in, like '+01:00'
+    #[serde(default = "default_utc_offset")]
+    pub utc_offset: String,
+    /// Number of attempts at sending a reaction response when it fails on the network or with a server error
+    #[serde(default = "default_response_attempts")]
+    pub response_attempts: u32,
+    /// Seconds a user can undo their last reaction in a channel for, 0 to disable
+    #[serde(default = "default_undo_window")]
+    pub undo_window: u64
+}
+
+impl ReactionSettings {
+
+    ///
+    /// Validate the reaction settings
+    ///
+    /// # Errors
+    ///
+    /// If the maximum number of targets, the undo window, the backend timeout or the footer fact probability
+    /// is out of range, or the utc offset or a category color is invalid
+    ///
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if !(1..=MAX_TARGETS_LIMIT).contains(&self.max_targets) {
+            return Err(anyhow::anyhow!("max_targets must be between 1 and {}", MAX_TARGETS_LIMIT));
+        }
+
+        if self.undo_window > MAX_UNDO_WINDOW {
+            return Err(anyhow::anyhow!("undo_window must be at most {} seconds", MAX_UNDO_WINDOW));
+        }
+
+        if let Some(scaling) = &self.cooldown_scaling {
+            scaling.validate()
+                .context("invalid cooldown_scaling")?;
+        }
 
-        // refresh cache
-        self.backend_manager.refresh_cache(backend, endpoint).await?;
+        if self.backend_timeout == Some(0) {
+            return Err(anyhow::anyhow!("backend_timeout must be at least 1 second"));
+        }
 
-        status.context("failed to send response")?;
+        if !(0.0..=1.0).contains(&self.footer_fact_probability) {
+            return Err(anyhow::anyhow!("footer_fact_probability must be between 0.0 and 1.0"));
+        }
+
+        self.utc_offset.parse::<FixedOffset>()
+            .map_err(|_| anyhow::anyhow!("utc_offset '{}' is not in the format '+HH:MM'", self.utc_offset))?;
+
+        for (category, color) in &self.category_colors {
+            if crate::color::parse(color).is_none() {
+                return Err(anyhow::anyhow!("color '{}' of category '{}' is not in the format '#rrggbb'", color, category));
+            }
+        }
 
         Ok(())
     }
 
 }
+
+impl Default for ReactionSettings {
+    fn default() -> Self {
+        Self {
+            default_target: DefaultTarget::default(),
+            selection_strategy: backend::SelectionStrategy::default(),
+            report_cooldown: default_report_cooldown(),
+            notify_owners_on_report: false,
+            cooldown: 0,
+            cooldown_response: default_cooldown_response(),
+            cooldown_scaling: None,
+            refresh_workers: default_refresh_workers(),
+            refresh_queue_size: default_refresh_queue_size(),
+            refresh_delay: default_refresh_delay(),
+            background_refresh: 0,
+            background_refresh_jitter: default_background_refresh_jitter(),
+            shared_cache: None,
+            typing_indicator: false,
+            ping_target: true,
+            allowed_mentions: MentionPolicy::default(),
+            sanitization: Sanitization::default(),
+            max_pool_size: None,
+            healthy_pool_size: None,
+            cache_memory_budget: None,
+            backend_timeout: None,
+            bot_responses: true,
+            other_bot_responses: false,
+            anonymous_reactions: false,
+            seed_from_interaction: false,
+            nsfw_spoiler: false,
+            max_download_size: default_max_download_size(),
+            avoid_repeats: false,
+            dedup_scope: DedupScope::default(),
+            dedup_window: None,
+            no_variety: NoVarietyBehavior::default(),
+            no_variety_response: default_no_variety_response(),
+            unavailable_response: default_unavailable_response(),
+            alias_description: default_alias_description(),
+            premium_response: default_premium_response(),
+            warmup: WarmupMode::default(),
+            warm_reactions: Vec::new(),
+            warm_priority: Vec::new(),
+            warm_priority_by_usage: false,
+            warm_budget: None,
+            cache_format: cache::CacheFormat::default(),
+            warmup_progress_interval: default_warmup_progress_interval(),
+            warmup_channel: None,
+            branding: None,
+            source_style: SourceStyle::default(),
+            random_selection: RandomSelection::default(),
+            category_colors: HashMap::new(),
+            footer_facts: Vec::new(),
+            footer_fact_probability: default_footer_fact_probability(),
+            max_targets: default_max_targets(),
+            utc_offset: default_utc_offset(),
+            response_attempts: default_response_attempts(),
+            undo_window: default_undo_window()
+        }
+    }
+}
+
+fn default_footer_fact_probability() -> f64 {
+    0.1
+}
+
+fn default_scaling_window() -> u64 {
+    60
+}
+
+fn default_max_multiplier() -> f64 {
+    2.0
+}
+
+fn default_cooldown_response() -> String {
+    "Please wait {remaining} more seconds before using this reaction again!".to_string()
+}
+
+fn default_no_variety_response() -> String {
+    "There's no new gif for this reaction right now, please try again later!".to_string()
+}
+
+fn default_unavailable_response() -> String {
+    "This reaction is temporarily unavailable, please try again later!".to_string()
+}
+
+fn default_alias_description() -> String {
+    "[Alias for /{command} {name}] {description}".to_string()
+}
+
+fn default_premium_response() -> String {
+    "This reaction is only available to supporters!".to_string()
+}
+
+fn default_response_attempts() -> u32 {
+    2
+}
+
+fn default_max_download_size() -> usize {
+    download::MAX_DOWNLOAD_SIZE
+}
+
+fn default_utc_offset() -> String {
+    "+00:00".to_string()
+}
+
+fn default_undo_window() -> u64 {
+    60
+}
+
+fn default_max_targets() -> usize {
+    5
+}
+
+fn default_warmup_progress_interval() -> usize {
+    10
+}
+
+fn default_report_cooldown() -> u64 {
+    60
+}
+
+fn default_refresh_workers() -> usize {
+    2
+}
+
+fn default_refresh_queue_size() -> usize {
+    64
+}
+
+fn default_background_refresh_jitter() -> f64 {
+    0.1
+}
+
+fn default_refresh_delay() -> u64 {
+    200
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Name of the file the usage stats were persisted to before they moved to the store
+const USAGE_FILE: &str = "usage.json";
+
+/// Upper limit of the maximum number of targets, keeping responses mentioning every target
+/// well below the message length limit of discord
+const MAX_TARGETS_LIMIT: usize = 25;
+
+/// Name replacing the user in anonymous reactions
+const ANONYMOUS_NAME: &str = "Someone";
+
+/// Name of the group command of reactions without a category
+pub const DEFAULT_GROUP: &str = "reaction";
+
+/// Maximum number of subcommands of a command
+const MAX_SUBCOMMANDS: usize = 25;
+
+/// Maximum length of a category, leaving room for the number of split commands
+const MAX_CATEGORY_LENGTH: usize = 30;
+
+/// Maximum length of a command or subcommand name allowed by discord
+const MAX_NAME_LENGTH: usize = 32;
+
+/// Maximum length of a command or subcommand description allowed by discord
+const MAX_DESCRIPTION_LENGTH: usize = 100;
+
+/// Maximum number of gifs in a gallery, as discord shows at most 4 images of an embed group
+const MAX_GALLERY_SIZE: usize = 4;
+
+/// Upper limit of the undo window in seconds, as interaction tokens expire after 15 minutes
+const MAX_UNDO_WINDOW: u64 = 15 * 60;
+
+/// Maximum number of remembered gifs and responses for avoiding repeats
+const MAX_DEDUP_ENTRIES: usize = 10_000;
+
+/// Upper limit of the window in seconds the reaction volume of a guild is counted over
+const MAX_SCALING_WINDOW: u64 = 60 * 60;
+
+/// Upper limit of the multiplier of scaled cooldowns
+const MAX_COOLDOWN_MULTIPLIER: f64 = 10.0;
+
+/// Delay before retrying a failed response, doubled after every attempt
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Upper limit of the delay before retrying a failed response
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Namespace of the store keys of the module
+const STORE_NAMESPACE: &str = "reaction";
+
+/// Store key the reactions allowed and denied in each guild are persisted to
+const GUILDS_KEY: &str = "guilds";
+
+/// Store key the favorite reactions of each user are persisted to
+const FAVORITES_KEY: &str = "favorites";
+
+/// Store key the pronouns of each user are persisted to
+const PRONOUNS_KEY: &str = "pronouns";
+
+/// Store key the usage stats are persisted to
+const USAGE_KEY: &str = "usage";
+
+/// Store key the running cooldowns are persisted to
+const COOLDOWNS_KEY: &str = "cooldowns";
+
+/// A running cooldown as persisted to the store
+#[derive(Serialize, Deserialize)]
+struct PersistedCooldown {
+    /// User that used the reaction
+    user: UserId,
+    /// Name of the reaction
+    reaction: String,
+    /// Unix time in milliseconds the reaction was used at
+    used: i64
+}
+
+/// Reaction module
+pub struct ReactionModule {
+    /// List of reactions
+    reactions: Vec<Reaction>,
+    /// Names of the group commands holding the reactions as subcommands
+    groups: Vec<String>,
+    /// List of reaction names with aliases
+    aliases: Vec<String>,
+    /// Paths of the reaction commands mapped to their reaction, so the bot tracks running reactions by name
+    command_keys: CommandKeys,
+    /// Commands of the module as last registered with discord
+    commands: Vec<CreateCommand>,
+    /// Default contexts of the commands, cached when the bot is ready
+    command_contexts: CommandContexts,
+    /// Settings of the reaction module
+    settings: ReactionSettings,
+    /// Settings of each backend by id
+    backend_settings: HashMap<String, BackendSettings>,
+    /// Backend manager
+    backend_manager: backend::BackendManager,
+    /// Analytics log, if enabled
+    analytics: Option<analytics::AnalyticsLog>,
+    /// Number of uses of each reaction by name
+    usage: HashMap<String, u64>,
+    /// Directory older versions persisted the usage stats to, if any
+    data_directory: Option<PathBuf>,
+    /// Store persisting shared state
+    store: Arc<dyn Store>,
+    /// Owners of the bot
+    owners: Vec<UserId>,
+    /// Backend id and url of the last gif served in each channel
+    last_served: HashMap<ChannelId, (String, String)>,
+    /// Url, response template and time of the last response of each reaction, for debugging
+    last_performed: HashMap<String, (String, String, Instant)>,
+    /// Backend id, url and time of the last gif shown in each dedup scope
+    recently_served: HashMap<DedupKey, (String, String, Instant)>,
+    /// Template and time of the last response of each reaction shown in each dedup scope
+    recent_responses: HashMap<(DedupKey, String), (String, Instant)>,
+    /// Time of the last report of each user
+    last_report: HashMap<UserId, Instant>,
+    /// Time of the last use of each reaction by each user, keyed by the reaction name so
+    /// the subcommand, the alias and the menu share one cooldown
+    last_use: HashMap<(UserId, String), Instant>,
+    /// Times of the recent uses of reactions in each guild, for scaling cooldowns
+    guild_uses: HashMap<GuildId, VecDeque<Instant>>,
+    /// Current page and creation time of each reaction list message
+    list_pages: HashMap<MessageId, (usize, Instant)>,
+    /// Interaction token and time of the last response of each user in each channel, for undoing it
+    last_sent: HashMap<(UserId, ChannelId), (String, Instant)>,
+    /// Reactions allowed and denied in each guild
+    guild_reactions: HashMap<GuildId, guild::GuildReactions>,
+    /// Seasonal themes replacing backends and responses of reactions
+    seasonal: Vec<seasonal::SeasonalTheme>,
+    /// Favorite reactions of each user
+    favorites: HashMap<UserId, BTreeSet<String>>,
+    /// Pronouns of each user that didn't pick the default ones
+    pronouns: HashMap<UserId, pronouns::Pronouns>,
+    /// User id of the bot, cached when the bot is ready
+    bot_id: Option<UserId>,
+    /// Avatar url of the bot, cached when the bot is ready
+    bot_avatar: Option<String>,
+    /// Display name of the bot, cached when the bot is ready
+    bot_name: Option<String>,
+    /// Http client for downloading gifs
+    http: reqwest::Client,
+    /// Audit log settings, if enabled
+    audit_log: Option<audit::AuditLogSettings>,
+    /// Http client of the bot, cached when the bot is ready
+    discord: Option<Arc<Http>>,
+    /// Random number generator used for picking gifs and responses
+    rng: StdRng
+}
+
+impl ReactionModule {
+
+    ///
+    /// Create a new reaction module
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store persisting shared state
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the reaction module
+    ///
+    /// # Errors
+    ///
+    /// If the backend manager fails to initialize
+    ///
+    pub fn new(store: Arc<dyn Store>) -> Result<Self, anyhow::Error> {
+        Self::with_rng(store, StdRng::from_entropy())
+    }
+
+    ///
+    /// Create a new reaction module with a specific random number generator
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store persisting shared state
+    /// * `rng` - The random number generator to use
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the reaction module
+    ///
+    /// # Errors
+    ///
+    /// If the backend manager or the http client for downloading gifs fails to initialize
+    ///
+    pub fn with_rng(store: Arc<dyn Store>, rng: StdRng) -> Result<Self, anyhow::Error> {
+        info!(target: "module/reaction", "creating reaction module");
+        let backend_manager = backend::BackendManager::builder().build()?;
+
+        Ok(Self {
+            reactions: Vec::new(),
+            groups: Vec::new(),
+            aliases: Vec::new(),
+            command_keys: CommandKeys::default(),
+            commands: Vec::new(),
+            command_contexts: CommandContexts::default(),
+            settings: ReactionSettings::default(),
+            backend_settings: HashMap::new(),
+            backend_manager,
+            analytics: None,
+            usage: HashMap::new(),
+            data_directory: None,
+            store,
+            owners: Vec::new(),
+            last_served: HashMap::new(),
+            last_performed: HashMap::new(),
+            recently_served: HashMap::new(),
+            recent_responses: HashMap::new(),
+            last_report: HashMap::new(),
+            last_use: HashMap::new(),
+            guild_uses: HashMap::new(),
+            list_pages: HashMap::new(),
+            last_sent: HashMap::new(),
+            guild_reactions: HashMap::new(),
+            seasonal: Vec::new(),
+            favorites: HashMap::new(),
+            pronouns: HashMap::new(),
+            bot_id: None,
+            bot_avatar: None,
+            bot_name: None,
+            http: reqwest::Client::builder().timeout(download::DOWNLOAD_TIMEOUT).build()
+                .context("failed to create http client")?,
+            audit_log: None,
+            discord: None,
+            rng
+        })
+    }
+
+    ///
+    /// Load the state persisted by previous runs
+    ///
+    /// Unreadable parts of the state are logged and dropped, so a corrupt or outdated state
+    /// doesn't keep the bot from starting.
+    ///
+    async fn load_state(&mut self) {
+        match self.load_value::<HashMap<String, u64>>(USAGE_KEY, "usage stats").await {
+            Some(usage) => self.usage = usage,
+            None => if let Some(data_directory) = &self.data_directory {
+                // usage stats were persisted to their own file before
+                match persistence::load_json(&data_directory.join(USAGE_FILE)) {
+                    Ok(usage) => self.usage = usage.unwrap_or_default(),
+                    Err(e) => warn!(target: "module/reaction", "dropping unreadable usage stats: {:?}", e)
+                }
+            }
+        }
+        if let Some(cooldowns) = self.load_value::<Vec<PersistedCooldown>>(COOLDOWNS_KEY, "cooldowns").await {
+            let now = chrono::Utc::now().timestamp_millis();
+            for cooldown in cooldowns {
+                let elapsed = Duration::from_millis(now.saturating_sub(cooldown.used).max(0) as u64);
+                let Some(used) = Instant::now().checked_sub(elapsed) else {
+                    continue;
+                };
+                if self.cooldown_may_run(&cooldown.reaction, elapsed) {
+                    self.last_use.insert((cooldown.user, cooldown.reaction), used);
+                }
+            }
+        }
+        match cache::load(&*self.store, self.settings.cache_format).await {
+            Ok(Some(cache)) => self.backend_manager.import_cache(cache),
+            Ok(None) => {},
+            Err(e) => warn!(target: "module/reaction", "dropping unreadable cache: {:?}", e)
+        }
+        if let Some(guilds) = self.load_value(GUILDS_KEY, "guild reactions").await {
+            self.guild_reactions = guilds;
+        }
+        if let Some(favorites) = self.load_value(FAVORITES_KEY, "favorites").await {
+            self.favorites = favorites;
+
+            // prune favorites of reactions that no longer exist
+            for favorites in self.favorites.values_mut() {
+                favorites.retain(|name| self.reactions.iter().any(|r| &r.name == name));
+            }
+            self.favorites.retain(|_, favorites| !favorites.is_empty());
+        }
+        if let Some(pronouns) = self.load_value(PRONOUNS_KEY, "pronouns").await {
+            self.pronouns = pronouns;
+        }
+    }
+
+    ///
+    /// Load a json value persisted to the store
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The store key of the value
+    /// * `what` - What the value is, used in the log
+    ///
+    /// # Returns
+    ///
+    /// The value or None if it wasn't persisted or is unreadable, which is logged
+    ///
+    async fn load_value<T: serde::de::DeserializeOwned>(&self, key: &str, what: &str) -> Option<T> {
+        let value = self.store.get(STORE_NAMESPACE, key).await
+            .and_then(|value| value.map(|value| serde_json::from_str(&value)).transpose().map_err(anyhow::Error::from));
+        match value {
+            Ok(value) => value,
+            Err(e) => {
+                warn!(target: "module/reaction", "dropping unreadable {}: {:?}", what, e);
+                None
+            }
+        }
+    }
+
+    ///
+    /// Check whether the cooldown of a reaction may still be running
+    ///
+    /// # Arguments
+    ///
+    /// * `reaction` - The name of the reaction
+    /// * `elapsed` - The time since the reaction was used
+    ///
+    /// # Returns
+    ///
+    /// False if the reaction no longer exists or its cooldown ended even at the largest multiplier
+    ///
+    fn cooldown_may_run(&self, reaction: &str, elapsed: Duration) -> bool {
+        let Some(reaction) = self.reactions.iter().find(|r| r.name == reaction) else {
+            return false;
+        };
+        let multiplier = self.settings.cooldown_scaling.as_ref().map_or(1.0, |scaling| scaling.max_multiplier);
+        elapsed < Duration::from_secs(reaction.cooldown.unwrap_or(self.settings.cooldown)).mul_f64(multiplier)
+    }
+
+    ///
+    /// Load the reactions of a configuration and create their commands without connecting to discord
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration
+    ///
+    /// # Returns
+    ///
+    /// The commands [`Module::init`] would register
+    ///
+    #[cfg(any(test, feature = "testing"))]
+    pub fn init_commands(&mut self, config: &Configuration) -> Vec<CreateCommand> {
+        self.reactions = config.active_reactions();
+        self.settings = config.reaction_settings.clone();
+        self.command_contexts = config.command_contexts.clone();
+
+        let (commands, groups, aliases) = self.create_commands();
+        self.groups = groups;
+        self.aliases = aliases;
+        self.update_command_keys();
+        commands
+    }
+
+    ///
+    /// Create the commands of the reactions and the utility command
+    ///
+    /// # Returns
+    ///
+    /// The command creation requests, the names of the group commands and the names of the reactions with aliases
+    ///
+    fn create_commands(&self) -> (Vec<CreateCommand>, Vec<String>, Vec<String>) {
+        // split reactions into group commands of 25 options
+        let groups = command_groups(&self.reactions);
+        let mut commands: Vec<CreateCommand> = groups.iter().map(|(name, batch)| {
+            // create command
+            // allow the command wherever any of its reactions can be used
+            let contexts = batch.iter().skip(1).fold(
+                batch[0].contexts.as_ref().unwrap_or(&self.command_contexts).clone(),
+                |contexts, r| contexts.union(r.contexts.as_ref().unwrap_or(&self.command_contexts))
+            );
+
+            info!(target: "module/reaction", "creating command '{}' with {} options", name, batch.len());
+            contexts.apply(CreateCommand::new(name))
+                .description("React to someone with an animated gif.")
+                .set_options(
+                    batch.iter().map(|i| {
+                        target_options(self.settings.max_targets, self.settings.anonymous_reactions).into_iter().fold(
+                            CreateCommandOption::new(CommandOptionType::SubCommand, &i.name, &i.description),
+                            |subcommand, option| subcommand.add_sub_option(option)
+                        )
+                    }).collect()
+                )
+        }).collect();
+
+        // create individual commands for reactions with alias
+        let mut aliases: Vec<String> = Vec::new();
+        self.reactions.iter().filter(|r| r.alias).for_each(|r| {
+            let description = render_alias_description(r, &self.settings.alias_description, &groups);
+            info!(target: "module/reaction", "creating alias command '{}'", r.name);
+            let contexts = r.contexts.as_ref().unwrap_or(&self.command_contexts);
+            commands.push(contexts.apply(CreateCommand::new(r.name.clone()))
+                .description(description)
+                .set_options(target_options(self.settings.max_targets, self.settings.anonymous_reactions))
+            );
+            aliases.push(r.name.clone());
+        });
+
+        // create utility command
+        info!(target: "module/reaction", "creating utility command '{}'", utility::UTILITY_COMMAND);
+        commands.push(utility::create_command(&self.command_contexts, self.settings.max_targets, self.settings.anonymous_reactions));
+
+        // create pronouns command
+        info!(target: "module/reaction", "creating pronouns command '{}'", pronouns::PRONOUNS_COMMAND);
+        commands.push(pronouns::create_command(&self.command_contexts));
+
+        let groups = groups.into_iter().map(|(name, _)| name).collect();
+        (commands, groups, aliases)
+    }
+
+    ///
+    /// Map the paths of the group subcommands and aliases to the names of their reactions
+    ///
+    /// This way `/hug` and `/<group> hug` are tracked as the same running command, like they share a cooldown.
+    ///
+    fn update_command_keys(&self) {
+        let mut keys = HashMap::new();
+        for (group, batch) in command_groups(&self.reactions) {
+            for reaction in batch {
+                keys.insert(format!("{} {}", group, reaction.name), reaction.name.clone());
+            }
+        }
+        for reaction in self.reactions.iter().filter(|r| r.alias) {
+            keys.insert(reaction.name.clone(), reaction.name.clone());
+        }
+        *self.command_keys.write().unwrap_or_else(|e| e.into_inner()) = keys;
+    }
+
+    ///
+    /// Get the user id of the bot
+    ///
+    /// # Arguments
+    ///
+    /// * `application_id` - The application id of the interaction, used if the bot user id isn't cached
+    ///
+    /// # Returns
+    ///
+    /// The user id of the bot
+    ///
+    fn bot_id(&self, application_id: ApplicationId) -> UserId {
+        self.bot_id.unwrap_or_else(|| UserId::new(application_id.get()))
+    }
+
+    ///
+    /// Check whether the bot responses apply to a target
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The target of the reaction
+    /// * `bot` - The user id of the bot
+    /// * `resolved` - The users resolved by the interaction, used for the bot flag of other users
+    ///
+    /// # Returns
+    ///
+    /// Whether the target is the bot, or any bot if other bot responses are enabled
+    ///
+    fn is_bot_target(&self, target: UserId, bot: UserId, resolved: &CommandDataResolved) -> bool {
+        target == bot || (self.settings.other_bot_responses && resolved.users.get(&target).is_some_and(|user| user.bot))
+    }
+
+    ///
+    /// Get the endpoints to warm before all others
+    ///
+    /// # Returns
+    ///
+    /// The endpoints as 'backend/endpoint' of the priority reactions in order, followed by
+    /// those of the used reactions by descending usage if enabled
+    ///
+    fn warm_priority(&self) -> Vec<String> {
+        let mut reactions: Vec<&Reaction> = self.settings.warm_priority.iter()
+            .filter_map(|name| self.reactions.iter().find(|r| &r.name == name))
+            .collect();
+        if self.settings.warm_priority_by_usage {
+            let mut used: Vec<&Reaction> = self.reactions.iter()
+                .filter(|r| !self.settings.warm_priority.contains(&r.name) && self.usage.get(&r.name).is_some_and(|uses| *uses > 0))
+                .collect();
+            used.sort_by_key(|r| std::cmp::Reverse(self.usage[&r.name]));
+            reactions.extend(used);
+        }
+
+        let mut endpoints = Vec::new();
+        for info in reactions.iter().flat_map(|r| r.backends.iter().chain(&r.rare_backends)) {
+            let Some((backend, combined)) = info.split_once('/') else {
+                continue;
+            };
+            for endpoint in combined.split('+').map(|endpoint| format!("{}/{}", backend, endpoint)) {
+                if !endpoints.contains(&endpoint) {
+                    endpoints.push(endpoint);
+                }
+            }
+        }
+        endpoints
+    }
+
+    ///
+    /// Seed the random number generator from an interaction id, if enabled
+    ///
+    /// # Arguments
+    ///
+    /// * `interaction` - The id of the interaction
+    ///
+    /// # Returns
+    ///
+    /// The replaced random number generator to restore once the reaction is performed
+    ///
+    fn seed_rng(&mut self, interaction: u64) -> Option<StdRng> {
+        if !self.settings.seed_from_interaction {
+            return None;
+        }
+
+        debug!(target: "module/reaction", "seeding random number generator with interaction id {}", interaction);
+        Some(std::mem::replace(&mut self.rng, StdRng::seed_from_u64(interaction)))
+    }
+
+    ///
+    /// Restore the random number generator replaced by a seeded one
+    ///
+    /// # Arguments
+    ///
+    /// * `previous` - The replaced random number generator, if any
+    ///
+    fn restore_rng(&mut self, previous: Option<StdRng>) {
+        if let Some(previous) = previous {
+            self.rng = previous;
+        }
+    }
+
+    ///
+    /// Check whether endpoints that aren't cached yet are warmed when they are first used
+    ///
+    /// # Returns
+    ///
+    /// Whether the cache is warmed lazily or the startup warmup may defer endpoints beyond its budget
+    ///
+    fn warms_on_first_use(&self) -> bool {
+        self.settings.warmup == WarmupMode::Lazy || self.settings.warm_budget.is_some()
+    }
+
+    ///
+    /// Check whether a reaction may have to warm an endpoint before responding
+    ///
+    /// # Arguments
+    ///
+    /// * `reaction` - The reaction
+    ///
+    /// # Returns
+    ///
+    /// Whether endpoints are warmed on first use and an endpoint of the reaction isn't cached yet
+    ///
+    fn needs_warming(&self, reaction: &Reaction) -> bool {
+        self.warms_on_first_use() && reaction.backends.iter().chain(&reaction.rare_backends)
+            .filter_map(|info| info.split_once('/'))
+            .any(|(backend, endpoint)| !self.backend_manager.is_cached(backend, endpoint))
+    }
+
+    ///
+    /// Check whether a reaction can be used in a guild
+    ///
+    /// # Arguments
+    ///
+    /// * `guild` - The guild the reaction is used in, if any
+    /// * `name` - The name of the reaction
+    ///
+    /// # Returns
+    ///
+    /// Whether the guild doesn't disable the reaction
+    ///
+    fn guild_allows(&self, guild: Option<GuildId>, name: &str) -> bool {
+        guild.and_then(|guild| self.guild_reactions.get(&guild)).is_none_or(|reactions| reactions.allows(name))
+    }
+
+    ///
+    /// Get the embed color of a reaction
+    ///
+    /// # Arguments
+    ///
+    /// * `reaction` - The reaction
+    ///
+    /// # Returns
+    ///
+    /// The color of the category of the reaction, or a random color if it has none
+    ///
+    fn category_color(&self, reaction: &Reaction) -> Colour {
+        self.settings.category_colors.get(reaction.category.as_deref().unwrap_or(DEFAULT_GROUP))
+            .and_then(|color| crate::color::parse(color))
+            .unwrap_or_else(crate::color::rand)
+    }
+
+    ///
+    /// Build the upsell response for a reaction requiring an entitlement the user doesn't have
+    ///
+    /// # Arguments
+    ///
+    /// * `reaction` - The reaction
+    /// * `entitlements` - The entitlements of the interaction
+    ///
+    /// # Returns
+    ///
+    /// The upsell response, or None if the reaction requires no entitlement or an active one is present
+    ///
+    #[cfg(feature = "entitlements")]
+    fn upsell(&self, reaction: &Reaction, entitlements: &[Entitlement]) -> Option<CreateInteractionResponseMessage> {
+        let sku = reaction.sku?;
+        let now = chrono::Utc::now().timestamp();
+        let active = entitlements.iter().any(|entitlement| entitlement.sku_id == sku
+            && !entitlement.deleted
+            && entitlement.consumed != Some(true)
+            && entitlement.ends_at.is_none_or(|ends_at| ends_at.unix_timestamp() > now));
+        if active {
+            return None;
+        }
+
+        Some(CreateInteractionResponseMessage::new()
+            .content(&self.settings.premium_response)
+            .components(vec![CreateActionRow::Buttons(vec![CreateButton::new_premium(sku)])])
+            .ephemeral(true))
+    }
+
+    ///
+    /// Apply the seasonal theme of the current day to a reaction
+    ///
+    /// # Arguments
+    ///
+    /// * `reaction` - The reaction
+    ///
+    /// # Returns
+    ///
+    /// The reaction with the overlay of the active theme applied, unchanged if no theme is active
+    ///
+    fn seasonal_reaction(&self, mut reaction: Reaction) -> Reaction {
+        let offset = self.settings.utc_offset.parse::<FixedOffset>().unwrap_or(FixedOffset::east_opt(0).expect("valid offset"));
+        let today = chrono::Utc::now().with_timezone(&offset).date_naive();
+        let overlay = seasonal::active_theme(&self.seasonal, today)
+            .and_then(|theme| theme.reactions.get(&reaction.name).map(|overlay| (theme, overlay)));
+        if let Some((theme, overlay)) = overlay {
+            trace!(target: "module/reaction", "applying seasonal theme '{}' to reaction '{}'", theme.name, reaction.name);
+            overlay.apply(&mut reaction);
+        }
+        reaction
+    }
+
+    ///
+    /// Get the avatar of the target of a reaction
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The user performing the reaction
+    /// * `target` - The target of the reaction
+    /// * `resolved` - The users resolved by the interaction
+    ///
+    /// # Returns
+    ///
+    /// The avatar url of the target, or None if the target wasn't resolved
+    ///
+    fn avatar_of(&self, user: &User, target: UserId, resolved: &CommandDataResolved) -> Option<String> {
+        if target == user.id {
+            Some(user.face())
+        } else if Some(target) == self.bot_id {
+            self.bot_avatar.clone()
+        } else {
+            resolved.users.get(&target).map(|u| u.face())
+        }
+    }
+
+    ///
+    /// Fetch a gif for a reaction, from the cache or from backends requiring the avatar of the target
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend to fetch the gif from
+    /// * `endpoint` - The endpoint to fetch the gif from
+    /// * `user` - The user performing the reaction
+    /// * `target` - The target of the reaction
+    /// * `channel` - The channel the reaction is performed in
+    /// * `resolved` - The users resolved by the interaction, used for avatars
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the url of the gif
+    ///
+    /// # Errors
+    ///
+    /// If no gif is cached or the avatar of the target can't be turned into a gif
+    ///
+    #[cfg_attr(not(feature = "avatar-backends"), allow(unused_variables))]
+    async fn fetch_gif(&mut self, backend: &str, endpoint: &str, user: &User, target: UserId, channel: ChannelId, resolved: &CommandDataResolved) -> Result<String, anyhow::Error> {
+        // turn the avatar of the target into a gif
+        #[cfg(feature = "avatar-backends")]
+        if self.backend_manager.requires_context(backend) {
+            let avatar = self.avatar_of(user, target, resolved)
+                .context("no avatar of the target")?;
+
+            return self.backend_manager.fetch_with_context(backend, endpoint, &backend::FetchContext { avatar }).await
+                .context("failed to fetch gif for the avatar of the target");
+        }
+
+        // warm the endpoint on first use
+        if self.warms_on_first_use() && !self.backend_manager.is_cached(backend, endpoint) {
+            debug!(target: "module/reaction", "warming '{}' endpoint on backend '{}' on first use", endpoint, backend);
+            if let Err(e) = self.backend_manager.refresh_cache(backend, endpoint).await {
+                warn!(target: "module/reaction", "failed to warm '{}' endpoint on backend '{}': {:#}", endpoint, backend, e);
+            }
+        }
+
+        let window = self.settings.dedup_window.map(Duration::from_secs);
+        let exclude = self.dedup_key(user.id, channel)
+            .and_then(|key| self.recently_served.get(&key))
+            .filter(|(served_backend, _, served)| served_backend == backend && window.is_none_or(|window| served.elapsed() < window))
+            .map(|(_, url, _)| url.as_str());
+        match self.backend_manager.get_cached(&mut self.rng, backend, endpoint, exclude) {
+            Err(e) if e.is::<backend::NoVariety>() && self.settings.no_variety == NoVarietyBehavior::Repeat => {
+                self.backend_manager.get_cached(&mut self.rng, backend, endpoint, None)
+            },
+            Err(e) if e.is::<backend::NoVariety>() => return Err(e),
+            image_url => image_url
+        }.context("no cached gif")
+    }
+
+    ///
+    /// Find the reaction requested by a command, either an alias or a subcommand of a group command
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - The command interaction
+    ///
+    /// # Returns
+    ///
+    /// The options of the reaction and the reaction, or None if a group command was executed without a subcommand
+    ///
+    /// # Errors
+    ///
+    /// If the command doesn't name a known reaction
+    ///
+    fn requested_reaction<'a>(&self, cmd: &'a CommandInteraction) -> Result<Option<(&'a [CommandDataOption], Reaction)>, anyhow::Error> {
+        if !self.groups.contains(&cmd.data.name) {
+            let reaction = self.reactions.iter().find(|r| r.name == cmd.data.name)
+                .context("unknown reaction")?;
+            return Ok(Some((&cmd.data.options, reaction.clone())));
+        }
+
+        // discord shouldn't send group commands without a subcommand, but point the user at one if it does
+        let subcommand = cmd.data.options.first()
+            .and_then(|subcommand| match &subcommand.value {
+                CommandDataOptionValue::SubCommand(o) => Some((subcommand, o)),
+                _ => None
+            });
+        let Some((subcommand, options)) = subcommand else {
+            return Ok(None);
+        };
+        let reaction = self.reactions.iter().find(|r| r.name == subcommand.name)
+            .context("unknown reaction")?;
+        Ok(Some((options, reaction.clone())))
+    }
+
+    ///
+    /// Get the target of a reaction from its options, falling back to the default target
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The options of the reaction
+    /// * `user` - The user using the reaction
+    /// * `bot` - The user id of the bot
+    ///
+    /// # Returns
+    ///
+    /// The target or None if no target was picked and the default target is an error
+    ///
+    fn requested_target(&self, options: &[CommandDataOption], user: UserId, bot: UserId) -> Option<UserId> {
+        match options.iter().find(|opt| opt.name == "user").and_then(|opt| opt.value.as_user_id()) {
+            Some(target) => Some(target),
+            None => match self.settings.default_target {
+                DefaultTarget::Bot => Some(bot),
+                DefaultTarget::SelfUser => Some(user),
+                DefaultTarget::Error => None
+            }
+        }
+    }
+
+    ///
+    /// Perform a reaction requested by a command and respond with it
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    /// * `reaction` - The reaction to perform
+    /// * `options` - The options of the reaction, holding its targets
+    ///
+    async fn react(&mut self, ctx: serenity::all::Context, cmd: &CommandInteraction, reaction: Reaction, options: &[CommandDataOption]) -> Result<(), anyhow::Error> {
+        let reaction = self.seasonal_reaction(reaction);
+        let invocation = Invocation::Command(cmd);
+        if let Some(refusal) = self.check_usable(invocation, &reaction) {
+            return invocation.create_response(&ctx.http, CreateInteractionResponse::Message(refusal)).await
+                .context("failed to send response");
+        }
+
+        // get target
+        let bot = self.bot_id(cmd.application_id);
+        let Some(target) = self.requested_target(options, cmd.user.id, bot) else {
+            return respond_ephemeral(&ctx, cmd, "Please pick someone to react to!").await;
+        };
+
+        // get additional targets
+        let others = options.iter().find(|opt| opt.name == "others").and_then(|opt| opt.value.as_str())
+            .map(parse_mentions)
+            .unwrap_or_default();
+        let mut targets = vec![target];
+        for other in others {
+            if !targets.contains(&other) {
+                targets.push(other);
+            }
+        }
+        if targets.len() > self.settings.max_targets {
+            let note = format!("You can react to at most {} people at once!", self.settings.max_targets);
+            return respond_ephemeral(&ctx, cmd, note).await;
+        }
+
+        // anonymous reactions are sent to the channel, as interaction responses show who used the command
+        let anonymous = self.settings.anonymous_reactions
+            && options.iter().find(|opt| opt.name == "anonymous").and_then(|opt| opt.value.as_bool()).unwrap_or(false);
+        if anonymous && cmd.guild_id.is_none() {
+            return respond_ephemeral(&ctx, cmd, "Anonymous reactions can only be sent in servers!").await;
+        }
+        if anonymous && targets.contains(&cmd.user.id) {
+            return respond_ephemeral(&ctx, cmd, "You can't react to yourself anonymously!").await;
+        }
+
+        self.perform_and_send(&ctx, invocation, &reaction, &targets, anonymous).await
+    }
+
+    ///
+    /// Check whether a reaction can be used by the user of an interaction right now
+    ///
+    /// # Arguments
+    ///
+    /// * `invocation` - The interaction performing the reaction
+    /// * `reaction` - The reaction to perform
+    ///
+    /// # Returns
+    ///
+    /// The response explaining why the reaction can't be used, or None if it can
+    ///
+    fn check_usable(&mut self, invocation: Invocation<'_>, reaction: &Reaction) -> Option<CreateInteractionResponseMessage> {
+        if let Some(refusal) = self.check_allowed(invocation, reaction) {
+            return Some(refusal);
+        }
+
+        // check cooldown
+        self.check_cooldown(reaction, invocation.user().id, invocation.guild_id())
+            .map(|response| CreateInteractionResponseMessage::new().content(response).ephemeral(true))
+    }
+
+    ///
+    /// Check whether the user of an interaction may use a reaction at all, regardless of its cooldown
+    ///
+    /// # Arguments
+    ///
+    /// * `invocation` - The interaction using the reaction
+    /// * `reaction` - The reaction
+    ///
+    /// # Returns
+    ///
+    /// The response explaining why the reaction can't be used, or None if it can
+    ///
+    fn check_allowed(&self, invocation: Invocation<'_>, reaction: &Reaction) -> Option<CreateInteractionResponseMessage> {
+        let note = |content: &str| Some(CreateInteractionResponseMessage::new().content(content).ephemeral(true));
+
+        // check if the reaction can be used here
+        if !reaction.contexts.as_ref().is_none_or(|contexts| contexts.allows(invocation.context())) {
+            return note("This reaction can't be used here!");
+        }
+
+        if !self.guild_allows(invocation.guild_id(), &reaction.name) {
+            return note("This reaction is disabled in this server!");
+        }
+
+        if let Some(response) = self.check_requirements(reaction, invocation.user().id, invocation.member()) {
+            return note(response);
+        }
+
+        #[cfg(feature = "entitlements")]
+        if let Some(upsell) = self.upsell(reaction, invocation.entitlements()) {
+            return Some(upsell);
+        }
+
+        None
+    }
+
+    ///
+    /// Perform a reaction for an interaction and respond with it
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `invocation` - The interaction performing the reaction
+    /// * `reaction` - The reaction to perform
+    /// * `targets` - The targets of the reaction, starting with the main target
+    /// * `anonymous` - Whether the reaction is sent to the channel without showing the user
+    ///
+    async fn perform_and_send(&mut self, ctx: &serenity::all::Context, invocation: Invocation<'_>, reaction: &Reaction, targets: &[UserId], anonymous: bool) -> Result<(), anyhow::Error> {
+        // defer the response while warming endpoints, downloading a spoiler attachment or showing the typing indicator
+        let deferred = self.needs_warming(reaction) || self.sends_spoiler(reaction) || self.settings.typing_indicator;
+        if deferred {
+            invocation.defer(&ctx.http, anonymous).await
+                .context("failed to defer response")?;
+        }
+        self.show_typing(ctx, invocation.channel_id()).await;
+
+        // perform reaction
+        let user = invocation.user();
+        let bot = self.bot_id(invocation.application_id());
+        let location = Location { channel: invocation.channel_id(), guild: invocation.guild_id(), anonymous };
+        let previous_rng = self.seed_rng(invocation.id().get());
+        let performed = self.perform(reaction, user, targets, bot, location, invocation.resolved()).await;
+        self.restore_rng(previous_rng);
+        let performed = match performed {
+            Err(e) => match self.fallback_response(&reaction.name, &e) {
+                Some(response) if deferred => {
+                    // the first followup takes the place of a public deferred response, so withdraw it first
+                    if !anonymous {
+                        invocation.withdraw_deferral(&ctx.http).await
+                            .context("failed to delete deferred response")?;
+                    }
+                    return invocation.create_followup(&ctx.http, CreateInteractionResponseFollowup::new().content(response).ephemeral(true)).await
+                        .context("failed to send response");
+                },
+                Some(response) => {
+                    return invocation.create_response(&ctx.http, CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().content(response).ephemeral(true)
+                    )).await.context("failed to send response");
+                },
+                None => return Err(e)
+            },
+            Ok(performed) => performed
+        };
+
+        // send response
+        let attachment = self.spoiler_attachment(&performed).await;
+        let spoiler = attachment.is_some();
+        let status = if anonymous {
+            let message = performed.message(attachment);
+            send_with_retry(self.settings.response_attempts, || async {
+                invocation.channel_id().send_message(&ctx.http, message.clone()).await.map(|_| ())
+            }).await
+        } else if deferred {
+            let followup = performed.followup(attachment);
+            send_with_retry(self.settings.response_attempts, || invocation.create_followup(&ctx.http, followup.clone())).await
+        } else {
+            let response = CreateInteractionResponse::Message(performed.response(attachment));
+            send_with_retry(self.settings.response_attempts, || invocation.create_response(&ctx.http, response.clone())).await
+        };
+
+        // explain missing permissions to the user or confirm an anonymous reaction
+        let note = if status.as_ref().is_err_and(is_permission_error) {
+            Some(permission_note(invocation.app_permissions(), spoiler))
+        } else if anonymous && status.is_ok() {
+            Some("Your reaction was sent anonymously!".to_string())
+        } else {
+            None
+        };
+        if let Some(note) = note {
+            let result = if deferred {
+                invocation.create_followup(&ctx.http, CreateInteractionResponseFollowup::new().content(note).ephemeral(true)).await
+            } else {
+                invocation.create_response(&ctx.http, CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(note).ephemeral(true)
+                )).await
+            };
+            if let Err(e) = result {
+                warn!(target: "module/reaction", "failed to send note to the user: {:?}", e);
+            }
+        }
+        self.finish(performed, invocation.channel_id(), invocation.token(), status)
+    }
+
+    ///
+    /// Get the response to send instead of a reaction that couldn't be performed
+    ///
+    /// # Arguments
+    ///
+    /// * `reaction` - The name of the reaction
+    /// * `error` - The error performing the reaction
+    ///
+    /// # Returns
+    ///
+    /// The response if the reaction failed because no gif could be shown, or None for other errors
+    ///
+    fn fallback_response(&self, reaction: &str, error: &anyhow::Error) -> Option<String> {
+        if error.is::<backend::NoVariety>() {
+            return Some(self.settings.no_variety_response.clone());
+        }
+
+        if error.is::<backend::EmptyCache>() {
+            warn!(target: "module/reaction", "reaction '{}' is unavailable: {:#}", reaction, error);
+            return Some(self.settings.unavailable_response.clone());
+        }
+
+        None
+    }
+
+    ///
+    /// Perform a reaction by picking a gif and a response
+    ///
+    /// # Arguments
+    ///
+    /// * `reaction` - The reaction to perform
+    /// * `user` - The user performing the reaction
+    /// * `targets` - The targets of the reaction, starting with the main target
+    /// * `bot` - The user id of the bot
+    /// * `location` - Where the reaction is performed, the dm responses are preferred outside of guilds
+    /// * `resolved` - The users resolved by the interaction, used for display names
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the performed reaction
+    ///
+    /// # Errors
+    ///
+    /// If no backend, gif or response is available
+    ///
+    async fn perform(&mut self, reaction: &Reaction, user: &User, targets: &[UserId], bot: UserId, location: Location, resolved: &CommandDataResolved) -> Result<PerformedReaction, anyhow::Error> {
+        // roll for a rare gif
+        let rare = !reaction.rare_backends.is_empty()
+            && reaction.rare_probability > 0.0
+            && self.rng.gen_bool(reaction.rare_probability);
+        let backends = if rare { &reaction.rare_backends } else { &reaction.backends };
+
+        // pick random backend
+        let backend_info = pick_random(&mut self.rng, backends)
+            .context("no backend")?;
+        let (backend, endpoint) = backend_info.split_once('/')
+            .context("no endpoint")?;
+        let (target, others) = targets.split_first()
+            .context("no target")?;
+        let target = *target;
+
+        // the real user is logged for anonymous reactions as well, for moderation
+        info!(target: "module/reaction", "user @{} ran /reaction {} on <@{}>{}{}{}", user.name, reaction.name, target,
+            if others.is_empty() { String::new() } else { format!(" and {} others", others.len()) },
+            if rare { " (rare)" } else { "" },
+            if location.anonymous { " (anonymous)" } else { "" });
+
+        // fetch reaction gif
+        let image_url = self.fetch_gif(backend, endpoint, user, target, location.channel, resolved).await?;
+
+        // add more cached gifs of the same pool, leaving the gallery out if the pool has no others
+        let gallery = match reaction.gallery {
+            Some(size) if reaction.style == ReactionStyle::Gif => self.backend_manager.get_cached_many(&mut self.rng, backend, endpoint, &image_url, size - 1),
+            _ => Vec::new()
+        };
+
+        // build response
+        let (responses, kind) = if location.anonymous && !reaction.anonymous_responses.is_empty() {
+            (&reaction.anonymous_responses, "anonymous")
+        } else if rare && !reaction.rare_responses.is_empty() {
+            (&reaction.rare_responses, "rare")
+        } else if user.id == target && others.is_empty() {
+            (&reaction.self_responses, "self")
+        } else if self.is_bot_target(target, bot, resolved) && others.is_empty() && self.settings.bot_responses {
+            (&reaction.bot_responses, "bot")
+        } else if location.guild.is_none() && !reaction.dm_responses.is_empty() {
+            (&reaction.dm_responses, "dm")
+        } else {
+            (&reaction.default_responses, "default")
+        };
+
+        // avoid the response shown last in the dedup scope, like the gif
+        let window = self.settings.dedup_window.map(Duration::from_secs);
+        let previous = self.dedup_key(user.id, location.channel)
+            .and_then(|key| self.recent_responses.get(&(key, reaction.name.clone())))
+            .filter(|(_, served)| window.is_none_or(|window| served.elapsed() < window))
+            .map(|(template, _)| template.as_str());
+        let template = pick_fresh_response(&mut self.rng, responses, previous)
+            .with_context(|| format!("no {} response", kind))?;
+
+        // mention the targets or use their display names to avoid pinging them,
+        // names that can't be resolved fall back to the mention or to a neutral word if pinging is off
+        let ping_target = reaction.ping_target.unwrap_or(self.settings.ping_target);
+        let mut mentions = Vec::new();
+        let mut names = Vec::new();
+        for target in targets {
+            let name = if *target == user.id {
+                Some(user.display_name().to_string())
+            } else if let Some(resolved) = resolved.users.get(target) {
+                Some(resolved.display_name().to_string())
+            } else {
+                self.bot_name.clone().filter(|_| *target == bot)
+            };
+            let (mention, name) = target_placeholders(*target, name, ping_target);
+            mentions.push(mention);
+            names.push(name);
+        }
+
+        let (user_mention, user_name) = match location.anonymous {
+            true => (ANONYMOUS_NAME.to_string(), ANONYMOUS_NAME),
+            false => (format!("<@{}>", user.id.get()), user.display_name())
+        };
+        // refer to a single target with their pronouns, several targets are always they/them
+        let pronouns = match targets {
+            [target] => self.pronouns.get(target).copied().unwrap_or_default(),
+            _ => pronouns::Pronouns::default()
+        };
+        let (target_mentions, target_names) = (join_names(&mentions), join_names(&names));
+        let mut placeholders = vec![
+            ("user", user_mention.as_str()),
+            ("user.name", user_name),
+            ("target", &target_mentions),
+            ("target.name", &target_names)
+        ];
+        placeholders.extend(pronouns.placeholders());
+        let mut message = render_response(template, &placeholders, self.settings.sanitization);
+
+        // show the target as the author of a card
+        let author = (reaction.style == ReactionStyle::Card).then(|| {
+            let name = if target == user.id {
+                Some(user.display_name().to_string())
+            } else if let Some(resolved) = resolved.users.get(&target) {
+                Some(resolved.display_name().to_string())
+            } else {
+                self.bot_name.clone().filter(|_| target == bot)
+            }.unwrap_or_else(|| target.to_string());
+            let author = CreateEmbedAuthor::new(name);
+            match self.avatar_of(user, target, resolved) {
+                Some(avatar) => author.icon_url(avatar),
+                None => author
+            }
+        });
+
+        // add source attribution
+        let attribution = self.backend_settings.get(backend).is_none_or(|b| b.attribution);
+        let source_button = attribution && self.settings.source_style == SourceStyle::Button;
+        if source_button {
+            message += format!("\n-# From: {}", backend).as_str();
+        } else if attribution {
+            message += format!("\n-# From: {} • [Source](<{}>)", backend, image_url).as_str();
+        }
+
+        // add a footer fact on its own line
+        if !self.settings.footer_facts.is_empty() && self.rng.gen_bool(self.settings.footer_fact_probability) {
+            if let Some(fact) = pick_random(&mut self.rng, &self.settings.footer_facts) {
+                message += format!("\n-# {}", render_response(fact, &[], self.settings.sanitization)).as_str();
+            }
+        }
+
+        Ok(PerformedReaction {
+            reaction: reaction.name.clone(),
+            user: user.id,
+            target,
+            targets: targets.to_vec(),
+            guild: location.guild,
+            backend_info: backend_info.clone(),
+            backend: backend.to_string(),
+            endpoint: endpoint.to_string(),
+            image_url,
+            gallery,
+            proxy: self.backend_settings.get(backend).and_then(|b| b.proxy.clone()),
+            template: template.to_string(),
+            message,
+            color: self.category_color(reaction),
+            // anonymous reactions only ping their targets, so nobody is pinged by the responses of the user
+            mentions: match (ping_target, self.settings.allowed_mentions) {
+                (false, _) => MentionPolicy::None,
+                (true, MentionPolicy::Users) if location.anonymous => MentionPolicy::Target,
+                (true, mentions) => mentions
+            },
+            spoiler: self.sends_spoiler(reaction),
+            branding: self.settings.branding.clone(),
+            author,
+            source_button,
+            anonymous: location.anonymous
+        })
+    }
+
+    ///
+    /// Check whether the gif of a reaction is hidden behind a spoiler
+    ///
+    /// # Arguments
+    ///
+    /// * `reaction` - The reaction
+    ///
+    /// # Returns
+    ///
+    /// True for nsfw reactions if spoilers are enabled
+    ///
+    fn sends_spoiler(&self, reaction: &Reaction) -> bool {
+        reaction.nsfw && self.settings.nsfw_spoiler
+    }
+
+    ///
+    /// Download the gif of a performed reaction as a spoiler attachment, if required
+    ///
+    /// # Arguments
+    ///
+    /// * `performed` - The performed reaction
+    ///
+    /// # Returns
+    ///
+    /// The spoiler attachment or None if not required or the download failed
+    ///
+    async fn spoiler_attachment(&self, performed: &PerformedReaction) -> Option<CreateAttachment> {
+        if !performed.spoiler {
+            return None;
+        }
+
+        match download::download(&self.http, &performed.image_url, self.settings.max_download_size).await {
+            Ok(contents) => Some(CreateAttachment::bytes(contents, format!("SPOILER_{}.gif", performed.reaction))),
+            Err(e) => {
+                warn!(target: "module/reaction", "failed to download gif for spoiler attachment, sending spoiler link instead: {:?}", e);
+                None
+            }
+        }
+    }
+
+    ///
+    /// Check if a user meets the account and membership age requirements of a reaction
+    ///
+    /// # Arguments
+    ///
+    /// * `reaction` - The reaction to check
+    /// * `user` - The user performing the reaction
+    /// * `member` - The guild member performing the reaction, if used in a guild
+    ///
+    /// # Returns
+    ///
+    /// A response explaining the unmet requirement, or None if all requirements are met
+    ///
+    fn check_requirements(&self, reaction: &Reaction, user: UserId, member: Option<&Member>) -> Option<&'static str> {
+        let now = chrono::Utc::now().timestamp();
+        let too_young = |since: i64, min_age: Option<u64>| min_age.is_some_and(|min_age| now.saturating_sub(since) < min_age as i64);
+
+        if too_young(user.created_at().unix_timestamp(), reaction.min_account_age) {
+            return Some("Your account is too new to use this reaction!");
+        }
+
+        let joined_at = member.and_then(|member| member.joined_at);
+        if joined_at.is_some_and(|joined_at| too_young(joined_at.unix_timestamp(), reaction.min_member_age)) {
+            return Some("You joined this server too recently to use this reaction!");
+        }
+
+        None
+    }
+
+    ///
+    /// Check if a reaction is on cooldown for a user
+    ///
+    /// # Arguments
+    ///
+    /// * `reaction` - The reaction to check
+    /// * `user` - The user using the reaction
+    /// * `guild` - The guild the reaction is used in, if any
+    ///
+    /// # Returns
+    ///
+    /// The cooldown response if the reaction is on cooldown
+    ///
+    fn check_cooldown(&mut self, reaction: &Reaction, user: UserId, guild: Option<GuildId>) -> Option<String> {
+        let cooldown = Duration::from_secs(reaction.cooldown.unwrap_or(self.settings.cooldown))
+            .mul_f64(self.cooldown_multiplier(guild));
+        let elapsed = self.last_use.get(&(user, reaction.name.clone()))?.elapsed();
+        if elapsed >= cooldown {
+            return None;
+        }
+
+        // pick a cooldown response
+        let remaining = (cooldown - elapsed).as_secs_f64().ceil();
+        let expiry = chrono::Utc::now().timestamp() + remaining as i64;
+        let template = pick_random(&mut self.rng, &reaction.cooldown_responses)
+            .unwrap_or(&self.settings.cooldown_response);
+        Some(render_response(template, &[
+            ("remaining", &remaining.to_string()),
+            ("remaining_ts", &format!("<t:{}:R>", expiry))
+        ], self.settings.sanitization))
+    }
+
+    ///
+    /// Get the multiplier of cooldowns in a guild from its recent reaction volume
+    ///
+    /// # Arguments
+    ///
+    /// * `guild` - The guild, if any
+    ///
+    /// # Returns
+    ///
+    /// The multiplier, 1.0 outside of guilds or without cooldown scaling
+    ///
+    fn cooldown_multiplier(&self, guild: Option<GuildId>) -> f64 {
+        let (Some(scaling), Some(guild)) = (&self.settings.cooldown_scaling, guild) else {
+            return 1.0;
+        };
+
+        let window = Duration::from_secs(scaling.window);
+        let uses = self.guild_uses.get(&guild)
+            .map_or(0, |uses| uses.iter().filter(|used| used.elapsed() < window).count());
+        scaling.multiplier(uses)
+    }
+
+    ///
+    /// Show the typing indicator in a channel if enabled
+    ///
+    /// The indicator is shown until the response is posted, so this doesn't wait.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `channel` - The channel to show the typing indicator in
+    ///
+    async fn show_typing(&self, ctx: &serenity::all::Context, channel: ChannelId) {
+        if !self.settings.typing_indicator {
+            return;
+        }
+
+        // the bot can't type in channels it isn't in, e.g. when installed by a user
+        if let Err(e) = channel.broadcast_typing(&ctx.http).await {
+            debug!(target: "module/reaction", "failed to show typing indicator: {:?}", e);
+        }
+    }
+
+    ///
+    /// Get what the last shown gif is remembered for
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The user performing the reaction
+    /// * `channel` - The channel the reaction is performed in
+    ///
+    /// # Returns
+    ///
+    /// The key of the dedup scope or None if repeats aren't avoided
+    ///
+    fn dedup_key(&self, user: UserId, channel: ChannelId) -> Option<DedupKey> {
+        if !self.settings.avoid_repeats {
+            return None;
+        }
+
+        match self.settings.dedup_scope {
+            DedupScope::None => None,
+            DedupScope::Channel => Some(DedupKey::Channel(channel)),
+            DedupScope::User => Some(DedupKey::User(user)),
+            DedupScope::Global => Some(DedupKey::Global)
+        }
+    }
+
+    ///
+    /// Remember the gif and response of a performed reaction for avoiding repeats
+    ///
+    /// Gifs and responses outside of the dedup window are forgotten and the number of
+    /// remembered gifs and responses is bounded by forgetting the oldest ones first.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the dedup scope
+    /// * `performed` - The performed reaction
+    ///
+    fn remember_served(&mut self, key: DedupKey, performed: &PerformedReaction) {
+        if let Some(window) = self.settings.dedup_window.map(Duration::from_secs) {
+            self.recently_served.retain(|_, (_, _, served)| served.elapsed() < window);
+            self.recent_responses.retain(|_, (_, served)| served.elapsed() < window);
+        }
+
+        self.recently_served.insert(key, (performed.backend.clone(), performed.image_url.clone(), Instant::now()));
+        while self.recently_served.len() > MAX_DEDUP_ENTRIES {
+            let Some(oldest) = self.recently_served.iter().min_by_key(|(_, (_, _, served))| *served).map(|(key, _)| *key) else {
+                break;
+            };
+            self.recently_served.remove(&oldest);
+        }
+
+        self.recent_responses.insert((key, performed.reaction.clone()), (performed.template.clone(), Instant::now()));
+        while self.recent_responses.len() > MAX_DEDUP_ENTRIES {
+            let Some(oldest) = self.recent_responses.iter().min_by_key(|(_, (_, served))| *served).map(|(key, _)| key.clone()) else {
+                break;
+            };
+            self.recent_responses.remove(&oldest);
+        }
+    }
+
+    ///
+    /// Finish a performed reaction after its response was sent
+    ///
+    /// # Arguments
+    ///
+    /// * `performed` - The performed reaction
+    /// * `channel` - The channel the response was sent to
+    /// * `token` - The token of the interaction the response was sent to
+    /// * `status` - The result of sending the response
+    ///
+    /// # Errors
+    ///
+    /// If the response failed to send
+    ///
+    fn finish(&mut self, performed: PerformedReaction, channel: ChannelId, token: &str, status: serenity::Result<()>) -> Result<(), anyhow::Error> {
+        if status.is_ok() {
+            self.last_served.insert(channel, (performed.backend.clone(), performed.image_url.clone()));
+            self.last_performed.insert(performed.reaction.clone(), (performed.image_url.clone(), performed.template.clone(), Instant::now()));
+            if let Some(key) = self.dedup_key(performed.user, channel) {
+                self.remember_served(key, &performed);
+            }
+            self.last_use.insert((performed.user, performed.reaction.clone()), Instant::now());
+
+            // count the use towards the volume of the guild, forgetting uses outside the window
+            if let (Some(scaling), Some(guild)) = (&self.settings.cooldown_scaling, performed.guild) {
+                let window = Duration::from_secs(scaling.window);
+                let uses = self.guild_uses.entry(guild).or_default();
+                while uses.front().is_some_and(|used| used.elapsed() >= window) {
+                    uses.pop_front();
+                }
+                uses.push_back(Instant::now());
+            }
+
+            // remember the response for undoing it, anonymous reactions aren't sent as the response
+            if self.settings.undo_window > 0 && !performed.anonymous {
+                let window = Duration::from_secs(self.settings.undo_window);
+                self.last_sent.retain(|_, (_, sent)| sent.elapsed() < window);
+                self.last_sent.insert((performed.user, channel), (token.to_string(), Instant::now()));
+            }
+        }
+
+        // record invocation
+        *self.usage.entry(performed.reaction.clone()).or_insert(0) += 1;
+        if let Some(analytics) = &mut self.analytics {
+            let record = analytics::Record {
+                reaction: &performed.reaction,
+                invoker: performed.user.get(),
+                target: performed.target.get(),
+                timestamp: chrono::Utc::now(),
+                backend: &performed.backend_info
+            };
+            if let Err(e) = analytics.record(&record) {
+                warn!(target: "module/reaction", "failed to record analytics: {:?}", e);
+            }
+        }
+
+        // post to the audit log
+        if let (Some(settings), Some(http)) = (&self.audit_log, &self.discord) {
+            if status.is_ok() && self.rng.gen_bool(settings.sample_rate) {
+                audit::post(http.clone(), settings, audit::Entry {
+                    reaction: performed.reaction.clone(),
+                    invoker: performed.user,
+                    target: performed.target,
+                    channel
+                });
+            }
+        }
+
+        // refresh cache in the background
+        self.backend_manager.enqueue_refresh(&performed.backend, &performed.endpoint);
+
+        status.context("failed to send response")
+    }
+
+}
+
+///
+/// Send a response, retrying when it fails on the network or with a server error
+///
+/// Retries wait for a delay starting at [`RETRY_DELAY`] that doubles after every attempt, up to [`MAX_RETRY_DELAY`].
+///
+/// # Arguments
+///
+/// * `attempts` - The maximum number of attempts, at least 1
+/// * `send` - Sends the response once
+///
+/// # Returns
+///
+/// The result of the last attempt
+///
+async fn send_with_retry<F, Fut>(attempts: u32, send: F) -> serenity::Result<()>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = serenity::Result<()>>
+{
+    let mut attempt = 1;
+    let mut delay = RETRY_DELAY;
+    loop {
+        match send().await {
+            Err(e) if attempt < attempts && is_transient_error(&e) => {
+                warn!(target: "module/reaction", "failed to send response (attempt {}/{}), retrying in {}ms: {:?}", attempt, attempts, delay.as_millis(), e);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+                attempt += 1;
+            },
+            result => return result
+        }
+    }
+}
+
+///
+/// Get the values of the target placeholders for a target
+///
+/// # Arguments
+///
+/// * `target` - The user id of the target
+/// * `name` - The display name of the target, if it could be resolved
+/// * `ping_target` - Whether the target is pinged
+///
+/// # Returns
+///
+/// The value of `{target}`, which is the mention or the name if pinging is off, and the value of `{target.name}`,
+/// which falls back to the mention if pinging is on and to a neutral word otherwise
+///
+fn target_placeholders(target: UserId, name: Option<String>, ping_target: bool) -> (String, String) {
+    let mention = format!("<@{}>", target.get());
+    match (name, ping_target) {
+        (Some(name), true) => (mention, name),
+        (Some(name), false) => (name.clone(), name),
+        (None, true) => (mention.clone(), mention),
+        (None, false) => (mention, NAME_FALLBACK.to_string())
+    }
+}
+
+///
+/// Join the names of the targets of a reaction
+///
+/// # Arguments
+///
+/// * `names` - The names of the targets
+///
+/// # Returns
+///
+/// The names separated by commas and 'and', e.g. 'a, b and c'
+///
+fn join_names(names: &[String]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => name.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last)
+    }
+}
+
+///
+/// Parse the users mentioned in a text
+///
+/// # Arguments
+///
+/// * `text` - The text containing mentions like '<@123>'
+///
+/// # Returns
+///
+/// The mentioned users in order
+///
+fn parse_mentions(text: &str) -> Vec<UserId> {
+    text.split("<@").skip(1)
+        .filter_map(|mention| mention.split_once('>'))
+        .filter_map(|(id, _)| id.trim_start_matches('!').parse().ok())
+        .filter(|id| *id != 0)
+        .map(UserId::new)
+        .collect()
+}
+
+///
+/// Create the target options of a reaction command
+///
+/// # Arguments
+///
+/// * `max_targets` - The maximum number of targets of a reaction
+/// * `anonymous` - Whether reactions can be sent anonymously
+///
+/// # Returns
+///
+/// The user option, followed by an option for mentioning more users if multiple targets are allowed
+/// and an option for hiding the user if anonymous reactions are enabled
+///
+fn target_options(max_targets: usize, anonymous: bool) -> Vec<CreateCommandOption> {
+    let mut options = vec![CreateCommandOption::new(CommandOptionType::User, "user", "The target user.").required(true)];
+    if max_targets > 1 {
+        options.push(CreateCommandOption::new(CommandOptionType::String, "others", format!("Up to {} more users to react to, mentioned like @user.", max_targets - 1)));
+    }
+    if anonymous {
+        options.push(CreateCommandOption::new(CommandOptionType::Boolean, "anonymous", "Hide who sent the reaction."));
+    }
+    options
+}
+
+///
+/// Explain which permissions the bot is missing to send a reaction
+///
+/// # Arguments
+///
+/// * `permissions` - The permissions of the bot in the channel, if known
+/// * `attachment` - Whether the response had an attachment
+///
+/// # Returns
+///
+/// A message for the user listing the missing permissions
+///
+fn permission_note(permissions: Option<Permissions>, attachment: bool) -> String {
+    let mut required = Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES | Permissions::EMBED_LINKS;
+    if attachment {
+        required |= Permissions::ATTACH_FILES;
+    }
+
+    let missing = permissions.map_or(required, |permissions| required - permissions);
+    let names = missing.get_permission_names();
+    if names.is_empty() {
+        return "I'm not allowed to send reactions in this channel!".to_string();
+    }
+
+    format!("I'm not allowed to send reactions in this channel, please ask an admin to give me these permissions: {}", names.join(", "))
+}
+
+/// Where and how a reaction is performed
+#[derive(Clone, Copy)]
+struct Location {
+    /// The channel the reaction is performed in
+    channel: ChannelId,
+    /// The guild the reaction is performed in, if any
+    guild: Option<GuildId>,
+    /// Whether the user is hidden from the response
+    anonymous: bool
+}
+
+/// An interaction performing a reaction
+#[derive(Clone, Copy)]
+enum Invocation<'a> {
+    /// A reaction command
+    Command(&'a CommandInteraction),
+    /// A component of a utility message, like the reaction menu
+    Component(&'a ComponentInteraction)
+}
+
+impl<'a> Invocation<'a> {
+
+    /// The id of the interaction
+    fn id(self) -> InteractionId {
+        match self {
+            Self::Command(cmd) => cmd.id,
+            Self::Component(component) => component.id
+        }
+    }
+
+    /// The id of the application receiving the interaction
+    fn application_id(self) -> ApplicationId {
+        match self {
+            Self::Command(cmd) => cmd.application_id,
+            Self::Component(component) => component.application_id
+        }
+    }
+
+    /// The user performing the reaction
+    fn user(self) -> &'a User {
+        match self {
+            Self::Command(cmd) => &cmd.user,
+            Self::Component(component) => &component.user
+        }
+    }
+
+    /// The guild member performing the reaction, if used in a guild
+    fn member(self) -> Option<&'a Member> {
+        match self {
+            Self::Command(cmd) => cmd.member.as_deref(),
+            Self::Component(component) => component.member.as_ref()
+        }
+    }
+
+    /// The guild the interaction was used in, if any
+    fn guild_id(self) -> Option<GuildId> {
+        match self {
+            Self::Command(cmd) => cmd.guild_id,
+            Self::Component(component) => component.guild_id
+        }
+    }
+
+    /// The channel the interaction was used in
+    fn channel_id(self) -> ChannelId {
+        match self {
+            Self::Command(cmd) => cmd.channel_id,
+            Self::Component(component) => component.channel_id
+        }
+    }
+
+    /// Where the interaction was used
+    fn context(self) -> Option<InteractionContext> {
+        match self {
+            Self::Command(cmd) => cmd.context,
+            Self::Component(component) => component.context
+        }
+    }
+
+    /// The permissions of the bot in the channel, if known
+    fn app_permissions(self) -> Option<Permissions> {
+        match self {
+            Self::Command(cmd) => cmd.app_permissions,
+            Self::Component(component) => component.app_permissions
+        }
+    }
+
+    /// The entitlements of the user
+    #[cfg(feature = "entitlements")]
+    fn entitlements(self) -> &'a [Entitlement] {
+        match self {
+            Self::Command(cmd) => &cmd.entitlements,
+            Self::Component(component) => &component.entitlements
+        }
+    }
+
+    /// The token for responding to the interaction
+    fn token(self) -> &'a str {
+        match self {
+            Self::Command(cmd) => &cmd.token,
+            Self::Component(component) => &component.token
+        }
+    }
+
+    /// The users resolved by discord, like the targets
+    fn resolved(self) -> &'a CommandDataResolved {
+        match self {
+            Self::Command(cmd) => &cmd.data.resolved,
+            Self::Component(component) => &component.data.resolved
+        }
+    }
+
+    ///
+    /// Respond to the interaction
+    ///
+    /// # Arguments
+    ///
+    /// * `http` - The http client
+    /// * `response` - The response
+    ///
+    /// # Errors
+    ///
+    /// If the response fails to send
+    ///
+    async fn create_response(self, http: &Http, response: CreateInteractionResponse) -> serenity::Result<()> {
+        match self {
+            Self::Command(cmd) => cmd.create_response(http, response).await,
+            Self::Component(component) => component.create_response(http, response).await
+        }
+    }
+
+    ///
+    /// Send a followup message to the interaction
+    ///
+    /// # Arguments
+    ///
+    /// * `http` - The http client
+    /// * `followup` - The followup message
+    ///
+    /// # Errors
+    ///
+    /// If the followup fails to send
+    ///
+    async fn create_followup(self, http: &Http, followup: CreateInteractionResponseFollowup) -> serenity::Result<()> {
+        match self {
+            Self::Command(cmd) => cmd.create_followup(http, followup).await.map(|_| ()),
+            Self::Component(component) => component.create_followup(http, followup).await.map(|_| ())
+        }
+    }
+
+    ///
+    /// Acknowledge the interaction to respond with a followup later
+    ///
+    /// # Arguments
+    ///
+    /// * `http` - The http client
+    /// * `ephemeral` - Whether the followup is only shown to the user
+    ///
+    /// # Errors
+    ///
+    /// If the acknowledgement fails to send
+    ///
+    async fn defer(self, http: &Http, ephemeral: bool) -> serenity::Result<()> {
+        match (self, ephemeral) {
+            (Self::Command(cmd), true) => cmd.defer_ephemeral(http).await,
+            (Self::Command(cmd), false) => cmd.defer(http).await,
+            (Self::Component(component), true) => component.defer_ephemeral(http).await,
+            (Self::Component(component), false) => component.defer(http).await
+        }
+    }
+
+    ///
+    /// Delete the response of a publicly deferred interaction, so the next followup isn't sent in its place
+    ///
+    /// # Arguments
+    ///
+    /// * `http` - The http client
+    ///
+    /// # Errors
+    ///
+    /// If the response fails to delete
+    ///
+    async fn withdraw_deferral(self, http: &Http) -> serenity::Result<()> {
+        match self {
+            Self::Command(cmd) => cmd.delete_response(http).await,
+            // components acknowledge the update of their message without a response of their own
+            Self::Component(_) => Ok(())
+        }
+    }
+
+}
+
+/// A performed reaction ready to be sent
+struct PerformedReaction {
+    /// Name of the reaction
+    reaction: String,
+    /// User performing the reaction
+    user: UserId,
+    /// Target of the reaction
+    target: UserId,
+    /// All targets of the reaction, starting with the target
+    targets: Vec<UserId>,
+    /// Guild the reaction was performed in, if any
+    guild: Option<GuildId>,
+    /// Backend and endpoint the gif was picked from
+    backend_info: String,
+    /// Backend the gif was picked from
+    backend: String,
+    /// Endpoint the gif was picked from
+    endpoint: String,
+    /// URL of the gif
+    image_url: String,
+    /// URLs of the other gifs of the gallery, if any
+    gallery: Vec<String>,
+    /// Url of the proxy the gifs are embedded through, if any
+    proxy: Option<String>,
+    /// Template the response message was rendered from
+    template: String,
+    /// Response message
+    message: String,
+    /// Color of the embed
+    color: Colour,
+    /// Who may be pinged by the response
+    mentions: MentionPolicy,
+    /// Whether the gif should be hidden behind a spoiler
+    spoiler: bool,
+    /// Brand shown on the embed, if any
+    branding: Option<Branding>,
+    /// Author of the embed in the card style, showing the target
+    author: Option<CreateEmbedAuthor>,
+    /// Whether the source of the gif is linked with a button
+    source_button: bool,
+    /// Whether the reaction was sent without revealing the user
+    anonymous: bool
+}
+
+impl PerformedReaction {
+
+    ///
+    /// Build the response message
+    ///
+    /// # Arguments
+    ///
+    /// * `attachment` - The spoiler attachment of the gif, if any
+    ///
+    /// # Returns
+    ///
+    /// The response message
+    ///
+    fn response(&self, attachment: Option<CreateAttachment>) -> CreateInteractionResponseMessage {
+        self.build(CreateInteractionResponseMessage::new(), attachment)
+    }
+
+    ///
+    /// Build the followup message of a deferred response
+    ///
+    /// # Arguments
+    ///
+    /// * `attachment` - The spoiler attachment of the gif, if any
+    ///
+    /// # Returns
+    ///
+    /// The followup message
+    ///
+    fn followup(&self, attachment: Option<CreateAttachment>) -> CreateInteractionResponseFollowup {
+        self.build(CreateInteractionResponseFollowup::new(), attachment)
+    }
+
+    ///
+    /// Build a channel message, used for anonymous reactions
+    ///
+    /// # Arguments
+    ///
+    /// * `attachment` - The spoiler attachment of the gif, if any
+    ///
+    /// # Returns
+    ///
+    /// The channel message
+    ///
+    fn message(&self, attachment: Option<CreateAttachment>) -> CreateMessage {
+        self.build(CreateMessage::new(), attachment)
+    }
+
+    ///
+    /// Fill a message builder with the gif and response
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - The message builder
+    /// * `attachment` - The spoiler attachment of the gif, if any
+    ///
+    /// # Returns
+    ///
+    /// The filled message builder
+    ///
+    fn build<B: ResponseBuilder>(&self, builder: B, attachment: Option<CreateAttachment>) -> B {
+        trace!(target: "module/reaction", "sending response:\n{}\n{}", self.message, self.image_url);
+        let image_url = proxied(self.proxy.as_deref(), &self.image_url);
+        let mentions = match self.mentions {
+            MentionPolicy::Target => CreateAllowedMentions::new().users(self.targets.clone()),
+            MentionPolicy::Users => CreateAllowedMentions::new().all_users(true),
+            MentionPolicy::None => CreateAllowedMentions::new()
+        };
+
+        let mut response = builder
+            .allowed_mentions(mentions);
+        if self.source_button {
+            response = response.components(vec![CreateActionRow::Buttons(vec![
+                CreateButton::new_link(&self.image_url).label("Source")
+            ])]);
+        }
+
+        // hide spoilered gifs in an attachment or a spoiler link
+        match (self.spoiler, attachment) {
+            (false, _) => {
+                let embed = match &self.author {
+                    Some(_) => CreateEmbed::new().thumbnail(&image_url),
+                    None => CreateEmbed::new().image(&image_url)
+                }.color(self.color);
+                let embed = match &self.branding {
+                    Some(branding) => branding.apply(embed),
+                    None => embed
+                };
+                let embed = match &self.author {
+                    Some(author) => embed.author(author.clone()),
+                    None => embed
+                };
+
+                // discord merges the images of embeds sharing a url into one gallery
+                let mut embeds = Vec::new();
+                if self.gallery.is_empty() {
+                    embeds.push(embed);
+                } else {
+                    embeds.push(embed.url(&self.image_url));
+                    embeds.extend(self.gallery.iter().map(|gif| CreateEmbed::new().url(&self.image_url).image(proxied(self.proxy.as_deref(), gif))));
+                }
+                response
+                    .content(self.message.clone())
+                    .embeds(embeds)
+            },
+            (true, Some(attachment)) => response
+                .content(self.message.clone())
+                .add_file(attachment),
+            (true, None) => response
+                .content(format!("{}\n|| {} ||", self.message, image_url))
+        }
+    }
+
+}
+
+///
+/// Rewrite the url of a gif to go through a proxy
+///
+/// # Arguments
+///
+/// * `proxy` - The url of the proxy, if any
+/// * `url` - The url of the gif
+///
+/// # Returns
+///
+/// The url of the gif on the proxy, or the url itself without a valid proxy
+///
+fn proxied(proxy: Option<&str>, url: &str) -> String {
+    let Some(mut proxy) = proxy.and_then(|proxy| reqwest::Url::parse(proxy).ok()) else {
+        return url.to_string();
+    };
+
+    proxy.query_pairs_mut().append_pair("url", url);
+    proxy.to_string()
+}
+
+/// Message builders a performed reaction can be sent with
+trait ResponseBuilder: Sized {
+    fn content(self, content: String) -> Self;
+    fn embeds(self, embeds: Vec<CreateEmbed>) -> Self;
+    fn add_file(self, file: CreateAttachment) -> Self;
+    fn allowed_mentions(self, mentions: CreateAllowedMentions) -> Self;
+    fn components(self, components: Vec<CreateActionRow>) -> Self;
+}
+
+macro_rules! impl_response_builder {
+    ($($builder:ty),*) => {$(
+        impl ResponseBuilder for $builder {
+            fn content(self, content: String) -> Self { <$builder>::content(self, content) }
+            fn embeds(self, embeds: Vec<CreateEmbed>) -> Self { <$builder>::embeds(self, embeds) }
+            fn add_file(self, file: CreateAttachment) -> Self { <$builder>::add_file(self, file) }
+            fn allowed_mentions(self, mentions: CreateAllowedMentions) -> Self { <$builder>::allowed_mentions(self, mentions) }
+            fn components(self, components: Vec<CreateActionRow>) -> Self { <$builder>::components(self, components) }
+        }
+    )*};
+}
+
+impl_response_builder!(CreateInteractionResponseMessage, CreateInteractionResponseFollowup, CreateMessage);
+
+#[async_trait]
+impl Module for ReactionModule {
+
+    async fn init(&mut self, ctx: &serenity::all::Context, config: &Configuration) -> Result<Vec<CreateCommand>, anyhow::Error> {
+        info!(target: "module/reaction", "initializing reaction module");
+        // the bot is ready again after every reconnect, keeping the state of the first initialization
+        let reconnected = self.discord.is_some();
+        self.bot_id = Some(ctx.cache.current_user().id);
+        self.bot_avatar = Some(ctx.cache.current_user().face());
+        self.bot_name = Some(ctx.cache.current_user().display_name().to_string());
+        self.discord = Some(ctx.http.clone());
+        self.audit_log = config.audit_log.clone();
+        self.reactions = config.active_reactions();
+        self.seasonal = config.seasonal.clone();
+        self.settings = config.reaction_settings.clone();
+        self.backend_settings = config.backends.clone();
+        self.analytics = config.analytics.clone().map(analytics::AnalyticsLog::new);
+        self.data_directory = config.data_directory.clone();
+        self.owners = config.owners.clone();
+        let mut builder = backend::BackendManager::builder()
+            .selection_strategy(self.settings.selection_strategy)
+            .pool_limits(self.settings.max_pool_size, self.backend_settings.iter()
+                .filter_map(|(id, settings)| settings.max_pool_size.map(|limit| (id.clone(), limit)))
+                .collect())
+            .memory_budget(self.settings.cache_memory_budget.map(|kilobytes| kilobytes.saturating_mul(1024)))
+            .healthy_pool_sizes(self.settings.healthy_pool_size, self.backend_settings.iter()
+                .filter_map(|(id, settings)| settings.healthy_pool_size.map(|size| (id.clone(), size)))
+                .collect())
+            .timeouts(self.settings.backend_timeout.map(Duration::from_secs), self.backend_settings.iter()
+                .filter_map(|(id, settings)| settings.timeout.map(|timeout| (id.clone(), Duration::from_secs(timeout))))
+                .collect());
+        for (id, settings) in &self.backend_settings {
+            if let Some(http) = &settings.http {
+                builder = builder.http_backend(id, http.clone());
+            }
+            #[cfg(feature = "feed-backends")]
+            if let Some(feed) = &settings.feed {
+                builder = builder.feed_backend(id, feed.clone());
+            }
+            #[cfg(not(feature = "feed-backends"))]
+            if settings.feed.is_some() {
+                return Err(anyhow::anyhow!("feed backend '{}' requires the 'feed-backends' feature", id));
+            }
+            #[cfg(feature = "avatar-backends")]
+            if let Some(avatar) = &settings.avatar {
+                builder = builder.avatar_backend(id, avatar.clone());
+            }
+            #[cfg(not(feature = "avatar-backends"))]
+            if settings.avatar.is_some() {
+                return Err(anyhow::anyhow!("avatar backend '{}' requires the 'avatar-backends' feature", id));
+            }
+            if !settings.headers.is_empty() {
+                builder = builder.headers(id, settings.headers.clone());
+            }
+            if let Some(filter) = settings.dimensions {
+                builder = builder.dimension_filter(id, filter);
+            }
+        }
+        if let Some(url) = &self.settings.shared_cache {
+            #[cfg(feature = "redis-cache")]
+            {
+                builder = builder.shared_cache(url);
+            }
+            #[cfg(not(feature = "redis-cache"))]
+            return Err(anyhow::anyhow!("shared cache at {} requires the 'redis-cache' feature", url));
+        }
+
+        // stop the tasks of the previous manager, so they don't keep refreshing next to the new ones
+        self.backend_manager.stop_background_refresh().await;
+        self.backend_manager.stop_refresh_queue().await;
+        let previous = std::mem::replace(&mut self.backend_manager, builder.build()?);
+        if reconnected {
+            // keep the gifs cached before the reconnect, so they aren't warmed again
+            self.backend_manager.import_cache(previous.export_cache());
+        }
+
+        // validate reactions
+        for reaction in &self.reactions {
+            reaction.validate()?;
+        }
+        self.command_contexts = config.command_contexts.clone();
+
+        // load persisted state
+        if !reconnected {
+            self.load_state().await;
+        }
+
+        // build cache
+        let (progress, updates) = tokio::sync::watch::channel((0, 0));
+        let report = self.settings.warmup_channel
+            .map(|channel| tokio::spawn(warmup::report(ctx.http.clone(), channel, updates)));
+        let only = (self.settings.warmup == WarmupMode::Lazy).then(|| self.reactions.iter()
+            .filter(|r| self.settings.warm_reactions.contains(&r.name))
+            .flat_map(|r| r.backends.iter().chain(&r.rare_backends))
+            .filter_map(|info| info.split_once('/'))
+            .flat_map(|(backend, endpoint)| endpoint.split('+').map(move |endpoint| format!("{}/{}", backend, endpoint)))
+            .collect::<HashSet<_>>());
+        let priority = self.warm_priority();
+        let result = self.backend_manager.build_cache(only.as_ref(), &priority, self.settings.warm_budget, self.settings.warmup_progress_interval, &progress).await;
+        drop(progress);
+        if let Some(report) = report {
+            let _ = report.await;
+        }
+        let deferred = result?;
+        self.backend_manager.start_refresh_queue(backend::RefreshQueueSettings {
+            workers: self.settings.refresh_workers,
+            capacity: self.settings.refresh_queue_size,
+            delay: Duration::from_millis(self.settings.refresh_delay)
+        });
+
+        // warm the endpoints beyond the budget in the background, the rest is warmed on first use
+        for (backend, endpoint) in deferred.iter().filter_map(|info| info.split_once('/')) {
+            self.backend_manager.enqueue_refresh(backend, endpoint);
+        }
+        self.backend_manager.start_background_refresh(backend::BackgroundRefreshSettings {
+            interval: Duration::from_secs(self.settings.background_refresh),
+            jitter: self.settings.background_refresh_jitter
+        });
+
+        let (commands, groups, aliases) = self.create_commands();
+        self.groups = groups;
+        self.aliases = aliases;
+        self.update_command_keys();
+        self.commands = commands.clone();
+        Ok(commands)
+    }
+
+    fn handles(&self, cmd: &CommandInteraction) -> bool {
+        cmd.data.name == utility::UTILITY_COMMAND || cmd.data.name == pronouns::PRONOUNS_COMMAND || self.groups.contains(&cmd.data.name) || self.aliases.contains(&cmd.data.name)
+    }
+
+    fn command_keys(&self) -> Option<CommandKeys> {
+        Some(self.command_keys.clone())
+    }
+
+    fn handles_component(&self, component: &ComponentInteraction) -> bool {
+        component.data.custom_id.starts_with(utility::COMPONENT_PREFIX)
+    }
+
+    async fn handle_component(&mut self, ctx: serenity::all::Context, component: ComponentInteraction) -> Result<(), anyhow::Error> {
+        self.handle_utility_component(ctx, component).await
+    }
+
+    async fn handle_admin(&mut self, request: &AdminRequest) -> Option<AdminResponse> {
+        self.handle_admin_request(request).await
+    }
+
+    async fn handle(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        debug!(target: "module/reaction", "handling command {} executed by @{}", cmd.data.name, cmd.user.name);
+
+        if cmd.data.name == utility::UTILITY_COMMAND {
+            return self.handle_utility(ctx, cmd).await;
+        }
+        if cmd.data.name == pronouns::PRONOUNS_COMMAND {
+            return self.handle_pronouns(ctx, cmd).await;
+        }
+
+        // get requested reaction
+        let Some((options, reaction)) = self.requested_reaction(&cmd)? else {
+            debug!(target: "module/reaction", "command {} was executed without a subcommand", cmd.data.name);
+            return respond_ephemeral(&ctx, &cmd, "Please pick a reaction!").await;
+        };
+
+        self.react(ctx, &cmd, reaction, options).await
+    }
+
+    async fn shutdown(&mut self) -> Result<(), anyhow::Error> {
+        info!(target: "module/reaction", "shutting down reaction module");
+
+        // finish queued refreshes
+        self.backend_manager.stop_background_refresh().await;
+        self.backend_manager.stop_refresh_queue().await;
+
+        // flush analytics
+        if let Some(analytics) = &mut self.analytics {
+            analytics.flush()?;
+        }
+
+        // save persisted state
+        let usage = serde_json::to_string(&self.usage)
+            .context("failed to serialize usage stats")?;
+        self.store.set(STORE_NAMESPACE, USAGE_KEY, &usage).await
+            .context("failed to save usage stats")?;
+        info!(target: "module/reaction", "saved usage stats of {} reactions", self.usage.len());
+
+        // cooldowns are saved as wall-clock times, as instants don't survive a restart
+        let now = chrono::Utc::now().timestamp_millis();
+        let cooldowns: Vec<_> = self.last_use.iter()
+            .filter(|((_, reaction), used)| self.cooldown_may_run(reaction, used.elapsed()))
+            .map(|((user, reaction), used)| PersistedCooldown { user: *user, reaction: reaction.clone(), used: now - used.elapsed().as_millis() as i64 })
+            .collect();
+        let serialized = serde_json::to_string(&cooldowns)
+            .context("failed to serialize cooldowns")?;
+        self.store.set(STORE_NAMESPACE, COOLDOWNS_KEY, &serialized).await
+            .context("failed to save cooldowns")?;
+        info!(target: "module/reaction", "saved {} running cooldowns", cooldowns.len());
+
+        let cache = self.backend_manager.export_cache();
+        cache::save(&*self.store, self.settings.cache_format, &cache).await
+            .context("failed to save cache")?;
+        info!(target: "module/reaction", "saved {} cached gifs",
+            cache.values().flat_map(|c| c.values()).map(|pool| pool.len()).sum::<usize>());
+
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{store, testing::{self, CommandInteractionBuilder}};
+
+    use super::*;
+
+    /// User id of the bot in tests, distinct from the ids the interaction builder uses
+    const BOT: UserId = UserId::new(99);
+
+    /// Create a reaction module with the sample configuration and a seeded rng
+    fn sample_module(seed: u64) -> ReactionModule {
+        sample_module_with_store(store::open(None, None).unwrap(), seed)
+    }
+
+    /// Create a reaction module with the sample configuration, a seeded rng and a given store
+    fn sample_module_with_store(store: Arc<dyn Store>, seed: u64) -> ReactionModule {
+        let mut module = ReactionModule::with_rng(store, StdRng::seed_from_u64(seed)).unwrap();
+        module.init_commands(&testing::sample_config().unwrap());
+        module.bot_id = Some(BOT);
+        module
+    }
+
+    /// Cache a number of gifs for every endpoint of a reaction
+    fn cache_gifs(module: &mut ReactionModule, reaction: &str, count: usize) {
+        let reaction = module.reactions.iter().find(|r| r.name == reaction).unwrap().clone();
+        let mut cache: cache::ExportedCache = HashMap::new();
+        for (backend, endpoint) in reaction.backends.iter().filter_map(|info| info.split_once('/')) {
+            let gifs = (0..count).map(|i| backend::CachedGif { url: format!("https://{}/{}/{}.gif", backend, endpoint, i), added: 0 }).collect();
+            cache.entry(backend.to_string()).or_default().insert(endpoint.to_string(), gifs);
+        }
+        module.backend_manager.import_cache(cache);
+    }
+
+    /// Perform a reaction of the module for an interaction
+    async fn perform(module: &mut ReactionModule, reaction: &str, cmd: &CommandInteraction, targets: &[UserId]) -> PerformedReaction {
+        let reaction = module.reactions.iter().find(|r| r.name == reaction).unwrap().clone();
+        let location = Location { channel: cmd.channel_id, guild: cmd.guild_id, anonymous: false };
+        module.perform(&reaction, &cmd.user, targets, BOT, location, &cmd.data.resolved).await.unwrap()
+    }
+
+    #[test]
+    fn creates_commands_of_the_sample_configuration() {
+        let mut module = sample_module(0);
+        let commands = module.init_commands(&testing::sample_config().unwrap());
+        let summaries: Vec<_> = commands.iter().map(|command| testing::summarize(command).unwrap()).collect();
+        let command = |name: &str| summaries.iter().find(|summary| summary.name == name);
+
+        // every reaction is a subcommand of a group command, reactions with an alias get their own command too
+        for reaction in &module.reactions {
+            assert!(summaries.iter().any(|summary| module.groups.contains(&summary.name) && summary.options.contains(&reaction.name)),
+                "reaction '{}' has no subcommand", reaction.name);
+            assert_eq!(command(&reaction.name).is_some(), reaction.alias, "alias of reaction '{}'", reaction.name);
+        }
+        assert_eq!(command("hug").unwrap().options, ["user", "others"]);
+        assert!(command(utility::UTILITY_COMMAND).unwrap().options.contains(&"list".to_string()));
+        assert!(command(pronouns::PRONOUNS_COMMAND).is_some());
+        assert_eq!(summaries.len(), module.groups.len() + module.aliases.len() + 2);
+    }
+
+    #[test]
+    fn maps_reaction_commands_to_their_reaction() {
+        let module = sample_module(0);
+        let group = command_groups(&module.reactions).into_iter().find(|(_, batch)| batch.iter().any(|r| r.name == "hug")).unwrap().0;
+        let keys = module.command_keys().unwrap();
+        let keys = keys.read().unwrap();
+        assert_eq!(keys.get("hug").map(String::as_str), Some("hug"));
+        assert_eq!(keys.get(&format!("{} hug", group)).map(String::as_str), Some("hug"));
+        assert!(!keys.keys().any(|path| path.starts_with(utility::UTILITY_COMMAND)));
+    }
+
+    #[test]
+    fn handles_its_commands() {
+        let module = sample_module(0);
+        let group = module.groups[0].clone();
+        assert!(module.handles(&CommandInteractionBuilder::new("hug").build().unwrap()));
+        assert!(module.handles(&CommandInteractionBuilder::new(group).subcommand("kick").build().unwrap()));
+        assert!(module.handles(&CommandInteractionBuilder::new(utility::UTILITY_COMMAND).subcommand("list").build().unwrap()));
+        assert!(module.handles(&CommandInteractionBuilder::new(pronouns::PRONOUNS_COMMAND).build().unwrap()));
+        assert!(!module.handles(&CommandInteractionBuilder::new("kick").build().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn performs_reactions_with_a_seeded_rng() {
+        let cmd = CommandInteractionBuilder::new("hug").user(UserId::new(1)).target(UserId::new(2)).guild(GuildId::new(3)).build().unwrap();
+        let mut first = sample_module(7);
+        let mut second = sample_module(7);
+        cache_gifs(&mut first, "hug", 5);
+        cache_gifs(&mut second, "hug", 5);
+
+        for _ in 0..5 {
+            let a = perform(&mut first, "hug", &cmd, &[UserId::new(2)]).await;
+            let b = perform(&mut second, "hug", &cmd, &[UserId::new(2)]).await;
+            assert_eq!((&a.image_url, &a.message), (&b.image_url, &b.message));
+
+            // the default responses are rendered with the mentions of the user and the target
+            let hug = first.reactions.iter().find(|r| r.name == "hug").unwrap();
+            assert!(hug.default_responses.iter().any(|response| response.text == a.template));
+            let rendered = render_response(&a.template, &[("user", "<@1>"), ("target", "<@2>")], Sanitization::Mentions);
+            assert!(a.message.starts_with(&rendered), "{:?} doesn't start with {:?}", a.message, rendered);
+            assert!(a.image_url.starts_with("https://nekos.best/hug/") || a.image_url.starts_with("https://otakugifs.xyz/hug/"));
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_persists_state_for_the_next_start() {
+        for format in [cache::CacheFormat::Json, cache::CacheFormat::Binary] {
+            let store = store::open(None, None).unwrap();
+
+            let mut module = sample_module_with_store(store.clone(), 0);
+            module.settings.cache_format = format;
+            module.settings.cooldown = 60;
+            module.usage.insert("hug".to_string(), 3);
+            module.last_use.insert((UserId::new(1), "hug".to_string()), Instant::now());
+            module.last_use.insert((UserId::new(2), "hug".to_string()), Instant::now() - Duration::from_secs(120));
+            cache_gifs(&mut module, "hug", 2);
+            module.shutdown().await.unwrap();
+
+            // a fresh module sharing the store picks up where the last one stopped
+            let mut reloaded = sample_module_with_store(store, 1);
+            reloaded.settings.cache_format = format;
+            reloaded.settings.cooldown = 60;
+            reloaded.load_state().await;
+            assert_eq!(reloaded.usage, module.usage);
+
+            // running cooldowns survive the restart, expired ones are dropped
+            let hug = sample_reaction("hug");
+            assert!(reloaded.check_cooldown(&hug, UserId::new(1), None).is_some());
+            assert!(reloaded.check_cooldown(&hug, UserId::new(2), None).is_none());
+            assert_eq!(reloaded.last_use.len(), 1);
+
+            let urls = |module: &ReactionModule| {
+                let mut urls: Vec<_> = module.backend_manager.export_cache().into_values()
+                    .flat_map(|pools| pools.into_values().flatten().map(|gif| gif.url))
+                    .collect();
+                urls.sort();
+                urls
+            };
+            assert_eq!(urls(&reloaded).len(), 4);
+            assert_eq!(urls(&reloaded), urls(&module));
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_usage_stats_of_the_old_usage_file() {
+        let directory = std::env::temp_dir().join(format!("puppy-rs-usage-{}", std::process::id()));
+        let mut usage = HashMap::new();
+        usage.insert("hug".to_string(), 5);
+        persistence::save_json(&directory.join(USAGE_FILE), &usage).unwrap();
+
+        let mut module = sample_module(0);
+        module.data_directory = Some(directory.clone());
+        module.load_state().await;
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(module.usage, usage);
+    }
+
+    #[tokio::test]
+    async fn drops_unreadable_state() {
+        let store = store::open(None, None).unwrap();
+        for key in [USAGE_KEY, COOLDOWNS_KEY, GUILDS_KEY, FAVORITES_KEY, cache::CACHE_KEY] {
+            store.set(STORE_NAMESPACE, key, "{ not json").await.unwrap();
+        }
+        store.set(STORE_NAMESPACE, PRONOUNS_KEY, r#"{"1":"she"}"#).await.unwrap();
+
+        // the readable parts are still loaded
+        let mut module = sample_module_with_store(store, 0);
+        module.load_state().await;
+        assert!(module.usage.is_empty() && module.last_use.is_empty() && module.guild_reactions.is_empty() && module.favorites.is_empty());
+        assert!(module.backend_manager.export_cache().values().all(|pools| pools.is_empty()));
+        assert!(module.pronouns.get(&UserId::new(1)) == Some(&pronouns::Pronouns::She));
+    }
+
+    #[tokio::test]
+    async fn alias_and_subcommand_share_a_cooldown() {
+        let mut module = sample_module(0);
+        module.settings.cooldown = 60;
+        cache_gifs(&mut module, "hug", 5);
+
+        let alias = CommandInteractionBuilder::new("hug").user(UserId::new(1)).target(UserId::new(2)).build().unwrap();
+        let group = module.groups.iter().find(|group| group.as_str() != "hug").unwrap().clone();
+        let subcommand = CommandInteractionBuilder::new(group).subcommand("hug").user(UserId::new(1)).target(UserId::new(2)).build().unwrap();
+        let (_, from_alias) = module.requested_reaction(&alias).unwrap().unwrap();
+        let (_, from_subcommand) = module.requested_reaction(&subcommand).unwrap().unwrap();
+        assert_eq!(from_alias.name, from_subcommand.name);
+
+        // using the alias puts the subcommand on cooldown and the other way around
+        assert!(module.check_cooldown(&from_subcommand, UserId::new(1), None).is_none());
+        let performed = perform(&mut module, "hug", &alias, &[UserId::new(2)]).await;
+        module.finish(performed, alias.channel_id, &alias.token, Ok(())).unwrap();
+        assert!(module.check_cooldown(&from_subcommand, UserId::new(1), None).is_some());
+        assert!(module.check_cooldown(&from_alias, UserId::new(1), None).is_some());
+
+        // other users and reactions aren't affected
+        assert!(module.check_cooldown(&from_alias, UserId::new(2), None).is_none());
+        assert!(module.check_cooldown(&sample_reaction("kick"), UserId::new(1), None).is_none());
+    }
+
+    /// Templates of a response list of a reaction
+    fn templates(module: &ReactionModule, reaction: &str, list: fn(&Reaction) -> &Vec<Response>) -> Vec<String> {
+        let reaction = module.reactions.iter().find(|r| r.name == reaction).unwrap();
+        list(reaction).iter().map(|response| response.text.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn other_bots_get_bot_responses_if_enabled() {
+        let other_bot = UserId::new(5);
+        let mut cmd = CommandInteractionBuilder::new("hug").target(other_bot).guild(GuildId::new(3)).build().unwrap();
+        cmd.data.resolved.users.get_mut(&other_bot).unwrap().bot = true;
+
+        let mut module = sample_module(0);
+        cache_gifs(&mut module, "hug", 5);
+        let bot_responses = templates(&module, "hug", |r| &r.bot_responses);
+        let default_responses = templates(&module, "hug", |r| &r.default_responses);
+
+        // without the setting only this bot gets bot responses
+        assert!(!module.settings.other_bot_responses);
+        for _ in 0..5 {
+            let performed = perform(&mut module, "hug", &cmd, &[other_bot]).await;
+            assert!(default_responses.contains(&performed.template), "{:?}", performed.template);
+        }
+
+        module.settings.other_bot_responses = true;
+        for _ in 0..5 {
+            let performed = perform(&mut module, "hug", &cmd, &[other_bot]).await;
+            assert!(bot_responses.contains(&performed.template), "{:?}", performed.template);
+        }
+
+        // users that aren't bots still get the default responses
+        let cmd = CommandInteractionBuilder::new("hug").target(UserId::new(2)).guild(GuildId::new(3)).build().unwrap();
+        let performed = perform(&mut module, "hug", &cmd, &[UserId::new(2)]).await;
+        assert!(default_responses.contains(&performed.template), "{:?}", performed.template);
+    }
+
+    #[test]
+    fn fresh_responses_dont_repeat() {
+        let response = |text: &str| Response { text: text.to_string(), weight: 1.0 };
+        let responses = [response("a"), response("b"), response("c")];
+        let mut rng = StdRng::seed_from_u64(3);
+
+        let mut previous = None;
+        for _ in 0..100 {
+            let picked = pick_fresh_response(&mut rng, &responses, previous).unwrap();
+            assert_ne!(Some(picked), previous);
+            previous = Some(picked);
+        }
+
+        // a single response is repeated rather than leaving the list empty
+        assert_eq!(pick_fresh_response(&mut rng, &responses[..1], Some("a")), Some("a"));
+    }
+
+    #[tokio::test]
+    async fn performed_responses_dont_repeat() {
+        let mut module = sample_module(5);
+        module.settings.avoid_repeats = true;
+        module.settings.dedup_scope = DedupScope::Channel;
+        cache_gifs(&mut module, "hug", 5);
+        assert!(templates(&module, "hug", |r| &r.default_responses).len() > 1);
+
+        let cmd = CommandInteractionBuilder::new("hug").target(UserId::new(2)).guild(GuildId::new(3)).build().unwrap();
+        let mut previous: Option<String> = None;
+        for _ in 0..20 {
+            let performed = perform(&mut module, "hug", &cmd, &[UserId::new(2)]).await;
+            assert_ne!(Some(&performed.template), previous.as_ref());
+            previous = Some(performed.template.clone());
+            module.finish(performed, cmd.channel_id, &cmd.token, Ok(())).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn bot_targets_get_bot_responses() {
+        let mut module = sample_module(0);
+        cache_gifs(&mut module, "hug", 5);
+        let bot_responses = templates(&module, "hug", |r| &r.bot_responses);
+
+        // the id of the bot user is used even though it differs from the application id
+        let targeted = CommandInteractionBuilder::new("hug").target(BOT).guild(GuildId::new(3)).build().unwrap();
+        let untargeted = CommandInteractionBuilder::new("hug").guild(GuildId::new(3)).build().unwrap();
+        assert_ne!(untargeted.application_id.get(), BOT.get());
+        let bot = module.bot_id(untargeted.application_id);
+        assert_eq!(bot, BOT);
+
+        // reacting without a target falls back to the bot with the default target of the sample configuration
+        assert!(matches!(module.settings.default_target, DefaultTarget::Bot));
+        assert_eq!(module.requested_target(&targeted.data.options, targeted.user.id, bot), Some(BOT));
+        assert_eq!(module.requested_target(&untargeted.data.options, untargeted.user.id, bot), Some(BOT));
+
+        for cmd in [&targeted, &untargeted] {
+            let performed = perform(&mut module, "hug", cmd, &[BOT]).await;
+            assert!(bot_responses.contains(&performed.template), "{:?}", performed.template);
+        }
+
+        // the other default targets
+        module.settings.default_target = DefaultTarget::SelfUser;
+        assert_eq!(module.requested_target(&untargeted.data.options, untargeted.user.id, bot), Some(untargeted.user.id));
+        module.settings.default_target = DefaultTarget::Error;
+        assert_eq!(module.requested_target(&untargeted.data.options, untargeted.user.id, bot), None);
+
+        // the application id is the fallback before the bot is ready
+        module.bot_id = None;
+        assert_eq!(module.bot_id(untargeted.application_id), UserId::new(untargeted.application_id.get()));
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors_with_backoff() {
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let started = Instant::now();
+        let result = send_with_retry(5, || async {
+            match attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 | 1 => Err(serenity::Error::Io(std::io::Error::from(std::io::ErrorKind::ConnectionReset))),
+                _ => Ok(())
+            }
+        }).await;
+
+        // the second retry waits twice as long as the first
+        assert!(result.is_ok());
+        assert_eq!(attempts.into_inner(), 3);
+        assert!(started.elapsed() >= RETRY_DELAY * 3, "retried after {:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn doesnt_retry_other_errors() {
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let result = send_with_retry(5, || async {
+            attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Err(serenity::Error::Other("invalid response"))
+        }).await;
+        assert!(result.is_err());
+        assert_eq!(attempts.into_inner(), 1);
+
+        // the last attempt returns its error
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let result = send_with_retry(2, || async {
+            attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Err(serenity::Error::Io(std::io::Error::from(std::io::ErrorKind::ConnectionReset)))
+        }).await;
+        assert!(result.is_err());
+        assert_eq!(attempts.into_inner(), 2);
+    }
+
+    #[test]
+    fn gates_apply_without_the_cooldown() {
+        let mut module = sample_module(0);
+        module.settings.cooldown = 60;
+        let cmd = CommandInteractionBuilder::new("kick").guild(GuildId::new(3)).build().unwrap();
+        let mut reaction = sample_reaction("kick");
+
+        // a cooldown keeps the reaction from being used, but not from being sampled
+        module.last_use.insert((cmd.user.id, reaction.name.clone()), Instant::now());
+        assert!(module.check_allowed(Invocation::Command(&cmd), &reaction).is_none());
+        assert!(module.check_usable(Invocation::Command(&cmd), &reaction).is_some());
+
+        // age requirements apply to both
+        reaction.min_account_age = Some(100 * 365 * 24 * 60 * 60);
+        assert!(module.check_allowed(Invocation::Command(&cmd), &reaction).is_some());
+        assert!(module.check_usable(Invocation::Command(&cmd), &reaction).is_some());
+    }
+
+    /// Get a reaction of the sample configuration
+    fn sample_reaction(name: &str) -> Reaction {
+        testing::sample_config().unwrap().reactions.into_iter().find(|r| r.name == name).unwrap()
+    }
+
+    #[test]
+    fn rejects_reactions_without_backends() {
+        let mut reaction = sample_reaction("kick");
+        assert!(reaction.validate().is_ok());
+        reaction.backends.clear();
+        assert_eq!(reaction.validate().unwrap_err().to_string(), "reaction 'kick' has no backends");
+
+        // the configuration is rejected before the bot starts
+        let mut config = testing::sample_config().unwrap();
+        config.reactions.iter_mut().find(|r| r.name == "kick").unwrap().backends.clear();
+        assert!(config.issues().iter().any(|issue| format!("{:#}", issue).contains("reaction 'kick' has no backends")));
+    }
+
+    #[test]
+    fn rejects_empty_response_lists() {
+        for list in ["default_responses", "bot_responses", "self_responses"] {
+            let mut reaction = sample_reaction("kick");
+            match list {
+                "default_responses" => reaction.default_responses.clear(),
+                "bot_responses" => reaction.bot_responses.clear(),
+                _ => reaction.self_responses.clear()
+            }
+            assert_eq!(reaction.validate().unwrap_err().to_string(), format!("reaction 'kick' has no {}", list));
+        }
+
+        // the optional lists may be empty
+        let mut reaction = sample_reaction("kick");
+        reaction.dm_responses.clear();
+        reaction.anonymous_responses.clear();
+        reaction.rare_responses.clear();
+        assert!(reaction.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_response_weights() {
+        type List = fn(&mut Reaction) -> &mut Vec<Response>;
+        let lists: [(&str, List); 6] = [
+            ("default_responses", |r| &mut r.default_responses),
+            ("bot_responses", |r| &mut r.bot_responses),
+            ("self_responses", |r| &mut r.self_responses),
+            ("dm_responses", |r| &mut r.dm_responses),
+            ("anonymous_responses", |r| &mut r.anonymous_responses),
+            ("rare_responses", |r| &mut r.rare_responses)
+        ];
+        let response = |weight| Response { text: "{user} kicks {target}".to_string(), weight };
+        for (list, responses) in lists {
+            for weight in [-1.0, f64::NAN, f64::INFINITY] {
+                let mut reaction = sample_reaction("kick");
+                *responses(&mut reaction) = vec![response(1.0), response(weight)];
+                assert_eq!(reaction.validate().unwrap_err().to_string(),
+                    format!("{} of reaction 'kick' have a negative or invalid weight", list));
+            }
+
+            let mut reaction = sample_reaction("kick");
+            *responses(&mut reaction) = vec![response(0.0), response(0.0)];
+            assert_eq!(reaction.validate().unwrap_err().to_string(), format!("{} of reaction 'kick' all have a weight of 0", list));
+
+            *responses(&mut reaction) = vec![response(0.0), response(2.0)];
+            assert!(reaction.validate().is_ok(), "{}", list);
+        }
+    }
+
+    #[test]
+    fn rejects_rare_probabilities_out_of_range() {
+        for probability in [-0.1, 1.5, f64::NAN] {
+            let mut reaction = sample_reaction("kick");
+            reaction.rare_probability = probability;
+            assert_eq!(reaction.validate().unwrap_err().to_string(),
+                format!("rare_probability of reaction 'kick' is {}, but must be 0.0 to 1.0", probability));
+        }
+
+        for probability in [0.0, 1.0] {
+            let mut reaction = sample_reaction("kick");
+            reaction.rare_probability = probability;
+            assert!(reaction.validate().is_ok(), "{}", probability);
+        }
+    }
+
+    #[test]
+    fn parses_plain_and_weighted_responses() {
+        let responses: Vec<Response> = serde_yml::from_str("- plain\n- text: heavy\n  weight: 2.5\n- text: default\n").unwrap();
+        let parsed: Vec<_> = responses.iter().map(|response| (response.text.as_str(), response.weight)).collect();
+        assert_eq!(parsed, [("plain", 1.0), ("heavy", 2.5), ("default", 1.0)]);
+    }
+
+    #[test]
+    fn picks_responses_by_weight() {
+        let response = |text: &str, weight| Response { text: text.to_string(), weight };
+        let responses = [response("rare", 1.0), response("common", 3.0), response("never", 0.0)];
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for _ in 0..10_000 {
+            *counts.entry(pick_response(&mut rng, &responses).unwrap()).or_default() += 1;
+        }
+
+        // the common response is picked about three times as often as the rare one
+        let (rare, common) = (counts["rare"] as f64, counts["common"] as f64);
+        assert!((common / (rare + common) - 0.75).abs() < 0.02, "{:?}", counts);
+        assert!(!counts.contains_key("never"));
+
+        // the same seed picks the same responses
+        let picks = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..20).map(|_| pick_response(&mut rng, &responses).unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(picks(7), picks(7));
+    }
+
+    #[test]
+    fn picks_nothing_from_empty_lists() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(pick_response(&mut rng, &[]).is_none());
+        assert!(pick_fresh_response(&mut rng, &[], None).is_none());
+        assert!(pick_weighted(&mut rng, &[] as &[&Response], |response| response.weight).is_none());
+    }
+
+    #[test]
+    fn target_placeholders_of_resolved_targets() {
+        let target = UserId::new(2);
+        assert_eq!(target_placeholders(target, Some("Bob".to_string()), true), ("<@2>".to_string(), "Bob".to_string()));
+        assert_eq!(target_placeholders(target, Some("Bob".to_string()), false), ("Bob".to_string(), "Bob".to_string()));
+    }
+
+    #[test]
+    fn target_placeholders_of_unresolved_targets() {
+        let target = UserId::new(2);
+        assert_eq!(target_placeholders(target, None, true), ("<@2>".to_string(), "<@2>".to_string()));
+        assert_eq!(target_placeholders(target, None, false), ("<@2>".to_string(), NAME_FALLBACK.to_string()));
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::Context;
+use log::{info, warn};
+use serde_json::json;
+use serenity::all::{Command, CreateCommand, Http};
+
+use crate::admin::{AdminRequest, AdminResponse};
+
+use super::{reaction_issues, Reaction, ReactionModule};
+
+/// Path of the reaction set
+const REACTIONS_PATH: &str = "/reactions";
+
+/// Path triggering a rebuild of the gif cache
+const CACHE_REBUILD_PATH: &str = "/cache/rebuild";
+
+impl ReactionModule {
+
+    ///
+    /// Handle a request to the admin api
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The authenticated request
+    ///
+    /// # Returns
+    ///
+    /// The response or None if the path doesn't belong to the reaction module
+    ///
+    pub(super) async fn handle_admin_request(&mut self, request: &AdminRequest) -> Option<AdminResponse> {
+        let response = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", REACTIONS_PATH) => self.get_reactions(),
+            ("PUT", REACTIONS_PATH) => self.put_reactions(&request.body).await,
+            ("POST", CACHE_REBUILD_PATH) => self.rebuild_cache(),
+            (_, REACTIONS_PATH | CACHE_REBUILD_PATH) => AdminResponse::error(405, format!("method {} is not allowed on {}", request.method, request.path)),
+            _ => return None
+        };
+        Some(response)
+    }
+
+    ///
+    /// Get the current reaction set
+    ///
+    /// # Returns
+    ///
+    /// The reactions as a json array
+    ///
+    fn get_reactions(&self) -> AdminResponse {
+        match serde_json::to_value(&self.reactions) {
+            Ok(reactions) => AdminResponse::ok(200, reactions),
+            Err(e) => AdminResponse::error(500, format!("failed to serialize reactions: {}", e))
+        }
+    }
+
+    ///
+    /// Replace the current reaction set
+    ///
+    /// The submitted reactions are validated like the reactions of the configuration. Commands that changed
+    /// are registered with discord, commands of removed reactions are deleted and endpoints that aren't
+    /// cached yet are queued for a refresh.
+    ///
+    /// # Arguments
+    ///
+    /// * `body` - The reactions as a json array
+    ///
+    /// # Returns
+    ///
+    /// A summary of the changes or an error describing why the reactions were rejected
+    ///
+    async fn put_reactions(&mut self, body: &[u8]) -> AdminResponse {
+        let Some(http) = self.discord.clone() else {
+            return AdminResponse::error(503, "the bot is not ready yet");
+        };
+
+        // parse and validate the reactions
+        let reactions: Vec<Reaction> = match serde_json::from_slice(body) {
+            Ok(reactions) => reactions,
+            Err(e) => return AdminResponse::error(400, format!("invalid reactions: {}", e))
+        };
+        let issues = reaction_issues(&reactions, &self.seasonal, &self.backend_settings, &self.settings.alias_description);
+        if !issues.is_empty() {
+            return AdminResponse::issues(422, "the reactions are invalid", &issues);
+        }
+
+        // register the changed commands
+        let previous = std::mem::replace(&mut self.reactions, reactions);
+        let (commands, groups, aliases) = self.create_commands();
+        let (registered, removed) = match self.sync_commands(&http, commands).await {
+            Ok(changes) => changes,
+            Err(e) => {
+                warn!(target: "module/reaction", "failed to register reactions submitted over the admin api: {:?}", e);
+                self.reactions = previous;
+                return AdminResponse::error(502, format!("{:#}", e));
+            }
+        };
+        self.groups = groups;
+        self.aliases = aliases;
+        self.update_command_keys();
+
+        // prune favorites of reactions that no longer exist
+        for favorites in self.favorites.values_mut() {
+            favorites.retain(|name| self.reactions.iter().any(|r| &r.name == name));
+        }
+        self.favorites.retain(|_, favorites| !favorites.is_empty());
+
+        // warm the endpoints of new reactions
+        let mut warming = 0;
+        for (backend, endpoint) in endpoints(&self.reactions) {
+            if !self.backend_manager.is_cached(&backend, &endpoint) {
+                self.backend_manager.enqueue_refresh(&backend, &endpoint);
+                warming += 1;
+            }
+        }
+
+        info!(target: "module/reaction", "replaced reactions over the admin api, now {} reactions", self.reactions.len());
+        AdminResponse::ok(200, json!({
+            "reactions": self.reactions.len(),
+            "registered": registered,
+            "removed": removed,
+            "warming": warming
+        }))
+    }
+
+    ///
+    /// Queue a refresh of every endpoint used by the reactions
+    ///
+    /// # Returns
+    ///
+    /// The number of queued endpoints
+    ///
+    fn rebuild_cache(&self) -> AdminResponse {
+        let endpoints = endpoints(&self.reactions);
+        for (backend, endpoint) in &endpoints {
+            self.backend_manager.enqueue_refresh(backend, endpoint);
+        }
+
+        info!(target: "module/reaction", "queued a refresh of {} endpoints over the admin api", endpoints.len());
+        AdminResponse::ok(202, json!({ "queued": endpoints.len() }))
+    }
+
+    ///
+    /// Register commands with discord that differ from the last registration and delete commands that are gone
+    ///
+    /// # Arguments
+    ///
+    /// * `http` - The http client of the bot
+    /// * `commands` - The new commands of the module
+    ///
+    /// # Returns
+    ///
+    /// The names of the registered and the deleted commands
+    ///
+    /// # Errors
+    ///
+    /// If a command fails to serialize, register or delete
+    ///
+    async fn sync_commands(&mut self, http: &Http, commands: Vec<CreateCommand>) -> Result<(Vec<String>, Vec<String>), anyhow::Error> {
+        let previous = self.commands.iter()
+            .map(serialize_command)
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        // commands are registered by name, replacing the command with the same name
+        let mut registered = Vec::new();
+        let mut names = Vec::new();
+        for command in &commands {
+            let (name, value) = serialize_command(command)?;
+            if previous.get(&name) != Some(&value) {
+                Command::create_global_command(http, command.clone()).await
+                    .with_context(|| format!("failed to register command '{}'", name))?;
+                registered.push(name.clone());
+            }
+            names.push(name);
+        }
+
+        let mut removed = Vec::new();
+        if previous.keys().any(|name| !names.contains(name)) {
+            for command in Command::get_global_commands(http).await.context("failed to get commands")? {
+                if previous.contains_key(&command.name) && !names.contains(&command.name) {
+                    Command::delete_global_command(http, command.id).await
+                        .with_context(|| format!("failed to delete command '{}'", command.name))?;
+                    removed.push(command.name);
+                }
+            }
+        }
+
+        self.commands = commands;
+        Ok((registered, removed))
+    }
+
+}
+
+///
+/// Serialize a command to compare it with another command
+///
+/// # Arguments
+///
+/// * `command` - The command creation request
+///
+/// # Returns
+///
+/// The name of the command and the command as json
+///
+/// # Errors
+///
+/// If the command fails to serialize or has no name
+///
+fn serialize_command(command: &CreateCommand) -> Result<(String, serde_json::Value), anyhow::Error> {
+    let value = serde_json::to_value(command)
+        .context("failed to serialize command")?;
+    let name = value.get("name").and_then(|name| name.as_str())
+        .context("command has no name")?
+        .to_string();
+    Ok((name, value))
+}
+
+///
+/// Collect every endpoint used by a set of reactions
+///
+/// # Arguments
+///
+/// * `reactions` - The reactions
+///
+/// # Returns
+///
+/// The backend and endpoint of every regular and rare backend, with combined endpoints split up
+///
+fn endpoints(reactions: &[Reaction]) -> BTreeSet<(String, String)> {
+    reactions.iter()
+        .flat_map(|r| r.backends.iter().chain(&r.rare_backends))
+        .filter_map(|info| info.split_once('/'))
+        .flat_map(|(backend, endpoint)| endpoint.split('+').map(move |endpoint| (backend.to_string(), endpoint.to_string())))
+        .collect()
+}
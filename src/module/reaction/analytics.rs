@@ -0,0 +1,224 @@
+use std::{fs::{self, File, OpenOptions}, io::Write, path::PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+
+/// Format of the analytics log files
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalyticsFormat {
+    /// Comma separated values with a header line
+    #[default]
+    Csv,
+    /// One json object per line
+    Jsonl
+}
+
+impl AnalyticsFormat {
+
+    ///
+    /// Get the file extension of the format
+    ///
+    /// # Returns
+    ///
+    /// The file extension without a leading dot
+    ///
+    fn extension(&self) -> &'static str {
+        match self {
+            AnalyticsFormat::Csv => "csv",
+            AnalyticsFormat::Jsonl => "jsonl"
+        }
+    }
+
+}
+
+/// Struct to hold the analytics settings
+#[derive(Deserialize, Clone)]
+pub struct AnalyticsSettings {
+    /// Directory to write the analytics log files to
+    pub directory: PathBuf,
+    /// Format of the analytics log files
+    #[serde(default)]
+    pub format: AnalyticsFormat
+}
+
+/// A single reaction invocation
+#[derive(Serialize)]
+pub struct Record<'a> {
+    /// Name of the reaction
+    pub reaction: &'a str,
+    /// ID of the user who ran the reaction
+    pub invoker: u64,
+    /// ID of the user the reaction was run on
+    pub target: u64,
+    /// Time the reaction was run at
+    pub timestamp: DateTime<Utc>,
+    /// Backend the gif was fetched from
+    pub backend: &'a str
+}
+
+impl Record<'_> {
+
+    ///
+    /// Format the record as a line of the given format
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The format to use
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the line without a trailing newline
+    ///
+    /// # Errors
+    ///
+    /// If the record fails to serialize
+    ///
+    fn to_line(&self, format: AnalyticsFormat) -> Result<String, anyhow::Error> {
+        match format {
+            AnalyticsFormat::Csv => Ok(format!("{},{},{},{},{}",
+                escape_csv(self.reaction),
+                self.invoker,
+                self.target,
+                self.timestamp.to_rfc3339(),
+                escape_csv(self.backend)
+            )),
+            AnalyticsFormat::Jsonl => serde_json::to_string(self)
+                .context("failed to serialize record")
+        }
+    }
+
+}
+
+/// Header line of csv analytics files
+const CSV_HEADER: &str = "reaction,invoker,target,timestamp,backend";
+
+///
+/// Escape a field for use in a csv line
+///
+/// # Arguments
+///
+/// * `field` - The field to escape
+///
+/// # Returns
+///
+/// The field, quoted if necessary
+///
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+///
+/// Analytics log for the '/reaction' command module.
+///
+/// Appends every reaction invocation to a log file, starting a new file every day.
+///
+pub struct AnalyticsLog {
+    settings: AnalyticsSettings,
+    file: Option<(NaiveDate, File)>
+}
+
+impl AnalyticsLog {
+
+    ///
+    /// Create a new analytics log.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - The analytics settings
+    ///
+    /// # Returns
+    ///
+    /// A new analytics log.
+    ///
+    pub fn new(settings: AnalyticsSettings) -> Self {
+        info!(target: "module/reaction/analytics", "writing analytics to '{}'", settings.directory.display());
+        Self { settings, file: None }
+    }
+
+    ///
+    /// Append a record to the log file of the record's date.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The record to append
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the log file cannot be opened or written to.
+    ///
+    pub fn record(&mut self, record: &Record) -> Result<(), anyhow::Error> {
+        let date = record.timestamp.date_naive();
+        let line = record.to_line(self.settings.format)?;
+
+        // rotate the file if the date changed
+        let file = match &mut self.file {
+            Some((file_date, file)) if *file_date == date => file,
+            _ => {
+                let file = self.open(date)?;
+                &mut self.file.insert((date, file)).1
+            }
+        };
+
+        writeln!(file, "{}", line)
+            .context("failed to write analytics record")?;
+
+        Ok(())
+    }
+
+    ///
+    /// Flush the current log file to disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the log file cannot be flushed.
+    ///
+    pub fn flush(&mut self) -> Result<(), anyhow::Error> {
+        if let Some((_, file)) = &mut self.file {
+            file.sync_all()
+                .context("failed to flush analytics file")?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Open the log file of the given date, creating it if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `date` - The date of the log file
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the opened file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created or opened.
+    ///
+    fn open(&self, date: NaiveDate) -> Result<File, anyhow::Error> {
+        fs::create_dir_all(&self.settings.directory)
+            .context("failed to create analytics directory")?;
+
+        let path = self.settings.directory.join(format!("reactions-{}.{}", date.format("%Y-%m-%d"), self.settings.format.extension()));
+        debug!(target: "module/reaction/analytics", "opening analytics file '{}'", path.display());
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)
+            .context("failed to open analytics file")?;
+
+        // write the csv header to new files
+        if self.settings.format == AnalyticsFormat::Csv && file.metadata().context("failed to read analytics file metadata")?.len() == 0 {
+            writeln!(file, "{}", CSV_HEADER)
+                .context("failed to write analytics header")?;
+        }
+
+        Ok(file)
+    }
+
+}
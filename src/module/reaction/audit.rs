@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use log::warn;
+use serde::Deserialize;
+use serenity::all::{ChannelId, CreateEmbed, CreateMessage, Http, Timestamp, UserId};
+
+/// Struct to hold the audit log settings
+#[derive(Deserialize, Clone)]
+pub struct AuditLogSettings {
+    /// Channel to post the audit log to
+    pub channel: ChannelId,
+    /// Share of reaction invocations to log (0.0 - 1.0)
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+/// An entry in the audit log
+pub struct Entry {
+    /// Name of the reaction
+    pub reaction: String,
+    /// User performing the reaction
+    pub invoker: UserId,
+    /// Target of the reaction
+    pub target: UserId,
+    /// Channel the reaction was performed in
+    pub channel: ChannelId
+}
+
+///
+/// Post an entry to the audit log channel in the background
+///
+/// # Arguments
+///
+/// * `http` - The http client of the bot
+/// * `settings` - The audit log settings
+/// * `entry` - The entry to post
+///
+pub fn post(http: Arc<Http>, settings: &AuditLogSettings, entry: Entry) {
+    let channel = settings.channel;
+    tokio::spawn(async move {
+        let embed = CreateEmbed::new()
+            .description(format!("<@{}> used **{}** on <@{}> in <#{}>", entry.invoker, entry.reaction, entry.target, entry.channel))
+            .timestamp(Timestamp::now());
+        if let Err(e) = channel.send_message(&http, CreateMessage::new().embed(embed)).await {
+            warn!(target: "module/reaction", "failed to post to audit log channel: {:?}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing;
+
+    use super::*;
+
+    #[test]
+    fn rejects_sample_rates_out_of_range() {
+        let mut config = testing::sample_config().unwrap();
+        for (sample_rate, valid) in [(0.0, true), (0.5, true), (1.0, true), (-0.1, false), (1.5, false), (f64::NAN, false), (f64::INFINITY, false)] {
+            config.audit_log = Some(AuditLogSettings { channel: ChannelId::new(1), sample_rate });
+            let rejected = config.issues().iter().any(|issue| issue.to_string().starts_with("sample_rate of the audit log"));
+            assert_eq!(rejected, !valid, "sample rate {}", sample_rate);
+        }
+    }
+}
@@ -1,16 +1,57 @@
-use std::collections::HashMap;
+use std::{cmp::Reverse, collections::{BinaryHeap, HashMap, HashSet, VecDeque}, sync::{Arc, Mutex, MutexGuard}, time::Duration};
 
 use anyhow::{anyhow, Context};
-use log::{info, trace};
+use log::{debug, info, trace, warn};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
 use serenity::async_trait;
+use tokio::{sync::{mpsc, watch}, task::{JoinHandle, JoinSet}, time::Instant};
+use tokio_util::sync::CancellationToken;
 
+use crate::{env, metrics, task::{self, BackgroundTask}};
+
+pub mod avatar;
+pub mod feed;
+pub mod http;
 mod nekosbest;
 mod otakugifs;
+#[cfg(feature = "redis-cache")]
+mod shared;
 
 /// The backend trait.
 #[async_trait]
 trait Backend: Send + Sync {
 
+    ///
+    /// Set additional headers sent with every request
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - The headers
+    ///
+    fn set_headers(&mut self, headers: HeaderMap);
+
+    ///
+    /// Replace the http client used for every request, e.g. to apply a timeout
+    ///
+    /// # Arguments
+    ///
+    /// * `http` - The http client
+    ///
+    fn set_http_client(&mut self, http: reqwest::Client);
+
+    ///
+    /// Set the dimensions gifs must have to be cached
+    ///
+    /// Backends not exposing the dimensions of their gifs ignore the filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The dimension filter
+    ///
+    fn set_dimension_filter(&mut self, _filter: DimensionFilter) {}
+
     ///
     /// Get the ID of the backend.
     ///
@@ -50,150 +91,1837 @@ trait Backend: Send + Sync {
     ///
     async fn fetch(&self, endpoint: &str) -> Result<String, anyhow::Error>;
 
+    ///
+    /// Check whether gifs of the backend depend on the fetch context and can't be cached
+    ///
+    /// # Returns
+    ///
+    /// Whether gifs must be fetched with a context
+    ///
+    #[cfg(feature = "avatar-backends")]
+    fn requires_context(&self) -> bool {
+        false
+    }
+
+    ///
+    /// Fetch an animated gif for the context of a reaction
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The endpoint to fetch from
+    /// * `context` - The context of the reaction
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the url of the animated gif
+    ///
+    /// # Errors
+    ///
+    /// If the request fails or the response is not successful
+    ///
+    #[cfg(feature = "avatar-backends")]
+    async fn fetch_with_context(&self, endpoint: &str, _context: &FetchContext) -> Result<String, anyhow::Error> {
+        self.fetch(endpoint).await
+    }
+
 }
 
-///
-/// Backend manager for the '/reaction' command module.
-///
-/// Provides a way to manage multiple backends as well as cache their endpoints.
-///
-pub struct BackendManager {
-    backends: HashMap<&'static str, Box<dyn Backend>>,
-    caches: HashMap<&'static str, HashMap<String, String>>,
+/// Context of a reaction passed to backends requiring it
+#[cfg(feature = "avatar-backends")]
+pub struct FetchContext {
+    /// Url of the avatar of the target
+    pub avatar: String
 }
 
-impl BackendManager {
+/// Dimensions a gif must have to be cached
+#[derive(Deserialize, Clone, Copy, Default)]
+pub struct DimensionFilter {
+    /// Minimum width in pixels
+    #[serde(default)]
+    pub min_width: Option<u64>,
+    /// Minimum height in pixels
+    #[serde(default)]
+    pub min_height: Option<u64>,
+    /// Minimum ratio of width to height
+    #[serde(default)]
+    pub min_aspect_ratio: Option<f64>,
+    /// Maximum ratio of width to height
+    #[serde(default)]
+    pub max_aspect_ratio: Option<f64>
+}
+
+impl DimensionFilter {
 
     ///
-    /// Create a new backend manager.
+    /// Check whether a gif passes the filter
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the gif, if known
+    /// * `height` - The height of the gif, if known
     ///
     /// # Returns
     ///
-    /// A new backend manager.
+    /// Whether the gif has the required dimensions, true if its dimensions are unknown
     ///
-    pub fn new() -> Result<Self, anyhow::Error> {
-        let mut backends: HashMap<&'static str, Box<dyn Backend>> = HashMap::new();
-        let mut caches = HashMap::new();
+    pub fn allows(&self, width: Option<u64>, height: Option<u64>) -> bool {
+        let (Some(width), Some(height)) = (width, height) else {
+            return true;
+        };
 
-        // add backends
-        let nekosbest = nekosbest::NekosBest::new();
-        let id = nekosbest.id();
-        backends.insert(id, Box::new(nekosbest));
-        caches.insert(id, HashMap::new());
-        info!(target: "module/reaction/backend", "registered nekos.best backend");
+        let ratio = width as f64 / height.max(1) as f64;
+        self.min_width.is_none_or(|min| width >= min)
+            && self.min_height.is_none_or(|min| height >= min)
+            && self.min_aspect_ratio.is_none_or(|min| ratio >= min)
+            && self.max_aspect_ratio.is_none_or(|max| ratio <= max)
+    }
 
-        let otakugifs = otakugifs::OtakuGifs::new();
-        let id = otakugifs.id();
-        backends.insert(id, Box::new(otakugifs));
-        caches.insert(id, HashMap::new());
-        info!(target: "module/reaction/backend", "registered otakugifs.xyz backend");
+}
 
-        // build manager
-        Ok(Self {
-            backends,
-            caches
-        })
+/// Strategy for picking a gif from a pool
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionStrategy {
+    /// Every gif is equally likely
+    #[default]
+    Uniform,
+    /// Recently cached gifs are more likely
+    RecencyWeighted
+}
+
+/// Error returned when the only cached gif of a pool was excluded
+#[derive(Debug)]
+pub struct NoVariety;
+
+impl std::fmt::Display for NoVariety {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no other gif is cached")
+    }
+}
+
+impl std::error::Error for NoVariety {}
+
+/// Error returned when no gif is cached for an endpoint
+#[derive(Debug)]
+pub struct EmptyCache;
+
+impl std::fmt::Display for EmptyCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no gif is cached")
+    }
+}
+
+impl std::error::Error for EmptyCache {}
+
+/// A gif in a cache pool
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CachedGif {
+    /// URL of the gif
+    pub url: String,
+    /// Unix timestamp of when the gif was cached
+    pub added: i64
+}
+
+impl CachedGif {
+    fn new(url: String) -> Self {
+        Self { url, added: chrono::Utc::now().timestamp() }
+    }
+
+    /// Estimated number of bytes the gif takes up in a pool
+    fn estimated_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.url.capacity()
     }
+}
+
+/// Backends by id
+type Backends = HashMap<&'static str, Box<dyn Backend>>;
+
+/// A (backend, endpoint) pair waiting to be refreshed
+type RefreshJob = (String, String);
+
+/// Minimum time to wait before refreshing again after a failed refresh
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Ids of the backends registered by default
+pub const BUILTIN_BACKENDS: [&str; 2] = ["nekos.best", "otakugifs.xyz"];
+
+/// Maximum time to wait before refreshing again after failed refreshes
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Number of recent fetches the success rate of a backend is computed from
+const SUCCESS_WINDOW: usize = 100;
+
+/// Cache state shared between the backend manager and its refresh workers
+#[derive(Default)]
+struct CacheState {
+    /// Pools of cached gifs by backend id and endpoint
+    caches: HashMap<&'static str, HashMap<String, Vec<CachedGif>>>,
+    /// Gifs that must not be cached again
+    blocked: HashSet<String>,
+    /// Outcomes of the recent fetches by backend id
+    outcomes: HashMap<&'static str, VecDeque<bool>>,
+    /// Maximum number of gifs in a pool
+    max_pool_size: Option<usize>,
+    /// Maximum number of gifs in a pool by backend id, overriding the default
+    pool_limits: HashMap<String, usize>,
+    /// Time of the last successful refresh by backend id and endpoint
+    refreshed: HashMap<&'static str, HashMap<String, Instant>>,
+    /// Estimated number of bytes all pools may take up together
+    memory_budget: Option<usize>,
+    /// Minimum number of gifs in a healthy pool
+    healthy_pool_size: Option<usize>,
+    /// Minimum number of gifs in a healthy pool by backend id, overriding the default
+    healthy_pool_sizes: HashMap<String, usize>,
+    /// Pools below their healthy size by backend id and endpoint
+    unhealthy: HashSet<(String, String)>
+}
+
+impl CacheState {
 
     ///
-    /// Build the initial cache of endpoints for all backends.
+    /// Add a gif to a pool unless it's blocked or already cached, evicting the least recently used gifs above the size limit.
     ///
-    /// # Errors
+    /// Pools are kept in order from least to most recently used.
     ///
-    /// Returns an error if the cache build fails.
+    /// # Arguments
     ///
-    pub async fn build_cache(&mut self) -> Result<(), anyhow::Error> {
-        info!(target: "module/reaction/backend", "building cache, this may take a while...");
+    /// * `backend` - The backend id.
+    /// * `endpoint` - The endpoint.
+    /// * `url` - The URL of the gif.
+    ///
+    fn insert(&mut self, backend: &'static str, endpoint: &str, url: String) {
+        let limit = self.pool_limits.get(backend).copied().or(self.max_pool_size);
+        let Some(cache) = self.caches.get_mut(backend) else {
+            return;
+        };
 
-        for backend in self.backends.values() {
-            let id = backend.id();
+        let pool = cache.entry(endpoint.to_string()).or_default();
+        if self.blocked.contains(&url) || pool.iter().any(|cached| cached.url == url) {
+            return;
+        }
 
-            // fetch list of endpoints
-            let endpoints = backend.endpoints().await
-                .context("failed to fetch endpoints")?;
-            let endpoints_len = endpoints.len();
+        pool.push(CachedGif::new(url));
+        if let Some(limit) = limit {
+            let excess = pool.len().saturating_sub(limit.max(1));
+            pool.drain(..excess);
+        }
 
-            // fetch a gif from each endpoint
-            for endpoint in endpoints {
-                let gif = backend.fetch(&endpoint).await
-                    .context("failed to fetch gif")?;
+        self.trim_to_budget();
+    }
 
-                // cache the gif
-                self.caches.get_mut(id).unwrap().insert(endpoint, gif);
+    ///
+    /// Drop the least recently used gifs of the largest pools until the estimated size of all pools fits the memory budget.
+    ///
+    /// Every pool keeps at least one gif, so the budget may still be exceeded with many endpoints.
+    ///
+    fn trim_to_budget(&mut self) {
+        let Some(budget) = self.memory_budget else {
+            return;
+        };
 
-                // wait 200ms before fetching the next gif
-                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-            }
+        let mut size: usize = self.caches.values()
+            .flat_map(|cache| cache.values())
+            .flatten()
+            .map(CachedGif::estimated_size)
+            .sum();
+        let mut trimmed = 0;
+        while size > budget {
+            let largest = self.caches.values_mut()
+                .flat_map(|cache| cache.values_mut())
+                .filter(|pool| pool.len() > 1)
+                .max_by_key(|pool| pool.len());
+            let Some(pool) = largest else {
+                break;
+            };
 
-            info!(target: "module/reaction/backend", "cached {} endpoints for backend '{}'", endpoints_len, id);
+            size -= pool.remove(0).estimated_size();
+            trimmed += 1;
         }
 
-        info!(target: "module/reaction/backend", "cache build complete");
-        Ok(())
+        if trimmed > 0 {
+            info!(target: "module/reaction/backend", "trimmed {} cached gifs to stay within the memory budget of {} bytes", trimmed, budget);
+            self.check_health(None);
+        }
     }
 
     ///
-    /// Get a cached gif from the specified backend.
+    /// Report the size of a pool and warn once it drops below its healthy size.
     ///
     /// # Arguments
     ///
-    /// * `backend` - The backend to fetch the gif from.
-    /// * `endpoint` - The endpoint to fetch the gif from.
+    /// * `backend` - The backend id.
+    /// * `endpoint` - The endpoint.
     ///
-    /// # Returns
+    fn check_pool_health(&mut self, backend: &str, endpoint: &str) {
+        let size = self.caches.get(backend).and_then(|cache| cache.get(endpoint)).map_or(0, Vec::len);
+        let labels = [("backend", backend), ("endpoint", endpoint)];
+        metrics::set_gauge("puppy_cache_pool_size", &labels, size as f64);
+
+        let Some(threshold) = self.healthy_pool_sizes.get(backend).copied().or(self.healthy_pool_size) else {
+            return;
+        };
+
+        let key = (backend.to_string(), endpoint.to_string());
+        let healthy = size >= threshold;
+        metrics::set_gauge("puppy_cache_pool_unhealthy", &labels, if healthy { 0.0 } else { 1.0 });
+        if !healthy && self.unhealthy.insert(key.clone()) {
+            warn!(target: "module/reaction/backend", "pool of '{}' endpoint on backend '{}' dropped to {} gifs, below the healthy size of {}", endpoint, backend, size, threshold);
+        } else if healthy && self.unhealthy.remove(&key) {
+            info!(target: "module/reaction/backend", "pool of '{}' endpoint on backend '{}' recovered to {} gifs", endpoint, backend, size);
+        }
+    }
+
     ///
-    /// A string containing the URL of the fetched gif.
+    /// Check the health of every pool, or of every pool of one backend.
     ///
-    /// # Errors
+    /// # Arguments
     ///
-    /// Returns an error if the backend is unknown or if the gif is not cached.
+    /// * `backend` - The backend id, all backends if none.
     ///
-    pub fn get_cached(&mut self, backend: &str, endpoint: &str) -> Result<String, anyhow::Error> {
-        trace!(target: "module/reaction/backend", "requested '{}' endpoint on cached backend '{}'", endpoint, backend);
+    fn check_health(&mut self, backend: Option<&str>) {
+        let pools: Vec<(String, String)> = self.caches.iter()
+            .filter(|(id, _)| backend.is_none_or(|backend| **id == backend))
+            .flat_map(|(id, cache)| cache.keys().map(|endpoint| (id.to_string(), endpoint.clone())))
+            .collect();
+        for (backend, endpoint) in pools {
+            self.check_pool_health(&backend, &endpoint);
+        }
+    }
 
-        // get backend and cache
-        let backend = self.backends.get(backend)
-            .ok_or_else(|| anyhow!("unknown backend: {}", backend))?;
-        let cache = self.caches.get_mut(backend.id())
-            .ok_or_else(|| anyhow!("no cache for backend: {}", backend.id()))?;
+    ///
+    /// Record that an endpoint was just refreshed.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend id.
+    /// * `endpoint` - The endpoint.
+    ///
+    fn mark_refreshed(&mut self, backend: &'static str, endpoint: &str) {
+        self.refreshed.entry(backend).or_default().insert(endpoint.to_string(), Instant::now());
+        metrics::set_gauge("puppy_cache_last_refreshed_timestamp_seconds", &[("backend", backend), ("endpoint", endpoint)], chrono::Utc::now().timestamp() as f64);
+        self.check_pool_health(backend, endpoint);
+    }
+
+    ///
+    /// Mark a gif as recently used by moving it to the end of its pools.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend id.
+    /// * `url` - The URL of the gif.
+    ///
+    fn touch(&mut self, backend: &str, url: &str) {
+        let Some(cache) = self.caches.get_mut(backend) else {
+            return;
+        };
+
+        for pool in cache.values_mut() {
+            if let Some(index) = pool.iter().position(|gif| gif.url == url) {
+                let gif = pool.remove(index);
+                pool.push(gif);
+            }
+        }
+    }
 
-        // try to fetch the gif from the cache
-        let url = cache.remove(endpoint)
-            .ok_or_else(|| anyhow!("unknown endpoint: {}", endpoint))?;
+}
 
-        // return the url
-        Ok(url)
+/// Settings of the refresh queue
+#[derive(Clone, Copy)]
+pub struct RefreshQueueSettings {
+    /// Number of refreshes running at the same time
+    pub workers: usize,
+    /// Number of refreshes that can wait in the queue
+    pub capacity: usize,
+    /// Time each worker waits after a refresh
+    pub delay: Duration
+}
+
+/// Settings of the background refresher
+#[derive(Clone, Copy)]
+pub struct BackgroundRefreshSettings {
+    /// Time between refreshes of an endpoint
+    pub interval: Duration,
+    /// Fraction of the interval each refresh is randomly moved by, between 0 and 1
+    pub jitter: f64
+}
+
+impl BackgroundRefreshSettings {
+
+    ///
+    /// Pick the time until the next refresh of an endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` - The random number generator.
+    ///
+    /// # Returns
+    ///
+    /// The interval moved by a random amount of up to the jitter fraction in either direction.
+    ///
+    fn next_delay(&self, rng: &mut impl Rng) -> Duration {
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return self.interval;
+        }
+
+        self.interval.mul_f64(1.0 + rng.gen_range(-jitter..=jitter))
+    }
+
+}
+
+/// Handle to a running refresh queue
+struct RefreshQueue {
+    /// Sender for queueing refreshes
+    sender: mpsc::Sender<RefreshJob>,
+    /// Refreshes that are queued or running
+    pending: Arc<Mutex<HashSet<RefreshJob>>>,
+    /// Task dispatching refreshes to the workers
+    task: JoinHandle<()>
+}
+
+///
+/// Build a header map from configured headers, expanding environment variables.
+///
+/// # Arguments
+///
+/// * `headers` - The header names and values.
+///
+/// # Returns
+///
+/// The header map.
+///
+/// # Errors
+///
+/// Returns an error if a header is invalid or an environment variable is not set.
+///
+fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap, anyhow::Error> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let value = env::expand(value)
+            .with_context(|| format!("failed to expand header '{}'", name))?;
+        map.insert(
+            HeaderName::from_bytes(name.as_bytes()).with_context(|| format!("invalid header name '{}'", name))?,
+            HeaderValue::from_str(&value).with_context(|| format!("invalid value of header '{}'", name))?
+        );
     }
+    Ok(map)
+}
+
+///
+/// Lock a mutex, ignoring poisoning.
+///
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Handle for refreshing the cache, shared between the backend manager and its refresh workers
+#[derive(Clone)]
+struct Refresher {
+    /// The registered backends
+    backends: Arc<Backends>,
+    /// The cache state
+    state: Arc<Mutex<CacheState>>,
+    /// Gif cache shared with other instances
+    #[cfg(feature = "redis-cache")]
+    shared: Option<Arc<shared::SharedCache>>
+}
+
+impl Refresher {
 
     ///
-    /// Refresh the cache for the specified backend.
+    /// Refresh the cache for the specified backend by adding a new gif to the pool.
     ///
     /// # Arguments
     ///
     /// * `backend` - The backend to refresh the cache for.
-    /// * `endpoint` - The endpoint to refresh the cache for.
+    /// * `endpoint` - The endpoint to refresh the cache for, optionally combined with '+'.
     ///
     /// # Errors
     ///
     /// Returns an error if the backend is unknown or if the cache refresh fails.
     ///
-    pub async fn refresh_cache(&mut self, backend: &str, endpoint: &str) -> Result<(), anyhow::Error> {
+    async fn refresh(&self, backend: &str, endpoint: &str) -> Result<(), anyhow::Error> {
         trace!(target: "module/reaction/backend", "refreshing '{}' endpoint on cached backend '{}'", endpoint, backend);
 
-        // get backend and cache
+        // get backend
         let backend = self.backends.get(backend)
             .ok_or_else(|| anyhow!("unknown backend: {}", backend))?;
-        let cache = self.caches.get_mut(backend.id())
-            .ok_or_else(|| anyhow!("no cache for backend: {}", backend.id()))?;
+
+        // pick one of the combined endpoints
+        let endpoints: Vec<&str> = endpoint.split('+').collect();
+        let endpoint = *endpoints.choose(&mut rand::thread_rng())
+            .ok_or_else(|| anyhow!("no endpoint"))?;
 
         // fetch the gif
-        let gif = backend.fetch(endpoint).await
-            .context("failed to fetch gif")?;
+        let gif = self.fetch(backend.as_ref(), endpoint).await?;
 
         // cache the gif
-        cache.insert(endpoint.to_string(), gif);
+        let mut state = lock(&self.state);
+        state.insert(backend.id(), endpoint, gif);
+        state.mark_refreshed(backend.id(), endpoint);
 
         Ok(())
     }
 
+    ///
+    /// Fetch a gif from a backend, drawing from the shared cache if another instance is already refreshing the endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend to fetch the gif from.
+    /// * `endpoint` - The endpoint to fetch the gif from.
+    ///
+    /// # Returns
+    ///
+    /// The URL of the gif.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the gif cannot be fetched.
+    ///
+    async fn fetch(&self, backend: &dyn Backend, endpoint: &str) -> Result<String, anyhow::Error> {
+        #[cfg(feature = "redis-cache")]
+        if let Some(shared) = &self.shared {
+            if !shared.try_lock_refresh(backend.id(), endpoint).await? {
+                if let Some(gif) = shared.random(backend.id(), endpoint).await? {
+                    return Ok(gif);
+                }
+            }
+
+            let gif = self.fetch_tracked(backend, endpoint).await?;
+            shared.add(backend.id(), endpoint, &gif).await?;
+            return Ok(gif);
+        }
+
+        self.fetch_tracked(backend, endpoint).await
+    }
+
+    ///
+    /// Fetch a gif from a backend and update the success rate of the backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend to fetch the gif from.
+    /// * `endpoint` - The endpoint to fetch the gif from.
+    ///
+    /// # Returns
+    ///
+    /// The URL of the gif.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the gif cannot be fetched.
+    ///
+    async fn fetch_tracked(&self, backend: &dyn Backend, endpoint: &str) -> Result<String, anyhow::Error> {
+        let result = backend.fetch(endpoint).await;
+
+        // track the outcome in a rolling window
+        let rate = {
+            let mut state = lock(&self.state);
+            let outcomes = state.outcomes.entry(backend.id()).or_default();
+            outcomes.push_back(result.is_ok());
+            if outcomes.len() > SUCCESS_WINDOW {
+                outcomes.pop_front();
+            }
+            outcomes.iter().filter(|ok| **ok).count() as f64 / outcomes.len() as f64
+        };
+        metrics::set_gauge("puppy_backend_success_rate", &[("backend", backend.id())], rate);
+
+        result.context("failed to fetch gif")
+    }
+
+}
+
+///
+/// Dispatch queued refreshes to a limited number of workers, backing off after failures.
+///
+/// # Arguments
+///
+/// * `refresher` - The handle for refreshing the cache.
+/// * `pending` - Refreshes that are queued or running.
+/// * `receiver` - Receiver of queued refreshes.
+/// * `settings` - The refresh queue settings.
+///
+async fn run_refresh_queue(refresher: Refresher, pending: Arc<Mutex<HashSet<RefreshJob>>>, mut receiver: mpsc::Receiver<RefreshJob>, settings: RefreshQueueSettings) {
+    let mut workers = JoinSet::new();
+    let mut backoff = Duration::ZERO;
+    let mut open = true;
+
+    while open || !workers.is_empty() {
+        tokio::select! {
+            job = receiver.recv(), if open && workers.len() < settings.workers => {
+                let Some((backend, endpoint)) = job else {
+                    open = false;
+                    continue;
+                };
+
+                // back off after failed refreshes
+                if !backoff.is_zero() {
+                    tokio::time::sleep(backoff).await;
+                }
+
+                let (refresher, pending) = (refresher.clone(), pending.clone());
+                workers.spawn(async move {
+                    let result = refresher.refresh(&backend, &endpoint).await
+                        .with_context(|| format!("failed to refresh '{}' endpoint on backend '{}'", endpoint, backend));
+                    lock(&pending).remove(&(backend, endpoint));
+
+                    tokio::time::sleep(settings.delay).await;
+                    result
+                });
+            },
+            Some(result) = workers.join_next() => {
+                match result {
+                    Ok(Ok(())) => backoff = Duration::ZERO,
+                    Ok(Err(e)) => {
+                        backoff = (backoff * 2).clamp(MIN_BACKOFF, MAX_BACKOFF);
+                        warn!(target: "module/reaction/backend", "{:?}, backing off for {}s", e, backoff.as_secs());
+                    },
+                    Err(e) => warn!(target: "module/reaction/backend", "refresh worker failed: {:?}", e)
+                }
+            }
+        }
+    }
+
+    debug!(target: "module/reaction/backend", "refresh queue stopped");
+}
+
+///
+/// Refresh every endpoint on its own jittered schedule until cancelled.
+///
+/// Failed and panicking refreshes are logged and rescheduled like successful ones.
+///
+/// # Arguments
+///
+/// * `refresher` - The handle for refreshing the cache.
+/// * `endpoints` - The endpoints to refresh by backend id.
+/// * `settings` - The background refresh settings.
+/// * `cancel` - The token stopping the refresher.
+///
+async fn run_background_refresh(refresher: Refresher, endpoints: Vec<(&'static str, String)>, settings: BackgroundRefreshSettings, cancel: CancellationToken) {
+    let mut rng = StdRng::from_entropy();
+
+    // spread the first refreshes over the interval
+    let now = Instant::now();
+    let mut schedule: BinaryHeap<_> = endpoints.into_iter()
+        .map(|(backend, endpoint)| Reverse((now + settings.interval.mul_f64(rng.gen()), backend, endpoint)))
+        .collect();
+
+    while let Some(Reverse((due, backend, endpoint))) = schedule.pop() {
+        let name = format!("refresh '{}' endpoint on backend '{}'", endpoint, backend);
+        let refresh = {
+            let (refresher, endpoint) = (refresher.clone(), endpoint.clone());
+            async move { refresher.refresh(backend, &endpoint).await }
+        };
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = async {
+                tokio::time::sleep_until(due).await;
+                task::run_iteration("module/reaction/backend", &name, refresh).await
+            } => {}
+        }
+
+        schedule.push(Reverse((Instant::now() + settings.next_delay(&mut rng), backend, endpoint)));
+    }
+
+    debug!(target: "module/reaction/backend", "background refresh stopped");
+}
+
+///
+/// Builder for a [`BackendManager`] with user configured backends and options.
+///
+#[derive(Default)]
+pub struct BackendManagerBuilder {
+    strategy: SelectionStrategy,
+    max_pool_size: Option<usize>,
+    pool_limits: HashMap<String, usize>,
+    memory_budget: Option<usize>,
+    healthy_pool_size: Option<usize>,
+    healthy_pool_sizes: HashMap<String, usize>,
+    timeout: Option<Duration>,
+    timeouts: HashMap<String, Duration>,
+    http_backends: Vec<(String, http::HttpBackendSettings)>,
+    #[cfg(feature = "feed-backends")]
+    feed_backends: Vec<(String, feed::FeedBackendSettings)>,
+    #[cfg(feature = "avatar-backends")]
+    avatar_backends: Vec<(String, avatar::AvatarBackendSettings)>,
+    headers: Vec<(String, HashMap<String, String>)>,
+    dimension_filters: Vec<(String, DimensionFilter)>,
+    #[cfg(feature = "redis-cache")]
+    shared_cache: Option<String>
+}
+
+impl BackendManagerBuilder {
+
+    ///
+    /// Set the strategy for picking gifs from a pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - The selection strategy.
+    ///
+    pub fn selection_strategy(mut self, strategy: SelectionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    ///
+    /// Set the maximum number of gifs in a pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_pool_size` - The default limit, unlimited if none.
+    /// * `pool_limits` - Limits by backend id, overriding the default.
+    ///
+    pub fn pool_limits(mut self, max_pool_size: Option<usize>, pool_limits: HashMap<String, usize>) -> Self {
+        self.max_pool_size = max_pool_size;
+        self.pool_limits = pool_limits;
+        self
+    }
+
+    ///
+    /// Set the estimated number of bytes all pools may take up together.
+    ///
+    /// # Arguments
+    ///
+    /// * `budget` - The memory budget in bytes, unlimited if none.
+    ///
+    pub fn memory_budget(mut self, budget: Option<usize>) -> Self {
+        self.memory_budget = budget;
+        self
+    }
+
+    ///
+    /// Set the minimum number of gifs in a healthy pool, below which a warning is logged.
+    ///
+    /// # Arguments
+    ///
+    /// * `healthy_pool_size` - The default size, unchecked if none.
+    /// * `healthy_pool_sizes` - Sizes by backend id, overriding the default.
+    ///
+    pub fn healthy_pool_sizes(mut self, healthy_pool_size: Option<usize>, healthy_pool_sizes: HashMap<String, usize>) -> Self {
+        self.healthy_pool_size = healthy_pool_size;
+        self.healthy_pool_sizes = healthy_pool_sizes;
+        self
+    }
+
+    ///
+    /// Set how long requests of a backend may take before they fail.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The default timeout, none if unset.
+    /// * `timeouts` - Timeouts by backend id, overriding the default.
+    ///
+    pub fn timeouts(mut self, timeout: Option<Duration>, timeouts: HashMap<String, Duration>) -> Self {
+        self.timeout = timeout;
+        self.timeouts = timeouts;
+        self
+    }
+
+    ///
+    /// Register a generic http backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the backend.
+    /// * `settings` - The http backend settings.
+    ///
+    pub fn http_backend(mut self, id: &str, settings: http::HttpBackendSettings) -> Self {
+        self.http_backends.push((id.to_string(), settings));
+        self
+    }
+
+    ///
+    /// Register a backend reading gifs off an rss or atom feed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the backend.
+    /// * `settings` - The feed backend settings.
+    ///
+    #[cfg(feature = "feed-backends")]
+    pub fn feed_backend(mut self, id: &str, settings: feed::FeedBackendSettings) -> Self {
+        self.feed_backends.push((id.to_string(), settings));
+        self
+    }
+
+    ///
+    /// Register a backend turning the avatar of the target into a gif.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the backend.
+    /// * `settings` - The avatar backend settings.
+    ///
+    #[cfg(feature = "avatar-backends")]
+    pub fn avatar_backend(mut self, id: &str, settings: avatar::AvatarBackendSettings) -> Self {
+        self.avatar_backends.push((id.to_string(), settings));
+        self
+    }
+
+    ///
+    /// Set additional headers sent with every request of a backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the backend.
+    /// * `headers` - The header names and values, supporting '${NAME}' environment variables.
+    ///
+    pub fn headers(mut self, id: &str, headers: HashMap<String, String>) -> Self {
+        self.headers.push((id.to_string(), headers));
+        self
+    }
+
+    ///
+    /// Set the dimensions gifs of a backend must have to be cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the backend.
+    /// * `filter` - The dimension filter.
+    ///
+    pub fn dimension_filter(mut self, id: &str, filter: DimensionFilter) -> Self {
+        self.dimension_filters.push((id.to_string(), filter));
+        self
+    }
+
+    ///
+    /// Share the gif cache with other instances through a redis server.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The url of the redis server, e.g. 'redis://127.0.0.1:6379'.
+    ///
+    #[cfg(feature = "redis-cache")]
+    pub fn shared_cache(mut self, url: &str) -> Self {
+        self.shared_cache = Some(url.to_string());
+        self
+    }
+
+    ///
+    /// Build the backend manager.
+    ///
+    /// # Returns
+    ///
+    /// The backend manager with the default and all registered backends.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a backend is registered twice, options refer to an unknown backend
+    /// or headers or the shared cache url are invalid.
+    ///
+    pub fn build(self) -> Result<BackendManager, anyhow::Error> {
+        let mut manager = BackendManager::new()?;
+
+        // register backends before applying options to them
+        for (id, settings) in self.http_backends {
+            manager.register_http_backend(&id, settings)?;
+        }
+        #[cfg(feature = "feed-backends")]
+        for (id, settings) in self.feed_backends {
+            manager.register_feed_backend(&id, settings)?;
+        }
+        #[cfg(feature = "avatar-backends")]
+        for (id, settings) in self.avatar_backends {
+            manager.register_avatar_backend(&id, settings)?;
+        }
+
+        for (id, headers) in self.headers {
+            manager.set_headers(&id, &headers)?;
+        }
+        for (id, filter) in self.dimension_filters {
+            manager.set_dimension_filter(&id, filter)?;
+        }
+        manager.set_timeouts(self.timeout, &self.timeouts)?;
+        manager.set_pool_limits(self.max_pool_size, self.pool_limits);
+        manager.set_memory_budget(self.memory_budget);
+        manager.set_healthy_pool_sizes(self.healthy_pool_size, self.healthy_pool_sizes);
+        manager.set_selection_strategy(self.strategy);
+        #[cfg(feature = "redis-cache")]
+        if let Some(url) = self.shared_cache {
+            manager.set_shared_cache(&url)?;
+        }
+
+        Ok(manager)
+    }
+
+}
+
+///
+/// Backend manager for the '/reaction' command module.
+///
+/// Provides a way to manage multiple backends as well as cache pools of gifs for their endpoints.
+///
+/// Endpoints can be combined with a '+' (e.g. 'hug+cuddle'), drawing from the pools of all of them.
+///
+pub struct BackendManager {
+    backends: Arc<Backends>,
+    state: Arc<Mutex<CacheState>>,
+    strategy: SelectionStrategy,
+    queue: Option<RefreshQueue>,
+    background: Option<BackgroundTask>,
+    #[cfg(feature = "redis-cache")]
+    shared: Option<Arc<shared::SharedCache>>
+}
+
+impl BackendManager {
+
+    ///
+    /// Create a builder for a backend manager.
+    ///
+    /// # Returns
+    ///
+    /// A builder without user configured backends.
+    ///
+    pub fn builder() -> BackendManagerBuilder {
+        BackendManagerBuilder::default()
+    }
+
+    ///
+    /// Create a new backend manager.
+    ///
+    /// # Returns
+    ///
+    /// A new backend manager.
+    ///
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let mut backends: Backends = HashMap::new();
+        let mut state = CacheState::default();
+
+        // add backends
+        let nekosbest = nekosbest::NekosBest::new();
+        let id = nekosbest.id();
+        backends.insert(id, Box::new(nekosbest));
+        state.caches.insert(id, HashMap::new());
+        info!(target: "module/reaction/backend", "registered nekos.best backend");
+
+        let otakugifs = otakugifs::OtakuGifs::new();
+        let id = otakugifs.id();
+        backends.insert(id, Box::new(otakugifs));
+        state.caches.insert(id, HashMap::new());
+        info!(target: "module/reaction/backend", "registered otakugifs.xyz backend");
+
+        // build manager
+        Ok(Self {
+            backends: Arc::new(backends),
+            state: Arc::new(Mutex::new(state)),
+            strategy: SelectionStrategy::default(),
+            queue: None,
+            background: None,
+            #[cfg(feature = "redis-cache")]
+            shared: None
+        })
+    }
+
+    ///
+    /// Share the gif cache with other instances through a redis server.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The url of the redis server, e.g. 'redis://127.0.0.1:6379'.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the url is invalid.
+    ///
+    #[cfg(feature = "redis-cache")]
+    pub fn set_shared_cache(&mut self, url: &str) -> Result<(), anyhow::Error> {
+        info!(target: "module/reaction/backend", "sharing cache through redis server at {}", crate::store::redis::redact_url(url));
+        self.shared = Some(Arc::new(shared::SharedCache::new(url)?));
+        Ok(())
+    }
+
+    ///
+    /// Get a handle for refreshing the cache.
+    ///
+    fn refresher(&self) -> Refresher {
+        Refresher {
+            backends: self.backends.clone(),
+            state: self.state.clone(),
+            #[cfg(feature = "redis-cache")]
+            shared: self.shared.clone()
+        }
+    }
+
+    ///
+    /// Register a generic http backend configured by the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the backend.
+    /// * `settings` - The http backend settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a backend with the id is already registered or the cache is in use.
+    ///
+    pub fn register_http_backend(&mut self, id: &str, settings: http::HttpBackendSettings) -> Result<(), anyhow::Error> {
+        let backends = Arc::get_mut(&mut self.backends)
+            .ok_or_else(|| anyhow!("backends can't be registered while the cache is in use"))?;
+        if backends.contains_key(id) {
+            return Err(anyhow!("backend '{}' is already registered", id));
+        }
+
+        // backends are registered once at startup, so leaking the id is fine
+        let id: &'static str = Box::leak(id.to_string().into_boxed_str());
+        backends.insert(id, Box::new(http::HttpBackend::new(id, settings)?));
+        lock(&self.state).caches.insert(id, HashMap::new());
+        info!(target: "module/reaction/backend", "registered http backend '{}'", id);
+        Ok(())
+    }
+
+    ///
+    /// Register a backend reading gifs off an rss or atom feed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the backend.
+    /// * `settings` - The feed backend settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a backend with the id is already registered or the cache is in use.
+    ///
+    #[cfg(feature = "feed-backends")]
+    pub fn register_feed_backend(&mut self, id: &str, settings: feed::FeedBackendSettings) -> Result<(), anyhow::Error> {
+        let backends = Arc::get_mut(&mut self.backends)
+            .ok_or_else(|| anyhow!("backends can't be registered while the cache is in use"))?;
+        if backends.contains_key(id) {
+            return Err(anyhow!("backend '{}' is already registered", id));
+        }
+
+        // backends are registered once at startup, so leaking the id is fine
+        let id: &'static str = Box::leak(id.to_string().into_boxed_str());
+        backends.insert(id, Box::new(feed::FeedBackend::new(id, settings)));
+        lock(&self.state).caches.insert(id, HashMap::new());
+        info!(target: "module/reaction/backend", "registered feed backend '{}'", id);
+        Ok(())
+    }
+
+    ///
+    /// Register a backend turning the avatar of the target into a gif.
+    ///
+    /// Gifs of these backends are never cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the backend.
+    /// * `settings` - The avatar backend settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a backend with the id is already registered or the cache is in use.
+    ///
+    #[cfg(feature = "avatar-backends")]
+    pub fn register_avatar_backend(&mut self, id: &str, settings: avatar::AvatarBackendSettings) -> Result<(), anyhow::Error> {
+        let backends = Arc::get_mut(&mut self.backends)
+            .ok_or_else(|| anyhow!("backends can't be registered while the cache is in use"))?;
+        if backends.contains_key(id) {
+            return Err(anyhow!("backend '{}' is already registered", id));
+        }
+
+        // backends are registered once at startup, so leaking the id is fine
+        let id: &'static str = Box::leak(id.to_string().into_boxed_str());
+        backends.insert(id, Box::new(avatar::AvatarBackend::new(id, settings)));
+        info!(target: "module/reaction/backend", "registered avatar backend '{}'", id);
+        Ok(())
+    }
+
+    ///
+    /// Check whether gifs of a backend must be fetched with the context of the reaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The id of the backend.
+    ///
+    /// # Returns
+    ///
+    /// Whether the backend requires a context, false for unknown backends.
+    ///
+    #[cfg(feature = "avatar-backends")]
+    pub fn requires_context(&self, backend: &str) -> bool {
+        self.backends.get(backend).is_some_and(|backend| backend.requires_context())
+    }
+
+    ///
+    /// Fetch a gif for the context of a reaction, bypassing the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The id of the backend.
+    /// * `endpoint` - The endpoint to fetch from.
+    /// * `context` - The context of the reaction.
+    ///
+    /// # Returns
+    ///
+    /// The url of the gif.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend is unknown or the request fails.
+    ///
+    #[cfg(feature = "avatar-backends")]
+    pub async fn fetch_with_context(&self, backend: &str, endpoint: &str, context: &FetchContext) -> Result<String, anyhow::Error> {
+        let backend = self.backends.get(backend)
+            .ok_or_else(|| anyhow!("unknown backend: {}", backend))?;
+        backend.fetch_with_context(endpoint, context).await
+    }
+
+    ///
+    /// Set additional headers sent with every request of a backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the backend.
+    /// * `headers` - The headers, supporting '${NAME}' environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend is unknown, a header is invalid or the cache is in use.
+    ///
+    pub fn set_headers(&mut self, id: &str, headers: &HashMap<String, String>) -> Result<(), anyhow::Error> {
+        let headers = header_map(headers)
+            .with_context(|| format!("invalid headers for backend '{}'", id))?;
+        let backends = Arc::get_mut(&mut self.backends)
+            .ok_or_else(|| anyhow!("headers can't be set while the cache is in use"))?;
+        let backend = backends.get_mut(id)
+            .ok_or_else(|| anyhow!("unknown backend: {}", id))?;
+
+        backend.set_headers(headers);
+        Ok(())
+    }
+
+    ///
+    /// Set the dimensions gifs of a backend must have to be cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the backend.
+    /// * `filter` - The dimension filter.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend is unknown or the cache is in use.
+    ///
+    pub fn set_dimension_filter(&mut self, id: &str, filter: DimensionFilter) -> Result<(), anyhow::Error> {
+        let backends = Arc::get_mut(&mut self.backends)
+            .ok_or_else(|| anyhow!("dimension filters can't be set while the cache is in use"))?;
+        let backend = backends.get_mut(id)
+            .ok_or_else(|| anyhow!("unknown backend: {}", id))?;
+
+        backend.set_dimension_filter(filter);
+        Ok(())
+    }
+
+    ///
+    /// Set how long requests of each backend may take before they fail.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The default timeout, none if unset.
+    /// * `timeouts` - Timeouts by backend id, overriding the default.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a backend is unknown, an http client fails to build or the cache is in use.
+    ///
+    pub fn set_timeouts(&mut self, timeout: Option<Duration>, timeouts: &HashMap<String, Duration>) -> Result<(), anyhow::Error> {
+        let backends = Arc::get_mut(&mut self.backends)
+            .ok_or_else(|| anyhow!("timeouts can't be set while the cache is in use"))?;
+        if let Some(id) = timeouts.keys().find(|id| !backends.contains_key(id.as_str())) {
+            return Err(anyhow!("unknown backend: {}", id));
+        }
+
+        for (id, backend) in backends.iter_mut() {
+            let Some(timeout) = timeouts.get(*id).copied().or(timeout) else {
+                continue;
+            };
+            let http = reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .with_context(|| format!("failed to build http client for backend '{}'", id))?;
+            backend.set_http_client(http);
+            debug!(target: "module/reaction/backend", "set timeout of backend '{}' to {:?}", id, timeout);
+        }
+        Ok(())
+    }
+
+    ///
+    /// Set the strategy for picking gifs from a pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - The selection strategy.
+    ///
+    pub fn set_selection_strategy(&mut self, strategy: SelectionStrategy) {
+        self.strategy = strategy;
+    }
+
+    ///
+    /// Limit the number of gifs in each pool, evicting the least recently used gifs first.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_pool_size` - The maximum number of gifs in a pool, if any.
+    /// * `pool_limits` - The maximum number of gifs in a pool by backend id, overriding the default.
+    ///
+    pub fn set_pool_limits(&mut self, max_pool_size: Option<usize>, pool_limits: HashMap<String, usize>) {
+        let mut state = lock(&self.state);
+        state.max_pool_size = max_pool_size;
+        state.pool_limits = pool_limits;
+    }
+
+    ///
+    /// Set the estimated number of bytes all pools may take up together, trimming the pools if they exceed it.
+    ///
+    /// # Arguments
+    ///
+    /// * `budget` - The memory budget in bytes, unlimited if none.
+    ///
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        let mut state = lock(&self.state);
+        state.memory_budget = budget;
+        state.trim_to_budget();
+    }
+
+    ///
+    /// Set the minimum number of gifs in a healthy pool and check every pool against it.
+    ///
+    /// # Arguments
+    ///
+    /// * `healthy_pool_size` - The minimum number of gifs in a healthy pool, unchecked if none.
+    /// * `healthy_pool_sizes` - The minimum number of gifs in a healthy pool by backend id, overriding the default.
+    ///
+    pub fn set_healthy_pool_sizes(&mut self, healthy_pool_size: Option<usize>, healthy_pool_sizes: HashMap<String, usize>) {
+        let mut state = lock(&self.state);
+        state.healthy_pool_size = healthy_pool_size;
+        state.healthy_pool_sizes = healthy_pool_sizes;
+        state.check_health(None);
+    }
+
+    ///
+    /// Build the initial cache of endpoints for all backends.
+    ///
+    /// # Arguments
+    ///
+    /// * `only` - The endpoints to warm as 'backend/endpoint', all endpoints if none.
+    /// * `priority` - The endpoints to warm before all others as 'backend/endpoint', in order.
+    /// * `budget` - The maximum number of gifs to fetch, deferring the remaining endpoints, unlimited if none.
+    /// * `progress_interval` - Number of warmed endpoints between progress logs, 0 to disable.
+    /// * `progress` - Sender receiving the number of warmed and total endpoints after every endpoint.
+    ///
+    /// # Returns
+    ///
+    /// The endpoints that weren't warmed as they didn't fit into the budget, as 'backend/endpoint'.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache build fails.
+    ///
+    pub async fn build_cache(&mut self, only: Option<&HashSet<String>>, priority: &[String], budget: Option<usize>, progress_interval: usize, progress: &watch::Sender<(usize, usize)>) -> Result<Vec<String>, anyhow::Error> {
+        info!(target: "module/reaction/backend", "building cache, this may take a while...");
+        let refresher = self.refresher();
+
+        // respect gifs blocked by other instances
+        #[cfg(feature = "redis-cache")]
+        if let Some(shared) = &self.shared {
+            let blocked = shared.blocked().await
+                .context("failed to fetch shared blocked gifs")?;
+            lock(&self.state).blocked.extend(blocked);
+        }
+
+        // fetch list of endpoints of all backends
+        let mut endpoints = Vec::new();
+        for backend in self.backends.values() {
+            #[cfg(feature = "avatar-backends")]
+            if backend.requires_context() {
+                continue;
+            }
+
+            let backend_endpoints = backend.endpoints().await
+                .context("failed to fetch endpoints")?;
+            endpoints.extend(backend_endpoints.into_iter()
+                .filter(|endpoint| only.is_none_or(|only| only.contains(&format!("{}/{}", backend.id(), endpoint))))
+                .map(|endpoint| (backend, endpoint)));
+        }
+        let total = endpoints.len();
+
+        // warm the priority endpoints first, keeping the order of the others
+        if !priority.is_empty() {
+            let rank = |backend: &dyn Backend, endpoint: &str| priority.iter()
+                .position(|info| info.split_once('/') == Some((backend.id(), endpoint)));
+            endpoints.sort_by_cached_key(|(backend, endpoint)| rank(backend.as_ref(), endpoint).unwrap_or(priority.len()));
+            let first = endpoints.iter()
+                .take_while(|(backend, endpoint)| rank(backend.as_ref(), endpoint).is_some())
+                .map(|(backend, endpoint)| format!("{}/{}", backend.id(), endpoint))
+                .collect::<Vec<_>>();
+            info!(target: "module/reaction/backend", "warming {} priority endpoints first: {}", first.len(), first.join(", "));
+        }
+
+        // fetch a gif from each endpoint that isn't cached yet, as long as the budget allows
+        let mut fetched = 0;
+        let mut deferred = Vec::new();
+        for (warmed, (backend, endpoint)) in endpoints.into_iter().enumerate() {
+            let id = backend.id();
+
+            #[cfg(feature = "redis-cache")]
+            self.import_shared_pool(id, &endpoint).await?;
+
+            let uncached = lock(&self.state).caches.get(id).and_then(|cache| cache.get(&endpoint)).is_none_or(|pool| pool.is_empty());
+            if uncached && budget.is_some_and(|budget| fetched >= budget) {
+                deferred.push(format!("{}/{}", id, endpoint));
+            } else if uncached {
+                let gif = refresher.fetch(backend.as_ref(), &endpoint).await?;
+                fetched += 1;
+
+                // cache the gif
+                {
+                    let mut state = lock(&self.state);
+                    state.insert(id, &endpoint, gif);
+                    state.mark_refreshed(id, &endpoint);
+                }
+
+                // wait 200ms before fetching the next gif
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            }
+
+            // report progress
+            let warmed = warmed + 1;
+            progress.send_replace((warmed, total));
+            if progress_interval > 0 && (warmed % progress_interval == 0 || warmed == total) {
+                info!(target: "module/reaction/backend", "warmed {}/{} endpoints", warmed, total);
+            }
+        }
+
+        if !deferred.is_empty() {
+            info!(target: "module/reaction/backend", "deferred {} endpoints beyond the budget of {} fetches: {}",
+                deferred.len(), fetched, deferred.join(", "));
+        }
+        info!(target: "module/reaction/backend", "cache build complete");
+        Ok(deferred)
+    }
+
+    ///
+    /// Add the gifs of a shared pool to the local pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The backend id.
+    /// * `endpoint` - The endpoint.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the shared pool cannot be fetched.
+    ///
+    #[cfg(feature = "redis-cache")]
+    async fn import_shared_pool(&self, id: &'static str, endpoint: &str) -> Result<(), anyhow::Error> {
+        let Some(shared) = &self.shared else {
+            return Ok(());
+        };
+
+        let gifs = shared.pool(id, endpoint).await
+            .context("failed to fetch shared pool")?;
+        let mut state = lock(&self.state);
+        for gif in gifs {
+            state.insert(id, endpoint, gif);
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Export the cache of all backends.
+    ///
+    /// # Returns
+    ///
+    /// A map of backend ids to maps of endpoints to pools of cached gifs.
+    ///
+    pub fn export_cache(&self) -> HashMap<String, HashMap<String, Vec<CachedGif>>> {
+        lock(&self.state).caches.iter()
+            .map(|(id, cache)| (id.to_string(), cache.clone()))
+            .collect()
+    }
+
+    ///
+    /// Import a previously exported cache, ignoring unknown backends.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - A map of backend ids to maps of endpoints to pools of cached gifs.
+    ///
+    pub fn import_cache(&mut self, cache: HashMap<String, HashMap<String, Vec<CachedGif>>>) {
+        let mut state = lock(&self.state);
+        let state = &mut *state;
+        for (id, mut entries) in cache {
+            let limit = state.pool_limits.get(&id).copied().or(state.max_pool_size);
+            if let Some(existing) = state.caches.get_mut(id.as_str()) {
+                // drop the least recently used gifs above the size limit
+                if let Some(limit) = limit {
+                    for pool in entries.values_mut() {
+                        let excess = pool.len().saturating_sub(limit.max(1));
+                        pool.drain(..excess);
+                    }
+                }
+
+                info!(target: "module/reaction/backend", "imported {} cached endpoints for backend '{}'", entries.len(), id);
+                existing.extend(entries);
+            }
+        }
+        state.trim_to_budget();
+        state.check_health(None);
+    }
+
+    ///
+    /// Get a random cached gif from the specified backend and keep it from being evicted.
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` - The random number generator picking the gif.
+    /// * `backend` - The backend to fetch the gif from.
+    /// * `endpoint` - The endpoint to fetch the gif from, optionally combined with '+'.
+    /// * `exclude` - The URL of a gif that must not be picked, if any.
+    ///
+    /// # Returns
+    ///
+    /// A string containing the URL of the fetched gif.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend is unknown.
+    /// Returns an [`EmptyCache`] error if no gif is cached.
+    /// Returns a [`NoVariety`] error if the excluded gif is the only cached gif.
+    ///
+    pub fn get_cached<R: Rng + ?Sized>(&mut self, rng: &mut R, backend: &str, endpoint: &str, exclude: Option<&str>) -> Result<String, anyhow::Error> {
+        trace!(target: "module/reaction/backend", "requested '{}' endpoint on cached backend '{}'", endpoint, backend);
+        let url = self.peek_cached(rng, backend, endpoint, exclude)?;
+
+        // keep the gif from being evicted
+        lock(&self.state).touch(backend, &url);
+
+        Ok(url)
+    }
+
+    ///
+    /// Get several distinct random cached gifs from the specified backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` - The random number generator picking the gifs.
+    /// * `backend` - The backend to fetch the gifs from.
+    /// * `endpoint` - The endpoint to fetch the gifs from, optionally combined with '+'.
+    /// * `exclude` - The URL of a gif that must not be picked, e.g. one picked before.
+    /// * `count` - The maximum number of gifs to pick.
+    ///
+    /// # Returns
+    ///
+    /// The URLs of up to `count` gifs, fewer if the pool is smaller and none if the backend isn't cached.
+    ///
+    pub fn get_cached_many<R: Rng + ?Sized>(&mut self, rng: &mut R, backend: &str, endpoint: &str, exclude: &str, count: usize) -> Vec<String> {
+        let mut state = lock(&self.state);
+        let Some(cache) = state.caches.get(backend) else {
+            return Vec::new();
+        };
+
+        // merge the pools of all endpoints, leaving out the excluded gif
+        let pool: Vec<&CachedGif> = endpoint.split('+')
+            .filter_map(|endpoint| cache.get(endpoint))
+            .flatten()
+            .filter(|gif| gif.url != exclude)
+            .collect();
+        let urls: Vec<String> = pool.choose_multiple(rng, count)
+            .map(|gif| gif.url.clone())
+            .collect();
+
+        // keep the gifs from being evicted
+        for url in &urls {
+            state.touch(backend, url);
+        }
+        urls
+    }
+
+    ///
+    /// Check whether any gif is cached for the specified backend.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend to check.
+    /// * `endpoint` - The endpoint to check, optionally combined with '+'.
+    ///
+    /// # Returns
+    ///
+    /// True if a pool of the endpoints isn't empty or the backend isn't cached at all.
+    ///
+    pub fn is_cached(&self, backend: &str, endpoint: &str) -> bool {
+        let state = lock(&self.state);
+        let Some(cache) = state.caches.get(backend) else {
+            return true;
+        };
+
+        endpoint.split('+').any(|endpoint| cache.get(endpoint).is_some_and(|pool| !pool.is_empty()))
+    }
+
+    ///
+    /// Get the size of a cached pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend id.
+    /// * `endpoint` - The endpoint.
+    ///
+    /// # Returns
+    ///
+    /// The number of cached gifs, or None if the backend isn't cached.
+    ///
+    pub fn pool_size(&self, backend: &str, endpoint: &str) -> Option<usize> {
+        lock(&self.state).caches.get(backend)
+            .map(|cache| cache.get(endpoint).map_or(0, Vec::len))
+    }
+
+    ///
+    /// Check whether a pool dropped below its healthy size.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend id.
+    /// * `endpoint` - The endpoint.
+    ///
+    /// # Returns
+    ///
+    /// True if the pool is smaller than its healthy size.
+    ///
+    pub fn is_unhealthy(&self, backend: &str, endpoint: &str) -> bool {
+        lock(&self.state).unhealthy.contains(&(backend.to_string(), endpoint.to_string()))
+    }
+
+    ///
+    /// Get the time an endpoint was last refreshed.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend id.
+    /// * `endpoint` - The endpoint.
+    ///
+    /// # Returns
+    ///
+    /// The time of the last successful refresh, or None if the endpoint wasn't refreshed since startup.
+    ///
+    pub fn last_refreshed(&self, backend: &str, endpoint: &str) -> Option<Instant> {
+        lock(&self.state).refreshed.get(backend)
+            .and_then(|refreshed| refreshed.get(endpoint))
+            .copied()
+    }
+
+    ///
+    /// Get the sizes of all cached pools.
+    ///
+    /// # Returns
+    ///
+    /// A list of backend ids, endpoints and the number of gifs cached for them.
+    ///
+    pub fn pool_sizes(&self) -> Vec<(&'static str, String, usize)> {
+        lock(&self.state).caches.iter()
+            .flat_map(|(id, cache)| cache.iter().map(|(endpoint, pool)| (*id, endpoint.clone(), pool.len())))
+            .collect()
+    }
+
+    ///
+    /// Get a random cached gif from the specified backend without marking it as used.
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` - The random number generator picking the gif.
+    /// * `backend` - The backend to fetch the gif from.
+    /// * `endpoint` - The endpoint to fetch the gif from, optionally combined with '+'.
+    /// * `exclude` - The URL of a gif that must not be picked, if any.
+    ///
+    /// # Returns
+    ///
+    /// A string containing the URL of the fetched gif.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend is unknown.
+    /// Returns an [`EmptyCache`] error if no gif is cached.
+    /// Returns a [`NoVariety`] error if the excluded gif is the only cached gif.
+    ///
+    pub fn peek_cached<R: Rng + ?Sized>(&self, rng: &mut R, backend: &str, endpoint: &str, exclude: Option<&str>) -> Result<String, anyhow::Error> {
+        // get backend and cache
+        let backend = self.backends.get(backend)
+            .ok_or_else(|| anyhow!("unknown backend: {}", backend))?;
+        let state = lock(&self.state);
+        let cache = state.caches.get(backend.id())
+            .ok_or_else(|| anyhow!("no cache for backend: {}", backend.id()))?;
+
+        // merge the pools of all endpoints
+        let mut pool: Vec<&CachedGif> = endpoint.split('+')
+            .filter_map(|endpoint| cache.get(endpoint))
+            .flatten()
+            .collect();
+
+        // leave out the excluded gif
+        if let Some(exclude) = exclude {
+            let len = pool.len();
+            pool.retain(|gif| gif.url != exclude);
+            if pool.is_empty() && len > 0 {
+                return Err(NoVariety.into());
+            }
+        }
+
+        // pick a random gif from the pool
+        let gif = match self.strategy {
+            SelectionStrategy::Uniform => pool.choose(rng),
+            SelectionStrategy::RecencyWeighted => {
+                // weigh gifs by their rank from oldest to newest
+                pool.sort_by_key(|gif| gif.added);
+                let ranks: Vec<usize> = (1..=pool.len()).collect();
+                ranks.choose_weighted(rng, |rank| *rank).ok()
+                    .map(|rank| &pool[rank - 1])
+            }
+        }.ok_or(EmptyCache).with_context(|| format!("no cached gif for endpoint: {}", endpoint))?;
+
+        // return the url
+        Ok(gif.url.clone())
+    }
+
+    ///
+    /// Remove a gif from all pools of the specified backend and prevent it from being cached again.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend the gif was fetched from.
+    /// * `url` - The URL of the gif.
+    ///
+    /// # Returns
+    ///
+    /// True if the gif was cached.
+    ///
+    pub fn remove_gif(&mut self, backend: &str, url: &str) -> bool {
+        let mut state = lock(&self.state);
+        state.blocked.insert(url.to_string());
+
+        let Some(cache) = state.caches.get_mut(backend) else {
+            return false;
+        };
+
+        let mut removed = false;
+        for pool in cache.values_mut() {
+            let len = pool.len();
+            pool.retain(|gif| gif.url != url);
+            removed |= pool.len() != len;
+        }
+
+        // block the gif for other instances
+        #[cfg(feature = "redis-cache")]
+        if let Some(shared) = self.shared.clone() {
+            let (backend, url) = (backend.to_string(), url.to_string());
+            let endpoints: Vec<String> = cache.keys().cloned().collect();
+            tokio::spawn(async move {
+                if let Err(e) = shared.block(&backend, &endpoints, &url).await {
+                    warn!(target: "module/reaction/backend", "failed to block gif in shared cache: {:?}", e);
+                }
+            });
+        }
+
+        if removed {
+            state.check_health(Some(backend));
+        }
+        removed
+    }
+
+    ///
+    /// Refresh the cache for the specified backend by adding a new gif to the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend to refresh the cache for.
+    /// * `endpoint` - The endpoint to refresh the cache for, optionally combined with '+'.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend is unknown or if the cache refresh fails.
+    ///
+    pub async fn refresh_cache(&self, backend: &str, endpoint: &str) -> Result<(), anyhow::Error> {
+        self.refresher().refresh(backend, endpoint).await
+    }
+
+    ///
+    /// Start the queue processing refreshes in the background.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - The refresh queue settings.
+    ///
+    pub fn start_refresh_queue(&mut self, settings: RefreshQueueSettings) {
+        if self.queue.is_some() {
+            return;
+        }
+
+        info!(target: "module/reaction/backend", "starting refresh queue with {} workers", settings.workers);
+        let (sender, receiver) = mpsc::channel(settings.capacity.max(1));
+        let pending = Arc::new(Mutex::new(HashSet::new()));
+        let task = tokio::spawn(run_refresh_queue(
+            self.refresher(), pending.clone(), receiver,
+            RefreshQueueSettings { workers: settings.workers.max(1), ..settings }
+        ));
+
+        self.queue = Some(RefreshQueue { sender, pending, task });
+    }
+
+    ///
+    /// Queue a refresh of the cache for the specified backend without waiting for it.
+    ///
+    /// Refreshes already queued and refreshes not fitting into the queue are skipped.
+    /// If the refresh queue isn't running, the cache is refreshed in a detached task instead.
+    /// Backends requiring a context aren't cached and therefore never refreshed.
+    /// Failed refreshes are logged.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend to refresh the cache for.
+    /// * `endpoint` - The endpoint to refresh the cache for, optionally combined with '+'.
+    ///
+    pub fn enqueue_refresh(&self, backend: &str, endpoint: &str) {
+        #[cfg(feature = "avatar-backends")]
+        if self.requires_context(backend) {
+            return;
+        }
+
+        let job = (backend.to_string(), endpoint.to_string());
+
+        let Some(queue) = &self.queue else {
+            let refresher = self.refresher();
+            tokio::spawn(async move {
+                let (backend, endpoint) = job;
+                if let Err(e) = refresher.refresh(&backend, &endpoint).await {
+                    warn!(target: "module/reaction/backend", "failed to refresh '{}' endpoint on backend '{}': {:?}", endpoint, backend, e);
+                }
+            });
+            return;
+        };
+
+        if !lock(&queue.pending).insert(job.clone()) {
+            trace!(target: "module/reaction/backend", "'{}' endpoint on backend '{}' is already queued for refresh", endpoint, backend);
+            return;
+        }
+
+        if let Err(e) = queue.sender.try_send(job) {
+            lock(&queue.pending).remove(&e.into_inner());
+            debug!(target: "module/reaction/backend", "skipped refresh of '{}' endpoint on backend '{}', queue is full", endpoint, backend);
+        }
+    }
+
+    ///
+    /// Stop the refresh queue, waiting for queued refreshes to finish.
+    ///
+    pub async fn stop_refresh_queue(&mut self) {
+        let Some(queue) = self.queue.take() else {
+            return;
+        };
+
+        info!(target: "module/reaction/backend", "stopping refresh queue");
+        drop(queue.sender);
+        if let Err(e) = queue.task.await {
+            warn!(target: "module/reaction/backend", "refresh queue failed: {:?}", e);
+        }
+    }
+
+    ///
+    /// Start refreshing all cached endpoints periodically in the background.
+    ///
+    /// Each endpoint is refreshed on its own schedule, randomly moved by the jitter fraction of the interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - The background refresh settings.
+    ///
+    pub fn start_background_refresh(&mut self, settings: BackgroundRefreshSettings) {
+        if self.background.is_some() || settings.interval.is_zero() {
+            return;
+        }
+
+        let endpoints: Vec<_> = lock(&self.state).caches.iter()
+            .flat_map(|(backend, cache)| cache.keys().map(|endpoint| (*backend, endpoint.clone())))
+            .collect();
+
+        info!(target: "module/reaction/backend", "refreshing {} endpoints every {}s in the background", endpoints.len(), settings.interval.as_secs());
+        let refresher = self.refresher();
+        self.background = Some(BackgroundTask::spawn(|cancel| run_background_refresh(refresher, endpoints, settings, cancel)));
+    }
+
+    ///
+    /// Stop the background refresher, waiting for it to return.
+    ///
+    pub async fn stop_background_refresh(&mut self) {
+        if let Some(task) = self.background.take() {
+            task.stop("module/reaction/backend").await;
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(manager: &BackendManager, backend: &str, endpoint: &str) -> Vec<String> {
+        lock(&manager.state).caches[backend][endpoint].iter().map(|gif| gif.url.clone()).collect()
+    }
+
+    #[test]
+    fn same_named_endpoints_keep_separate_pools() {
+        let mut manager = BackendManager::new().unwrap();
+        {
+            let mut state = lock(&manager.state);
+            state.insert("nekos.best", "hug", "https://nekos.best/hug.gif".to_string());
+            state.insert("otakugifs.xyz", "hug", "https://otakugifs.xyz/hug.gif".to_string());
+        }
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..10 {
+            assert_eq!(manager.get_cached(&mut rng, "nekos.best", "hug", None).unwrap(), "https://nekos.best/hug.gif");
+            assert_eq!(manager.get_cached(&mut rng, "otakugifs.xyz", "hug", None).unwrap(), "https://otakugifs.xyz/hug.gif");
+        }
+
+        // inserting into one pool leaves the other untouched
+        lock(&manager.state).insert("nekos.best", "hug", "https://nekos.best/hug2.gif".to_string());
+        assert_eq!(pool(&manager, "nekos.best", "hug"), ["https://nekos.best/hug.gif", "https://nekos.best/hug2.gif"]);
+        assert_eq!(pool(&manager, "otakugifs.xyz", "hug"), ["https://otakugifs.xyz/hug.gif"]);
+
+        // an empty pool of one backend doesn't fall back to the other
+        lock(&manager.state).insert("nekos.best", "pat", "https://nekos.best/pat.gif".to_string());
+        assert!(manager.get_cached(&mut rng, "otakugifs.xyz", "pat", None).unwrap_err().is::<EmptyCache>());
+        assert!(!manager.is_cached("otakugifs.xyz", "pat"));
+    }
+
+    #[test]
+    fn insert_evicts_the_least_recently_used_gifs() {
+        let mut manager = BackendManager::new().unwrap();
+        let mut pool_limits = HashMap::new();
+        pool_limits.insert("otakugifs.xyz".to_string(), 1);
+        manager.set_pool_limits(Some(3), pool_limits);
+
+        let mut state = lock(&manager.state);
+        for i in 1..=3 {
+            state.insert("nekos.best", "hug", format!("{}.gif", i));
+        }
+
+        // using a gif moves it behind the others, so the oldest unused gif is evicted first
+        state.touch("nekos.best", "1.gif");
+        state.insert("nekos.best", "hug", "4.gif".to_string());
+        let urls = |state: &CacheState| state.caches["nekos.best"]["hug"].iter().map(|gif| gif.url.clone()).collect::<Vec<_>>();
+        assert_eq!(urls(&state), ["3.gif", "1.gif", "4.gif"]);
+
+        // gifs already in the pool aren't added again and don't evict others
+        state.insert("nekos.best", "hug", "3.gif".to_string());
+        assert_eq!(urls(&state), ["3.gif", "1.gif", "4.gif"]);
+        state.insert("nekos.best", "hug", "5.gif".to_string());
+        assert_eq!(urls(&state), ["1.gif", "4.gif", "5.gif"]);
+
+        // the limit of a backend overrides the default one
+        state.insert("otakugifs.xyz", "hug", "a.gif".to_string());
+        state.insert("otakugifs.xyz", "hug", "b.gif".to_string());
+        assert_eq!(state.caches["otakugifs.xyz"]["hug"].len(), 1);
+        assert_eq!(state.caches["otakugifs.xyz"]["hug"][0].url, "b.gif");
+    }
 }
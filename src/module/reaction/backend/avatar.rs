@@ -0,0 +1,121 @@
+#[cfg(feature = "avatar-backends")]
+use anyhow::{anyhow, Context, Error};
+#[cfg(feature = "avatar-backends")]
+use log::trace;
+#[cfg(feature = "avatar-backends")]
+use rand::seq::SliceRandom;
+#[cfg(feature = "avatar-backends")]
+use reqwest::header::{HeaderMap, CONTENT_TYPE, USER_AGENT};
+use serde::Deserialize;
+#[cfg(feature = "avatar-backends")]
+use serde_json::{Map, Value};
+#[cfg(feature = "avatar-backends")]
+use serenity::async_trait;
+
+#[cfg(feature = "avatar-backends")]
+use crate::BOT_USER_AGENT;
+
+#[cfg(feature = "avatar-backends")]
+use super::{http::extract, Backend, FetchContext};
+
+/// Struct to hold the settings of a backend turning avatars into gifs
+#[derive(Deserialize, Clone)]
+pub struct AvatarBackendSettings {
+    /// Url of the api, '{endpoint}' is replaced with the requested endpoint
+    pub url: String,
+    /// Key of the avatar url in the json body of the request
+    #[serde(default = "default_field")]
+    pub field: String,
+    /// Dotted path to the gif url in the json response, e.g. 'result.url'
+    pub path: String,
+    /// Endpoints supported by the api
+    pub endpoints: Vec<String>
+}
+
+fn default_field() -> String {
+    "image".to_string()
+}
+
+///
+/// puppy-rs backend for turning the avatar of the target into an animated gif
+///
+/// Gifs depend on the target, so they are fetched for every reaction instead of being cached.
+///
+#[cfg(feature = "avatar-backends")]
+pub struct AvatarBackend {
+    id: &'static str,
+    http: reqwest::Client,
+    headers: HeaderMap,
+    settings: AvatarBackendSettings
+}
+
+#[cfg(feature = "avatar-backends")]
+impl AvatarBackend {
+    pub fn new(id: &'static str, settings: AvatarBackendSettings) -> Self {
+        Self { id, http: reqwest::Client::new(), headers: HeaderMap::new(), settings }
+    }
+}
+
+#[cfg(feature = "avatar-backends")]
+#[async_trait]
+impl Backend for AvatarBackend {
+
+    fn set_headers(&mut self, headers: HeaderMap) {
+        self.headers.extend(headers);
+    }
+
+    fn set_http_client(&mut self, http: reqwest::Client) {
+        self.http = http;
+    }
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    async fn endpoints(&self) -> Result<Vec<String>, Error> {
+        Ok(self.settings.endpoints.clone())
+    }
+
+    async fn fetch(&self, _endpoint: &str) -> Result<String, Error> {
+        Err(anyhow!("{} requires the avatar of the target", self.id))
+    }
+
+    fn requires_context(&self) -> bool {
+        true
+    }
+
+    async fn fetch_with_context(&self, endpoint: &str, context: &FetchContext) -> Result<String, Error> {
+        // build request
+        let url = self.settings.url.replace("{endpoint}", endpoint);
+        let mut body = Map::new();
+        body.insert(self.settings.field.clone(), Value::String(context.avatar.clone()));
+        let request = self.http.post(&url)
+            .header(USER_AGENT, BOT_USER_AGENT)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Value::Object(body).to_string())
+            .headers(self.headers.clone());
+
+        // make request
+        trace!(target: "module/reaction/backend/avatar", "fetching gif for avatar '{}' from '{}'", context.avatar, url);
+        let response = request.send().await
+            .context("failed to make web request to api")?;
+        let status = response.status();
+        let body = response.text().await
+            .context("failed to get response text")?;
+
+        // check error
+        if !status.is_success() {
+            return Err(anyhow!("{} returned status code '{}':\n{}", self.id, status, body));
+        }
+
+        // parse response
+        trace!(target: "module/reaction/backend/avatar", "parsing response:\n{}", body);
+        let json: Value = serde_json::from_str(&body)
+            .context("unable to parse response as json")?;
+        let urls = extract(&json, &self.settings.path);
+        urls.choose(&mut rand::thread_rng())
+            .map(|url| url.to_string())
+            .ok_or_else(|| anyhow!("path '{}' matched no url in response of {}", self.settings.path, self.id))
+    }
+
+}
@@ -0,0 +1,197 @@
+use serde::Deserialize;
+
+#[cfg(feature = "feed-backends")]
+use anyhow::{anyhow, Context, Error};
+#[cfg(feature = "feed-backends")]
+use log::trace;
+#[cfg(feature = "feed-backends")]
+use rand::seq::SliceRandom;
+#[cfg(feature = "feed-backends")]
+use reqwest::header::{HeaderMap, USER_AGENT};
+#[cfg(feature = "feed-backends")]
+use serenity::async_trait;
+
+#[cfg(feature = "feed-backends")]
+use crate::BOT_USER_AGENT;
+
+#[cfg(feature = "feed-backends")]
+use super::Backend;
+
+/// Struct to hold the settings of a backend reading gifs off rss or atom feeds
+#[derive(Deserialize, Clone)]
+pub struct FeedBackendSettings {
+    /// Url of the feed, '{endpoint}' is replaced with the requested endpoint
+    pub url: String,
+    /// File extensions of media urls taken from the feed
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    /// Endpoints supported by the feed
+    pub endpoints: Vec<String>
+}
+
+fn default_extensions() -> Vec<String> {
+    vec!["gif".to_string()]
+}
+
+/// Attributes of feed elements that may link to media
+#[cfg(feature = "feed-backends")]
+const URL_ATTRIBUTES: [&str; 3] = ["url=", "href=", "src="];
+
+///
+/// puppy-rs backend for fetching animated gifs off rss and atom feeds
+///
+/// Media urls are taken from enclosures, media elements, links and images embedded in the entries,
+/// entries without a url of the configured extensions are skipped.
+///
+#[cfg(feature = "feed-backends")]
+pub struct FeedBackend {
+    id: &'static str,
+    http: reqwest::Client,
+    headers: HeaderMap,
+    settings: FeedBackendSettings
+}
+
+#[cfg(feature = "feed-backends")]
+impl FeedBackend {
+    pub fn new(id: &'static str, settings: FeedBackendSettings) -> Self {
+        Self { id, http: reqwest::Client::new(), headers: HeaderMap::new(), settings }
+    }
+
+    ///
+    /// Check whether a url points to media of the configured extensions
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The url
+    ///
+    /// # Returns
+    ///
+    /// Whether the path of the url ends with one of the extensions
+    ///
+    fn is_media(&self, url: &str) -> bool {
+        let path = url.split(['?', '#']).next().unwrap_or(url).to_ascii_lowercase();
+        url.starts_with("http") && self.settings.extensions.iter()
+            .any(|extension| path.ends_with(&format!(".{}", extension.to_ascii_lowercase())))
+    }
+}
+
+#[cfg(feature = "feed-backends")]
+#[async_trait]
+impl Backend for FeedBackend {
+
+    fn set_headers(&mut self, headers: HeaderMap) {
+        self.headers.extend(headers);
+    }
+
+    fn set_http_client(&mut self, http: reqwest::Client) {
+        self.http = http;
+    }
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    async fn endpoints(&self) -> Result<Vec<String>, Error> {
+        Ok(self.settings.endpoints.clone())
+    }
+
+    async fn fetch(&self, endpoint: &str) -> Result<String, Error> {
+        // make request
+        let url = self.settings.url.replace("{endpoint}", endpoint);
+        trace!(target: "module/reaction/backend/feed", "fetching feed from '{}'", url);
+        let response = self.http.get(&url).header(USER_AGENT, BOT_USER_AGENT).headers(self.headers.clone()).send().await
+            .context("failed to make web request to feed")?;
+        let status = response.status();
+        let body = response.text().await
+            .context("failed to get response text")?;
+
+        // check error
+        if !status.is_success() {
+            return Err(anyhow!("{} returned status code '{}':\n{}", self.id, status, body));
+        }
+
+        // parse feed
+        trace!(target: "module/reaction/backend/feed", "parsing feed:\n{}", body);
+        let urls: Vec<String> = extract_urls(&body).into_iter()
+            .filter(|url| self.is_media(url))
+            .collect();
+        trace!(target: "module/reaction/backend/feed", "found {} media urls", urls.len());
+        urls.choose(&mut rand::thread_rng())
+            .cloned()
+            .ok_or_else(|| anyhow!("feed of {} contains no media", self.id))
+    }
+
+}
+
+///
+/// Extract all urls of a feed
+///
+/// Reads quoted attributes that may link to media, including escaped html inside entries,
+/// as well as the contents of link elements.
+///
+/// # Arguments
+///
+/// * `feed` - The rss or atom feed
+///
+/// # Returns
+///
+/// The unescaped urls
+///
+#[cfg(feature = "feed-backends")]
+fn extract_urls(feed: &str) -> Vec<String> {
+    // html inside entries is usually escaped once
+    let feed = unescape(feed);
+    let mut urls = Vec::new();
+
+    // read attributes like url="..." or src='...'
+    for attribute in URL_ATTRIBUTES {
+        let mut rest = feed.as_str();
+        while let Some(start) = rest.find(attribute) {
+            rest = &rest[start + attribute.len()..];
+            let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+                continue;
+            };
+            let Some(end) = rest[1..].find(quote) else {
+                break;
+            };
+            urls.push(unescape(&rest[1..end + 1]));
+            rest = &rest[end + 1..];
+        }
+    }
+
+    // read rss links like <link>...</link>
+    let mut rest = feed.as_str();
+    while let Some(start) = rest.find("<link>") {
+        rest = &rest[start + "<link>".len()..];
+        let Some(end) = rest.find("</link>") else {
+            break;
+        };
+        urls.push(rest[..end].trim().trim_start_matches("<![CDATA[").trim_end_matches("]]>").to_string());
+        rest = &rest[end..];
+    }
+
+    urls.sort();
+    urls.dedup();
+    urls
+}
+
+///
+/// Replace the xml entities of a string
+///
+/// # Arguments
+///
+/// * `text` - The escaped text
+///
+/// # Returns
+///
+/// The unescaped text
+///
+#[cfg(feature = "feed-backends")]
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context, Error};
+use log::trace;
+use rand::seq::SliceRandom;
+use reqwest::header::{HeaderMap, CONTENT_TYPE, USER_AGENT};
+use serde::Deserialize;
+use serde_json::Value;
+use serenity::async_trait;
+
+use crate::BOT_USER_AGENT;
+
+use super::{Backend, DimensionFilter};
+
+/// Http method of a generic http backend
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    /// Send a GET request
+    #[default]
+    Get,
+    /// Send a POST request
+    Post
+}
+
+/// Struct to hold the settings of a generic http backend
+#[derive(Deserialize, Clone)]
+pub struct HttpBackendSettings {
+    /// Url of the api, '{endpoint}' is replaced with the requested endpoint
+    pub url: String,
+    /// Http method of the request
+    #[serde(default)]
+    pub method: HttpMethod,
+    /// Additional headers of the request, supporting '${NAME}' environment variables
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Json body of the request, '{endpoint}' is replaced with the requested endpoint
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Dotted path to the gif url(s) in the json response, e.g. 'results.url'
+    pub path: String,
+    /// Dotted path to the width(s) of the gif(s) in the json response, e.g. 'results.width'
+    #[serde(default)]
+    pub width_path: Option<String>,
+    /// Dotted path to the height(s) of the gif(s) in the json response, e.g. 'results.height'
+    #[serde(default)]
+    pub height_path: Option<String>,
+    /// Endpoints supported by the api
+    pub endpoints: Vec<String>
+}
+
+///
+/// Generic puppy-rs backend for fetching animated gifs off any json api
+///
+pub struct HttpBackend {
+    id: &'static str,
+    http: reqwest::Client,
+    headers: HeaderMap,
+    filter: DimensionFilter,
+    settings: HttpBackendSettings
+}
+
+impl HttpBackend {
+    pub fn new(id: &'static str, settings: HttpBackendSettings) -> Result<Self, Error> {
+        let headers = super::header_map(&settings.headers)?;
+        Ok(Self { id, http: reqwest::Client::new(), headers, filter: DimensionFilter::default(), settings })
+    }
+}
+
+impl HttpBackend {
+
+    ///
+    /// Extract the dimensions of the gifs in a json response
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The json response
+    /// * `path` - The dotted path to the dimensions, if configured
+    /// * `count` - The number of gifs in the response
+    ///
+    /// # Returns
+    ///
+    /// A dimension for every gif, all unknown if the path isn't configured or doesn't match every gif
+    ///
+    fn dimensions(&self, json: &Value, path: Option<&str>, count: usize) -> Vec<Option<u64>> {
+        let dimensions: Vec<u64> = path.map(|path| extract_numbers(json, path)).unwrap_or_default();
+        if dimensions.len() != count {
+            return vec![None; count];
+        }
+
+        dimensions.into_iter().map(Some).collect()
+    }
+
+}
+
+#[async_trait]
+impl Backend for HttpBackend {
+
+    fn set_headers(&mut self, headers: HeaderMap) {
+        self.headers.extend(headers);
+    }
+
+    fn set_http_client(&mut self, http: reqwest::Client) {
+        self.http = http;
+    }
+
+    fn set_dimension_filter(&mut self, filter: DimensionFilter) {
+        self.filter = filter;
+    }
+
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    async fn endpoints(&self) -> Result<Vec<String>, Error> {
+        Ok(self.settings.endpoints.clone())
+    }
+
+    async fn fetch(&self, endpoint: &str) -> Result<String, Error> {
+        // build request
+        let url = self.settings.url.replace("{endpoint}", endpoint);
+        let mut request = match self.settings.method {
+            HttpMethod::Get => self.http.get(&url),
+            HttpMethod::Post => self.http.post(&url)
+        }.header(USER_AGENT, BOT_USER_AGENT);
+        if let Some(body) = &self.settings.body {
+            request = request
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.replace("{endpoint}", endpoint));
+        }
+        request = request.headers(self.headers.clone());
+
+        // make request
+        trace!(target: "module/reaction/backend/http", "fetching gif from '{}'", url);
+        let response = request.send().await
+            .context("failed to make web request to api")?;
+        let status = response.status();
+        let body = response.text().await
+            .context("failed to get response text")?;
+
+        // check error
+        if !status.is_success() {
+            return Err(anyhow!("{} returned status code '{}':\n{}", self.id, status, body));
+        }
+
+        // parse response
+        trace!(target: "module/reaction/backend/http", "parsing response:\n{}", body);
+        let json: Value = serde_json::from_str(&body)
+            .context("unable to parse response as json")?;
+        let urls = extract(&json, &self.settings.path);
+        if urls.is_empty() {
+            return Err(anyhow!("path '{}' matched no url in response of {}", self.settings.path, self.id));
+        }
+
+        // leave out gifs with unwanted dimensions
+        let widths = self.dimensions(&json, self.settings.width_path.as_deref(), urls.len());
+        let heights = self.dimensions(&json, self.settings.height_path.as_deref(), urls.len());
+        let urls: Vec<&str> = urls.into_iter().enumerate()
+            .filter(|(i, _)| self.filter.allows(widths[*i], heights[*i]))
+            .map(|(_, url)| url)
+            .collect();
+        urls.choose(&mut rand::thread_rng())
+            .map(|url| url.to_string())
+            .ok_or_else(|| anyhow!("no gif in response of {} has the required dimensions", self.id))
+    }
+
+}
+
+///
+/// Extract all strings at a dotted path from a json value
+///
+/// Numeric segments index into arrays, other segments are applied to every element of an array.
+///
+/// # Arguments
+///
+/// * `value` - The json value
+/// * `path` - The dotted path, e.g. 'results.url' or 'results.0.url'
+///
+/// # Returns
+///
+/// The strings found at the path
+///
+pub(super) fn extract<'a>(value: &'a Value, path: &str) -> Vec<&'a str> {
+    lookup(value, path).into_iter().flat_map(|value| match value {
+        Value::String(string) => vec![string.as_str()],
+        Value::Array(array) => array.iter().filter_map(|element| element.as_str()).collect(),
+        _ => vec![]
+    }).collect()
+}
+
+///
+/// Extract all unsigned integers at a dotted path from a json value
+///
+/// # Arguments
+///
+/// * `value` - The json value
+/// * `path` - The dotted path, e.g. 'results.width'
+///
+/// # Returns
+///
+/// The integers found at the path
+///
+fn extract_numbers(value: &Value, path: &str) -> Vec<u64> {
+    lookup(value, path).into_iter().flat_map(|value| match value {
+        Value::Array(array) => array.iter().filter_map(|element| element.as_u64()).collect::<Vec<_>>(),
+        value => value.as_u64().into_iter().collect()
+    }).collect()
+}
+
+///
+/// Look up all values at a dotted path in a json value
+///
+/// Numeric segments index into arrays, other segments are applied to every element of an array.
+///
+/// # Arguments
+///
+/// * `value` - The json value
+/// * `path` - The dotted path
+///
+/// # Returns
+///
+/// The values found at the path
+///
+fn lookup<'a>(value: &'a Value, path: &str) -> Vec<&'a Value> {
+    let mut values = vec![value];
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        values = values.into_iter().flat_map(|value| match (value, segment.parse::<usize>()) {
+            (Value::Array(array), Ok(index)) => array.get(index).into_iter().collect::<Vec<_>>(),
+            (Value::Array(array), Err(_)) => array.iter().filter_map(|element| element.get(segment)).collect(),
+            (value, _) => value.get(segment).into_iter().collect()
+        }).collect();
+    }
+    values
+}
@@ -1,6 +1,6 @@
 use anyhow::{anyhow, Context, Error};
 use log::trace;
-use reqwest::header::USER_AGENT;
+use reqwest::header::{HeaderMap, USER_AGENT};
 use serenity::async_trait;
 
 use crate::BOT_USER_AGENT;
@@ -14,18 +14,27 @@ const API_URL: &str = "https://nekos.best/api/v2/";
 /// Simple and lightweight puppy-rs backend for fetching animated gifs off the nekos.best API
 ///
 pub struct NekosBest {
-    http: reqwest::Client
+    http: reqwest::Client,
+    headers: HeaderMap
 }
 
 impl NekosBest {
     pub fn new() -> Self {
-        Self { http: reqwest::Client::new() }
+        Self { http: reqwest::Client::new(), headers: HeaderMap::new() }
     }
 }
 
 #[async_trait]
 impl Backend for NekosBest {
 
+    fn set_headers(&mut self, headers: HeaderMap) {
+        self.headers = headers;
+    }
+
+    fn set_http_client(&mut self, http: reqwest::Client) {
+        self.http = http;
+    }
+
     fn id(&self) ->  &'static str {
         "nekos.best"
     }
@@ -36,7 +45,7 @@ impl Backend for NekosBest {
 
         // make request
         trace!(target: "module/reaction/backend/nekosbest", "fetching endpoints from '{}'", url);
-        let response = self.http.get(url).header(USER_AGENT, BOT_USER_AGENT).send().await
+        let response = self.http.get(url).header(USER_AGENT, BOT_USER_AGENT).headers(self.headers.clone()).send().await
             .context("failed to make web request to api")?;
         let status = response.status();
         let body = response.text().await
@@ -71,7 +80,7 @@ impl Backend for NekosBest {
 
         // make request
         trace!(target: "module/reaction/backend/nekosbest", "fetching gif from '{}'", url);
-        let response = self.http.get(url).header(USER_AGENT, BOT_USER_AGENT).send().await
+        let response = self.http.get(url).header(USER_AGENT, BOT_USER_AGENT).headers(self.headers.clone()).send().await
             .context("failed to make web request to api")?;
         let status = response.status();
         let body = response.text().await
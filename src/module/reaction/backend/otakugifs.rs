@@ -1,6 +1,6 @@
 use anyhow::{anyhow, Context, Error};
 use log::trace;
-use reqwest::header::USER_AGENT;
+use reqwest::header::{HeaderMap, USER_AGENT};
 use serenity::async_trait;
 
 use crate::BOT_USER_AGENT;
@@ -14,18 +14,27 @@ const API_URL: &str = "https://api.otakugifs.xyz/gif";
 /// Simple and lightweight puppy-rs backend for fetching animated gifs off the otakugifs.xyz API
 ///
 pub struct OtakuGifs {
-    http: reqwest::Client
+    http: reqwest::Client,
+    headers: HeaderMap
 }
 
 impl OtakuGifs {
     pub fn new() -> Self {
-        Self { http: reqwest::Client::new() }
+        Self { http: reqwest::Client::new(), headers: HeaderMap::new() }
     }
 }
 
 #[async_trait]
 impl Backend for OtakuGifs {
 
+    fn set_headers(&mut self, headers: HeaderMap) {
+        self.headers = headers;
+    }
+
+    fn set_http_client(&mut self, http: reqwest::Client) {
+        self.http = http;
+    }
+
     fn id(&self) ->  &'static str {
         "otakugifs.xyz"
     }
@@ -36,7 +45,7 @@ impl Backend for OtakuGifs {
 
         // make request
         trace!(target: "module/reaction/backend/otakugifs", "fetching endpoints from '{}'", url);
-        let response = self.http.get(url).header(USER_AGENT, BOT_USER_AGENT).send().await
+        let response = self.http.get(url).header(USER_AGENT, BOT_USER_AGENT).headers(self.headers.clone()).send().await
             .context("failed to make web request to api")?;
         let status = response.status();
         let body = response.text().await
@@ -76,7 +85,7 @@ impl Backend for OtakuGifs {
 
         // make request
         trace!(target: "module/reaction/backend/otakugifs", "fetching gif from '{}'", url);
-        let response = self.http.get(url).header(USER_AGENT, BOT_USER_AGENT).send().await
+        let response = self.http.get(url).header(USER_AGENT, BOT_USER_AGENT).headers(self.headers.clone()).send().await
             .context("failed to make web request to api")?;
         let status = response.status();
         let body = response.text().await
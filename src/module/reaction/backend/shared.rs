@@ -0,0 +1,186 @@
+use anyhow::anyhow;
+
+use crate::store::redis::{RedisClient, Reply, KEY_PREFIX};
+
+/// Seconds an instance may refresh an endpoint before another instance takes over
+const REFRESH_LOCK_SECONDS: &str = "30";
+
+///
+/// Gif cache shared between multiple bot instances through a redis server
+///
+/// Pools are stored as sets at 'puppy:cache:<backend>:<endpoint>', blocked gifs at 'puppy:cache:blocked'.
+///
+pub struct SharedCache {
+    /// Client of the redis server
+    client: RedisClient
+}
+
+impl SharedCache {
+
+    ///
+    /// Create a new shared cache
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The url of the redis server, e.g. 'redis://127.0.0.1:6379'
+    ///
+    /// # Returns
+    ///
+    /// The shared cache
+    ///
+    /// # Errors
+    ///
+    /// If the url is invalid
+    ///
+    pub fn new(url: &str) -> Result<Self, anyhow::Error> {
+        Ok(Self { client: RedisClient::new(url)? })
+    }
+
+    ///
+    /// Get all gifs of a shared pool
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend id
+    /// * `endpoint` - The endpoint
+    ///
+    /// # Returns
+    ///
+    /// The urls of all gifs in the pool
+    ///
+    /// # Errors
+    ///
+    /// If the redis server cannot be reached
+    ///
+    pub async fn pool(&self, backend: &str, endpoint: &str) -> Result<Vec<String>, anyhow::Error> {
+        strings(self.client.command(&["SMEMBERS", &pool_key(backend, endpoint)]).await?)
+    }
+
+    ///
+    /// Get a random gif of a shared pool
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend id
+    /// * `endpoint` - The endpoint
+    ///
+    /// # Returns
+    ///
+    /// The url of the gif or None if the pool is empty
+    ///
+    /// # Errors
+    ///
+    /// If the redis server cannot be reached
+    ///
+    pub async fn random(&self, backend: &str, endpoint: &str) -> Result<Option<String>, anyhow::Error> {
+        match self.client.command(&["SRANDMEMBER", &pool_key(backend, endpoint)]).await? {
+            Reply::String(url) => Ok(Some(url)),
+            _ => Ok(None)
+        }
+    }
+
+    ///
+    /// Add a gif to a shared pool
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend id
+    /// * `endpoint` - The endpoint
+    /// * `url` - The url of the gif
+    ///
+    /// # Errors
+    ///
+    /// If the redis server cannot be reached
+    ///
+    pub async fn add(&self, backend: &str, endpoint: &str, url: &str) -> Result<(), anyhow::Error> {
+        self.client.command(&["SADD", &pool_key(backend, endpoint), url]).await?;
+        Ok(())
+    }
+
+    ///
+    /// Remove a gif from shared pools and block it for all instances
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend id
+    /// * `endpoints` - The endpoints whose pools to remove the gif from
+    /// * `url` - The url of the gif
+    ///
+    /// # Errors
+    ///
+    /// If the redis server cannot be reached
+    ///
+    pub async fn block(&self, backend: &str, endpoints: &[String], url: &str) -> Result<(), anyhow::Error> {
+        self.client.command(&["SADD", &blocked_key(), url]).await?;
+        for endpoint in endpoints {
+            self.client.command(&["SREM", &pool_key(backend, endpoint), url]).await?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Get all blocked gifs
+    ///
+    /// # Returns
+    ///
+    /// The urls of all blocked gifs
+    ///
+    /// # Errors
+    ///
+    /// If the redis server cannot be reached
+    ///
+    pub async fn blocked(&self) -> Result<Vec<String>, anyhow::Error> {
+        strings(self.client.command(&["SMEMBERS", &blocked_key()]).await?)
+    }
+
+    ///
+    /// Try to become the instance refreshing an endpoint
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend id
+    /// * `endpoint` - The endpoint
+    ///
+    /// # Returns
+    ///
+    /// True if no other instance is refreshing the endpoint
+    ///
+    /// # Errors
+    ///
+    /// If the redis server cannot be reached
+    ///
+    pub async fn try_lock_refresh(&self, backend: &str, endpoint: &str) -> Result<bool, anyhow::Error> {
+        let key = format!("{}:refresh:{}:{}", KEY_PREFIX, backend, endpoint);
+        let reply = self.client.command(&["SET", &key, "1", "NX", "EX", REFRESH_LOCK_SECONDS]).await?;
+        Ok(matches!(reply, Reply::String(_)))
+    }
+
+}
+
+///
+/// Build the redis key of a shared pool
+///
+fn pool_key(backend: &str, endpoint: &str) -> String {
+    format!("{}:cache:{}:{}", KEY_PREFIX, backend, endpoint)
+}
+
+///
+/// Build the redis key of the blocked gifs
+///
+fn blocked_key() -> String {
+    format!("{}:cache:blocked", KEY_PREFIX)
+}
+
+///
+/// Convert an array reply to a list of strings
+///
+fn strings(reply: Reply) -> Result<Vec<String>, anyhow::Error> {
+    match reply {
+        Reply::Array(elements) => Ok(elements.into_iter().filter_map(|element| match element {
+            Reply::String(value) => Some(value),
+            _ => None
+        }).collect()),
+        Reply::Null => Ok(vec![]),
+        _ => Err(anyhow!("unexpected reply, expected array"))
+    }
+}
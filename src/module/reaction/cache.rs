@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+
+use crate::store::Store;
+
+use super::{backend::CachedGif, STORE_NAMESPACE};
+
+/// Store key the gif cache is persisted to as json
+pub(super) const CACHE_KEY: &str = "cache";
+
+/// Store key the gif cache is persisted to in the binary format
+const BINARY_CACHE_KEY: &str = "cache_binary";
+
+/// Magic bytes and version at the start of the binary format
+const MAGIC: &[u8; 4] = b"PGC1";
+
+/// Pools of cached gifs by backend id and endpoint, as exported by the backend manager
+pub type ExportedCache = HashMap<String, HashMap<String, Vec<CachedGif>>>;
+
+/// Format the gif cache is persisted in
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheFormat {
+    /// Readable json, slower to read and write for large caches
+    #[default]
+    Json,
+    /// Compact length-prefixed records
+    Binary
+}
+
+///
+/// Save the gif cache to the store
+///
+/// # Arguments
+///
+/// * `store` - The store
+/// * `format` - The format to persist the cache in
+/// * `cache` - The exported cache
+///
+/// # Errors
+///
+/// If the cache fails to serialize or the store cannot be written
+///
+pub async fn save(store: &dyn Store, format: CacheFormat, cache: &ExportedCache) -> Result<(), anyhow::Error> {
+    match format {
+        CacheFormat::Json => {
+            let serialized = serde_json::to_string(cache)
+                .context("failed to serialize cache")?;
+            store.set(STORE_NAMESPACE, CACHE_KEY, &serialized).await
+        },
+        CacheFormat::Binary => store.set_bytes(STORE_NAMESPACE, BINARY_CACHE_KEY, &encode(cache)).await
+    }
+}
+
+///
+/// Load the gif cache from the store
+///
+/// A cache persisted in the other format is loaded if none was persisted in the given one,
+/// so switching formats keeps the cache.
+///
+/// # Arguments
+///
+/// * `store` - The store
+/// * `format` - The format the cache is persisted in
+///
+/// # Returns
+///
+/// The persisted cache or None if no cache was persisted
+///
+/// # Errors
+///
+/// If the store cannot be read or the cache fails to parse
+///
+pub async fn load(store: &dyn Store, format: CacheFormat) -> Result<Option<ExportedCache>, anyhow::Error> {
+    let formats = match format {
+        CacheFormat::Json => [CacheFormat::Json, CacheFormat::Binary],
+        CacheFormat::Binary => [CacheFormat::Binary, CacheFormat::Json]
+    };
+    for format in formats {
+        let cache = match format {
+            CacheFormat::Json => store.get(STORE_NAMESPACE, CACHE_KEY).await?
+                .map(|cache| serde_json::from_str(&cache).context("failed to parse cache"))
+                .transpose()?,
+            CacheFormat::Binary => store.get_bytes(STORE_NAMESPACE, BINARY_CACHE_KEY).await?
+                .map(|cache| decode(&cache))
+                .transpose()?
+        };
+        if cache.is_some() {
+            return Ok(cache);
+        }
+    }
+    Ok(None)
+}
+
+///
+/// Encode a gif cache in the binary format
+///
+/// The format starts with the magic bytes and the number of pools, followed by one record per pool holding
+/// the backend id, the endpoint and the gifs. Strings are prefixed with their length and integers are little endian.
+///
+/// # Arguments
+///
+/// * `cache` - The exported cache
+///
+/// # Returns
+///
+/// The encoded cache
+///
+pub fn encode(cache: &ExportedCache) -> Vec<u8> {
+    fn put_str(data: &mut Vec<u8>, value: &str) {
+        data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        data.extend_from_slice(value.as_bytes());
+    }
+
+    let pools: Vec<_> = cache.iter()
+        .flat_map(|(backend, pools)| pools.iter().map(move |(endpoint, gifs)| (backend, endpoint, gifs)))
+        .collect();
+
+    let mut data = MAGIC.to_vec();
+    data.extend_from_slice(&(pools.len() as u32).to_le_bytes());
+    for (backend, endpoint, gifs) in pools {
+        put_str(&mut data, backend);
+        put_str(&mut data, endpoint);
+        data.extend_from_slice(&(gifs.len() as u32).to_le_bytes());
+        for gif in gifs {
+            data.extend_from_slice(&gif.added.to_le_bytes());
+            put_str(&mut data, &gif.url);
+        }
+    }
+    data
+}
+
+///
+/// Decode a gif cache from the binary format
+///
+/// # Arguments
+///
+/// * `data` - The encoded cache
+///
+/// # Returns
+///
+/// The cache
+///
+/// # Errors
+///
+/// If the data isn't in the binary format or is truncated
+///
+pub fn decode(data: &[u8]) -> Result<ExportedCache, anyhow::Error> {
+    let mut reader = Reader(data);
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(anyhow!("cache is not in the binary format"));
+    }
+
+    let mut cache = ExportedCache::new();
+    for _ in 0..reader.u32()? {
+        let backend = reader.string()?;
+        let endpoint = reader.string()?;
+        let count = reader.u32()?;
+
+        // the count is untrusted, so the pool only grows as gifs are read
+        let mut gifs = Vec::new();
+        for _ in 0..count {
+            let added = i64::from_le_bytes(reader.take(8)?.try_into()?);
+            gifs.push(CachedGif { url: reader.string()?, added });
+        }
+        cache.entry(backend).or_default().insert(endpoint, gifs);
+    }
+
+    if !reader.0.is_empty() {
+        return Err(anyhow!("cache has {} trailing bytes", reader.0.len()));
+    }
+    Ok(cache)
+}
+
+/// Reader over the remaining bytes of an encoded cache
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+
+    ///
+    /// Read a number of bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The number of bytes
+    ///
+    /// # Returns
+    ///
+    /// The bytes
+    ///
+    /// # Errors
+    ///
+    /// If fewer bytes are left
+    ///
+    fn take(&mut self, len: usize) -> Result<&'a [u8], anyhow::Error> {
+        if self.0.len() < len {
+            return Err(anyhow!("cache is truncated"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    ///
+    /// Read a little endian u32
+    ///
+    /// # Returns
+    ///
+    /// The integer
+    ///
+    /// # Errors
+    ///
+    /// If fewer than 4 bytes are left
+    ///
+    fn u32(&mut self) -> Result<u32, anyhow::Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    ///
+    /// Read a length-prefixed string
+    ///
+    /// # Returns
+    ///
+    /// The string
+    ///
+    /// # Errors
+    ///
+    /// If the string is truncated or not utf-8
+    ///
+    fn string(&mut self) -> Result<String, anyhow::Error> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .context("cache contains an invalid string")
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store;
+
+    use super::*;
+
+    fn sample() -> ExportedCache {
+        let gif = |url: &str, added| CachedGif { url: url.to_string(), added };
+        let mut cache = ExportedCache::new();
+        cache.entry("nekos.best".to_string()).or_default()
+            .insert("hug".to_string(), vec![gif("https://nekos.best/hug/1.gif", 1), gif("https://nekos.best/hug/2.gif", -2)]);
+        cache.entry("nekos.best".to_string()).or_default()
+            .insert("pat".to_string(), Vec::new());
+        cache.entry("otakugifs.xyz".to_string()).or_default()
+            .insert("kiss".to_string(), vec![gif("https://otakugifs.xyz/kiss/ü.gif", i64::MAX)]);
+        cache
+    }
+
+    /// Pools as sorted (backend, endpoint, gifs) tuples, as cached gifs can't be compared
+    type Pools = Vec<(String, String, Vec<(String, i64)>)>;
+
+    fn flatten(cache: &ExportedCache) -> Pools {
+        let mut pools: Vec<_> = cache.iter()
+            .flat_map(|(backend, pools)| pools.iter().map(move |(endpoint, gifs)| {
+                (backend.clone(), endpoint.clone(), gifs.iter().map(|gif| (gif.url.clone(), gif.added)).collect())
+            }))
+            .collect();
+        pools.sort();
+        pools
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let cache = sample();
+        let decoded = decode(&encode(&cache)).unwrap();
+        assert_eq!(flatten(&decoded), flatten(&cache));
+    }
+
+    #[test]
+    fn binary_is_smaller_than_json() {
+        let cache = sample();
+        assert!(encode(&cache).len() < serde_json::to_vec(&cache).unwrap().len());
+    }
+
+    #[test]
+    fn rejects_corrupt_binary() {
+        let encoded = encode(&sample());
+        assert!(decode(&encoded[..encoded.len() - 1]).is_err());
+        assert!(decode(b"JSON").is_err());
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(decode(&trailing).is_err());
+    }
+
+    #[tokio::test]
+    async fn store_round_trip() {
+        for format in [CacheFormat::Json, CacheFormat::Binary] {
+            let store = store::open(None, None).unwrap();
+            let store = &*store;
+            assert!(load(store, format).await.unwrap().is_none());
+
+            save(store, format, &sample()).await.unwrap();
+            assert_eq!(flatten(&load(store, format).await.unwrap().unwrap()), flatten(&sample()));
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_the_other_format() {
+        let store = store::open(None, None).unwrap();
+        let store = &*store;
+        save(store, CacheFormat::Json, &sample()).await.unwrap();
+        assert_eq!(flatten(&load(store, CacheFormat::Binary).await.unwrap().unwrap()), flatten(&sample()));
+    }
+
+    #[tokio::test]
+    async fn hex_fallback_round_trip() {
+        // stores without native binary values keep them hex-encoded
+        struct StringStore(std::sync::Arc<dyn Store>);
+
+        #[serenity::async_trait]
+        impl Store for StringStore {
+            async fn get(&self, namespace: &str, key: &str) -> Result<Option<String>, anyhow::Error> {
+                self.0.get(namespace, key).await
+            }
+            async fn set(&self, namespace: &str, key: &str, value: &str) -> Result<(), anyhow::Error> {
+                self.0.set(namespace, key, value).await
+            }
+            async fn increment(&self, namespace: &str, key: &str, by: i64) -> Result<i64, anyhow::Error> {
+                self.0.increment(namespace, key, by).await
+            }
+        }
+
+        let store = StringStore(store::open(None, None).unwrap());
+        save(&store, CacheFormat::Binary, &sample()).await.unwrap();
+        assert_eq!(flatten(&load(&store, CacheFormat::Binary).await.unwrap().unwrap()), flatten(&sample()));
+    }
+}
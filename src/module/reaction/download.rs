@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use reqwest::header::USER_AGENT;
+
+use crate::BOT_USER_AGENT;
+
+/// Default maximum size of a downloaded gif in bytes, matching the upload limit of discord
+pub const MAX_DOWNLOAD_SIZE: usize = 8 * 1024 * 1024;
+
+/// Time a download may take before it fails and the gif is sent as a spoiler link instead
+pub const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+///
+/// Download a gif so it can be uploaded as an attachment
+///
+/// The gif is streamed and the download is aborted as soon as it exceeds the maximum size,
+/// so responses lying about or leaving out their length can't exhaust the memory.
+///
+/// # Arguments
+///
+/// * `http` - The http client
+/// * `url` - The url of the gif
+/// * `max_size` - The maximum size of the gif in bytes
+///
+/// # Returns
+///
+/// The contents of the gif
+///
+/// # Errors
+///
+/// If the request fails or the gif is larger than the maximum download size
+///
+pub async fn download(http: &reqwest::Client, url: &str, max_size: usize) -> Result<Vec<u8>, anyhow::Error> {
+    let mut response = http.get(url).header(USER_AGENT, BOT_USER_AGENT).send().await
+        .context("failed to download gif")?
+        .error_for_status()
+        .context("failed to download gif")?;
+
+    if response.content_length().is_some_and(|len| len > max_size as u64) {
+        return Err(anyhow!("gif is larger than {} bytes", max_size));
+    }
+
+    // read the gif chunk by chunk
+    let mut contents = Vec::new();
+    while let Some(chunk) = response.chunk().await.context("failed to read gif")? {
+        if contents.len() + chunk.len() > max_size {
+            return Err(anyhow!("gif is larger than {} bytes", max_size));
+        }
+        contents.extend_from_slice(&chunk);
+    }
+
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use super::*;
+
+    /// Serve a single http response on a local port
+    async fn serve(content_length: bool, body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await;
+
+            // without a length the body is sent until the connection closes
+            let header = if content_length {
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len())
+            } else {
+                "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n".to_string()
+            };
+            let _ = stream.write_all(header.as_bytes()).await;
+            for chunk in body.chunks(1024) {
+                if stream.write_all(chunk).await.is_err() {
+                    return;
+                }
+            }
+        });
+        format!("http://{}/hug.gif", address)
+    }
+
+    #[tokio::test]
+    async fn downloads_gifs_within_the_limit() {
+        let url = serve(true, vec![1; 4096]).await;
+        assert_eq!(download(&reqwest::Client::new(), &url, 4096).await.unwrap(), vec![1; 4096]);
+    }
+
+    #[tokio::test]
+    async fn aborts_oversized_downloads() {
+        // a declared length above the limit is rejected before reading the body
+        let url = serve(true, vec![1; 64 * 1024]).await;
+        let error = download(&reqwest::Client::new(), &url, 4096).await.unwrap_err();
+        assert_eq!(error.to_string(), "gif is larger than 4096 bytes");
+
+        // a body without a length is aborted once it grows past the limit
+        let url = serve(false, vec![1; 64 * 1024]).await;
+        let error = download(&reqwest::Client::new(), &url, 4096).await.unwrap_err();
+        assert_eq!(error.to_string(), "gif is larger than 4096 bytes");
+    }
+}
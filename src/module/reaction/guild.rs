@@ -0,0 +1,87 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+/// Reactions allowed and denied in a guild
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct GuildReactions {
+    /// Reactions allowed in the guild, all reactions if empty
+    #[serde(default)]
+    pub allow: BTreeSet<String>,
+    /// Reactions denied in the guild
+    #[serde(default)]
+    pub deny: BTreeSet<String>
+}
+
+impl GuildReactions {
+
+    ///
+    /// Check whether a reaction can be used in the guild
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the reaction
+    ///
+    /// # Returns
+    ///
+    /// Whether the reaction is allowed and not denied
+    ///
+    pub fn allows(&self, name: &str) -> bool {
+        (self.allow.is_empty() || self.allow.contains(name)) && !self.deny.contains(name)
+    }
+
+    ///
+    /// Allow a reaction in the guild, removing it from the deny list
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the reaction
+    ///
+    pub fn allow(&mut self, name: &str) {
+        self.deny.remove(name);
+        self.allow.insert(name.to_string());
+    }
+
+    ///
+    /// Deny a reaction in the guild, removing it from the allow list
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the reaction
+    ///
+    pub fn deny(&mut self, name: &str) {
+        self.allow.remove(name);
+        self.deny.insert(name.to_string());
+    }
+
+    ///
+    /// Check whether the guild restricts no reactions
+    ///
+    /// # Returns
+    ///
+    /// Whether both lists are empty
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+}
+
+///
+/// Format a list of reactions for a message
+///
+/// # Arguments
+///
+/// * `reactions` - The names of the reactions
+///
+/// # Returns
+///
+/// The names joined by commas, or 'none'
+///
+pub fn format_list(reactions: &BTreeSet<String>) -> String {
+    if reactions.is_empty() {
+        return "none".to_string();
+    }
+
+    reactions.iter().map(|name| format!("`{}`", name)).collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,137 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serenity::all::{CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption};
+
+use crate::module::{respond_ephemeral, CommandContexts};
+
+use super::{ReactionModule, PRONOUNS_KEY, STORE_NAMESPACE};
+
+/// Name of the pronouns command
+pub const PRONOUNS_COMMAND: &str = "pronouns";
+
+/// Names of the pronoun placeholders of responses
+pub const PRONOUN_PLACEHOLDERS: [&str; 3] = ["they", "them", "their"];
+
+/// Pronouns responses refer to a user with
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Pronouns {
+    /// they/them/their
+    #[default]
+    They,
+    /// she/her/her
+    She,
+    /// he/him/his
+    He,
+    /// it/it/its
+    It
+}
+
+impl Pronouns {
+
+    /// Every pronoun option with the value of its choice
+    const ALL: [(Pronouns, &'static str); 4] = [(Self::They, "they"), (Self::She, "she"), (Self::He, "he"), (Self::It, "it")];
+
+    ///
+    /// Get the forms of the pronouns
+    ///
+    /// # Returns
+    ///
+    /// The subject, object and possessive forms, in the order of the placeholders
+    ///
+    pub fn forms(self) -> [&'static str; 3] {
+        match self {
+            Self::They => ["they", "them", "their"],
+            Self::She => ["she", "her", "her"],
+            Self::He => ["he", "him", "his"],
+            Self::It => ["it", "it", "its"]
+        }
+    }
+
+    ///
+    /// Get the placeholders of the pronouns for rendering a response
+    ///
+    /// # Returns
+    ///
+    /// The placeholder names and their values
+    ///
+    pub fn placeholders(self) -> [(&'static str, &'static str); 3] {
+        let forms = self.forms();
+        [(PRONOUN_PLACEHOLDERS[0], forms[0]), (PRONOUN_PLACEHOLDERS[1], forms[1]), (PRONOUN_PLACEHOLDERS[2], forms[2])]
+    }
+
+    ///
+    /// Get a readable name of the pronouns
+    ///
+    /// # Returns
+    ///
+    /// The subject and object forms, like 'she/her'
+    ///
+    fn label(self) -> String {
+        let forms = self.forms();
+        match self {
+            Self::It => format!("{}/{}", forms[0], forms[2]),
+            _ => format!("{}/{}", forms[0], forms[1])
+        }
+    }
+
+}
+
+///
+/// Create the pronouns command
+///
+/// # Arguments
+///
+/// * `contexts` - Where the command can be installed and used
+///
+/// # Returns
+///
+/// The command creation request
+///
+pub fn create_command(contexts: &CommandContexts) -> CreateCommand {
+    let option = Pronouns::ALL.iter().fold(
+        CreateCommandOption::new(CommandOptionType::String, "pronouns", "The pronouns reactions use for you, or none to show them"),
+        |option, (pronouns, value)| option.add_string_choice(pronouns.label(), *value)
+    );
+    contexts.apply(CreateCommand::new(PRONOUNS_COMMAND))
+        .description("Set the pronouns reactions use for you.")
+        .add_option(option)
+}
+
+impl ReactionModule {
+
+    ///
+    /// Show or set the pronouns of the user
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    pub(super) async fn handle_pronouns(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        let choice = cmd.data.options.first().and_then(|opt| opt.value.as_str());
+        let Some(choice) = choice else {
+            let pronouns = self.pronouns.get(&cmd.user.id).copied().unwrap_or_default();
+            return respond_ephemeral(&ctx, &cmd, format!("Reactions use **{}** for you.", pronouns.label())).await;
+        };
+        let pronouns = Pronouns::ALL.iter().find(|(_, value)| *value == choice)
+            .map(|(pronouns, _)| *pronouns)
+            .context("unknown pronouns")?;
+
+        // users with the default pronouns aren't stored
+        if pronouns == Pronouns::default() {
+            self.pronouns.remove(&cmd.user.id);
+        } else {
+            self.pronouns.insert(cmd.user.id, pronouns);
+        }
+
+        // persist the pronouns of all users
+        let serialized = serde_json::to_string(&self.pronouns)
+            .context("failed to serialize pronouns")?;
+        self.store.set(STORE_NAMESPACE, PRONOUNS_KEY, &serialized).await
+            .context("failed to save pronouns")?;
+
+        respond_ephemeral(&ctx, &cmd, format!("Reactions now use **{}** for you.", pronouns.label())).await
+    }
+
+}
@@ -0,0 +1,253 @@
+use serde::Deserialize;
+
+use super::pronouns::Pronouns;
+
+/// Hosts of discord invite links removed by strict sanitization
+const INVITE_HOSTS: &[&str] = &["discord.gg/", "discord.com/invite/", "discordapp.com/invite/"];
+
+/// Word replacing names that can't be resolved
+pub const NAME_FALLBACK: &str = "someone";
+
+/// How rendered responses are sanitized
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Sanitization {
+    /// Send responses as they are rendered
+    Off,
+    /// Neutralize @everyone, @here and role mentions
+    #[default]
+    Mentions,
+    /// Also remove invite links and invisible or text direction changing characters
+    Strict
+}
+
+///
+/// Render a response template by replacing its placeholders and sanitize the result
+///
+/// The template is substituted in a single pass, so placeholders inside the values, like a display
+/// name of `{target}`, are copied in literally instead of being replaced again.
+///
+/// # Arguments
+///
+/// * `template` - The response template containing placeholders like `{user}`
+/// * `placeholders` - A list of placeholder names and their values
+/// * `sanitization` - How the rendered response is sanitized, covering both the template and the values
+///
+/// # Returns
+///
+/// The rendered response, with name placeholders like `{target.name}` that weren't given replaced by a neutral word
+/// and pronoun placeholders like `{them}` that weren't given replaced by they/them
+///
+pub fn render_response(template: &str, placeholders: &[(&str, &str)], sanitization: Sanitization) -> String {
+    let mut response = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let value = rest[start + 1..].split_once('}')
+            .and_then(|(inner, _)| resolve_placeholder(inner, placeholders).map(|value| (inner.len(), value)));
+        match value {
+            Some((len, value)) => {
+                response.push_str(&rest[..start]);
+                response.push_str(value);
+                rest = &rest[start + len + 2..];
+            },
+            None => {
+                response.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    response.push_str(rest);
+    sanitize(&response, sanitization)
+}
+
+///
+/// Resolve a placeholder of a template
+///
+/// # Arguments
+///
+/// * `name` - The name of the placeholder, without braces
+/// * `placeholders` - A list of placeholder names and their values
+///
+/// # Returns
+///
+/// The given value, the fallback of an unresolved name or pronoun placeholder, or None if it isn't a placeholder
+///
+fn resolve_placeholder<'a>(name: &str, placeholders: &[(&str, &'a str)]) -> Option<&'a str> {
+    if let Some((_, value)) = placeholders.iter().find(|(placeholder, _)| *placeholder == name) {
+        return Some(value);
+    }
+    if let Some((_, value)) = Pronouns::default().placeholders().into_iter().find(|(placeholder, _)| *placeholder == name) {
+        return Some(value);
+    }
+
+    // name placeholders of the form {<word>.name} fall back to a neutral word
+    name.strip_suffix(".name")
+        .filter(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        .map(|_| NAME_FALLBACK)
+}
+
+///
+/// Sanitize a rendered response
+///
+/// # Arguments
+///
+/// * `response` - The rendered response
+/// * `sanitization` - How the response is sanitized
+///
+/// # Returns
+///
+/// The sanitized response
+///
+fn sanitize(response: &str, sanitization: Sanitization) -> String {
+    if sanitization == Sanitization::Off {
+        return response.to_string();
+    }
+
+    // strip invisible characters first, as they are used to neutralize mentions
+    let mut response = response.to_string();
+    if sanitization == Sanitization::Strict {
+        response.retain(|c| !is_invisible(c));
+        response = remove_invites(&response);
+    }
+
+    response.replace("@everyone", "@\u{200B}everyone")
+        .replace("@here", "@\u{200B}here")
+        .replace("<@&", "<@\u{200B}&")
+}
+
+///
+/// Check whether a character is invisible or changes the direction of the text
+///
+/// # Arguments
+///
+/// * `c` - The character
+///
+/// # Returns
+///
+/// True for zero width characters and bidirectional formatting characters
+///
+fn is_invisible(c: char) -> bool {
+    matches!(c, '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2069}' | '\u{FEFF}')
+}
+
+///
+/// Replace discord invite links in a response
+///
+/// # Arguments
+///
+/// * `response` - The response
+///
+/// # Returns
+///
+/// The response with every invite link replaced by a note
+///
+fn remove_invites(response: &str) -> String {
+    let mut result = String::with_capacity(response.len());
+    let mut rest = response;
+    loop {
+        // ascii lowercase keeps the byte offsets intact
+        let lower = rest.to_ascii_lowercase();
+        let Some(start) = INVITE_HOSTS.iter().filter_map(|host| lower.find(host)).min() else {
+            result.push_str(rest);
+            return result;
+        };
+
+        // extend the link backwards over its scheme and forwards over its code
+        let start = rest[..start].rfind(|c: char| c.is_ascii_whitespace() || matches!(c, '<' | '(' | '[')).map_or(0, |i| i + 1);
+        let end = rest[start..].find(|c: char| c.is_ascii_whitespace() || matches!(c, '>' | ')' | ']')).map_or(rest.len(), |i| start + i);
+        result.push_str(&rest[..start]);
+        result.push_str("[invite removed]");
+        rest = &rest[end..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neutralizes_mass_and_role_mentions() {
+        let response = render_response("{user} hugs @everyone", &[("user", "@here <@&123>")], Sanitization::Mentions);
+        assert_eq!(response, "@\u{200B}here <@\u{200B}&123> hugs @\u{200B}everyone");
+    }
+
+    #[test]
+    fn keeps_mentions_without_sanitization() {
+        let response = render_response("{user} hugs @everyone", &[("user", "<@&123>")], Sanitization::Off);
+        assert_eq!(response, "<@&123> hugs @everyone");
+    }
+
+    #[test]
+    fn removes_invites_in_strict_mode() {
+        let response = render_response("{user} hugs you", &[("user", "join https://discord.gg/abc or <discord.com/invite/def>")], Sanitization::Strict);
+        assert_eq!(response, "join [invite removed] or <[invite removed]> hugs you");
+    }
+
+    #[test]
+    fn keeps_invites_outside_strict_mode() {
+        let response = render_response("{user}", &[("user", "discord.gg/abc")], Sanitization::Mentions);
+        assert_eq!(response, "discord.gg/abc");
+    }
+
+    #[test]
+    fn strips_invisible_characters_in_strict_mode() {
+        let response = render_response("{user} hugs you", &[("user", "e\u{200B}vil\u{202E}eman\u{2066}\u{FEFF}")], Sanitization::Strict);
+        assert_eq!(response, "evileman hugs you");
+    }
+
+    #[test]
+    fn strict_mode_doesnt_reveal_hidden_mentions() {
+        // a zero width space inside @everyone must not turn into a working mention once stripped
+        let response = render_response("{user}", &[("user", "@\u{200B}everyone")], Sanitization::Strict);
+        assert_eq!(response, "@\u{200B}everyone");
+    }
+
+    #[test]
+    fn copies_hostile_display_names_literally() {
+        let placeholders = [("user.name", "{target} {them} {x.name}"), ("target", "<@2>")];
+        let response = render_response("{user.name} hugs {target}", &placeholders, Sanitization::Mentions);
+        assert_eq!(response, "{target} {them} {x.name} hugs <@2>");
+    }
+
+    #[test]
+    fn sanitizes_hostile_display_names() {
+        let response = render_response("{user.name} pats {target.name}", &[("user.name", "@everyone"), ("target.name", "<@&1>")], Sanitization::Mentions);
+        assert_eq!(response, "@\u{200B}everyone pats <@\u{200B}&1>");
+    }
+
+    #[test]
+    fn replaces_unknown_name_placeholders() {
+        assert_eq!(render_response("{user} hugs {foo.name}", &[("user", "<@1>")], Sanitization::Off), "<@1> hugs someone");
+        assert_eq!(render_response("{target.name} waves", &[], Sanitization::Off), "someone waves");
+    }
+
+    #[test]
+    fn keeps_invalid_placeholders() {
+        assert_eq!(render_response("{.name} {a b.name} {foo}", &[], Sanitization::Off), "{.name} {a b.name} {foo}");
+    }
+
+    #[test]
+    fn keeps_unclosed_braces() {
+        assert_eq!(render_response("{user} hugs {target.name", &[("user", "<@1>")], Sanitization::Off), "<@1> hugs {target.name");
+        assert_eq!(render_response("{{user}}", &[("user", "<@1>")], Sanitization::Off), "{<@1>}");
+    }
+
+    #[test]
+    fn prefers_given_names() {
+        let placeholders = [("user.name", "Alice"), ("target.name", "Bob")];
+        assert_eq!(render_response("{user.name} hugs {target.name}", &placeholders, Sanitization::Off), "Alice hugs Bob");
+    }
+
+    #[test]
+    fn falls_back_only_in_the_template() {
+        let placeholders = [("user.name", "{target.name}")];
+        assert_eq!(render_response("{user.name} hugs {target.name}", &placeholders, Sanitization::Off), "{target.name} hugs someone");
+    }
+
+    #[test]
+    fn falls_back_to_they_them() {
+        assert_eq!(render_response("{they} pat {them} on {their} head", &[], Sanitization::Off), "they pat them on their head");
+        let placeholders = Pronouns::She.placeholders();
+        assert_eq!(render_response("{they} pat {them} on {their} head", &placeholders, Sanitization::Off), "she pat her on her head");
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context};
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+
+use super::{Reaction, ResponseProfile};
+
+/// Alternate backends and responses of reactions during a date range
+#[derive(Deserialize, Clone)]
+pub struct SeasonalTheme {
+    /// Name of the theme
+    pub name: String,
+    /// First day of the theme as 'MM-DD'
+    pub start: String,
+    /// Last day of the theme as 'MM-DD', before the start if the theme spans the new year
+    pub end: String,
+    /// Overlays by reaction name
+    pub reactions: HashMap<String, SeasonalOverlay>
+}
+
+/// Backends and responses replacing those of a reaction during a theme
+#[derive(Deserialize, Clone)]
+pub struct SeasonalOverlay {
+    /// List of backends to fetch the reaction from
+    #[serde(default)]
+    pub backends: Option<Vec<String>>,
+    /// List of backends to fetch the rare reaction from
+    #[serde(default)]
+    pub rare_backends: Option<Vec<String>>,
+    /// Responses replacing those of the reaction
+    #[serde(flatten)]
+    pub responses: ResponseProfile
+}
+
+impl SeasonalOverlay {
+
+    ///
+    /// Replace the backends and responses of a reaction with those of the overlay
+    ///
+    /// # Arguments
+    ///
+    /// * `reaction` - The reaction, lists missing in the overlay are kept
+    ///
+    pub fn apply(&self, reaction: &mut Reaction) {
+        if let Some(backends) = &self.backends {
+            reaction.backends.clone_from(backends);
+        }
+        if let Some(backends) = &self.rare_backends {
+            reaction.rare_backends.clone_from(backends);
+        }
+        reaction.apply_profile(&self.responses);
+    }
+
+}
+
+impl SeasonalTheme {
+
+    ///
+    /// Validate the date range of the theme
+    ///
+    /// # Errors
+    ///
+    /// If the start or end is not a valid day of the year
+    ///
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        parse_day(&self.start)
+            .with_context(|| format!("invalid start of seasonal theme '{}'", self.name))?;
+        parse_day(&self.end)
+            .with_context(|| format!("invalid end of seasonal theme '{}'", self.name))?;
+        Ok(())
+    }
+
+    ///
+    /// Check whether a date falls into the theme
+    ///
+    /// # Arguments
+    ///
+    /// * `date` - The date
+    ///
+    /// # Returns
+    ///
+    /// Whether the date is between the start and end, both inclusive
+    ///
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        let (Ok(start), Ok(end)) = (parse_day(&self.start), parse_day(&self.end)) else {
+            return false;
+        };
+
+        let day = (date.month(), date.day());
+        if start <= end {
+            start <= day && day <= end
+        } else {
+            day >= start || day <= end
+        }
+    }
+
+}
+
+///
+/// Find the theme a date falls into
+///
+/// # Arguments
+///
+/// * `themes` - The seasonal themes
+/// * `date` - The date
+///
+/// # Returns
+///
+/// The first theme containing the date, if any
+///
+pub fn active_theme(themes: &[SeasonalTheme], date: NaiveDate) -> Option<&SeasonalTheme> {
+    themes.iter().find(|theme| theme.contains(date))
+}
+
+///
+/// Parse a day of the year
+///
+/// # Arguments
+///
+/// * `day` - The day as 'MM-DD'
+///
+/// # Returns
+///
+/// The month and day
+///
+/// # Errors
+///
+/// If the day is not in the format 'MM-DD' or doesn't exist
+///
+fn parse_day(day: &str) -> Result<(u32, u32), anyhow::Error> {
+    // use a leap year to allow the 29th of february
+    let date = NaiveDate::parse_from_str(&format!("2000-{}", day), "%Y-%m-%d")
+        .map_err(|_| anyhow!("'{}' is not a day in the format 'MM-DD'", day))?;
+    Ok((date.month(), date.day()))
+}
@@ -0,0 +1,911 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context};
+use log::{debug, info, warn};
+use serenity::all::{CommandDataOption, CommandDataOptionValue, CommandInteraction, CommandOptionType, ComponentInteraction, ComponentInteractionDataKind, CreateActionRow, CreateAllowedMentions, CreateButton, CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption};
+
+use crate::module::{pick_random, pick_weighted, respond_ephemeral, CommandContexts};
+
+use super::{guild::{self, GuildReactions}, pick_response, proxied, render::{render_response, NAME_FALLBACK}, target_options, Invocation, RandomSelection, Reaction, ReactionModule, FAVORITES_KEY, GUILDS_KEY, STORE_NAMESPACE};
+
+/// Name of the utility command
+pub const UTILITY_COMMAND: &str = "reactions";
+
+/// Prefix of the custom ids of all components created by the utility command
+pub const COMPONENT_PREFIX: &str = "reactions:";
+
+/// Maximum number of options in a select menu
+const MENU_PAGE_SIZE: usize = 25;
+
+/// Number of reactions on a page of the reaction list
+const LIST_PAGE_SIZE: usize = 15;
+
+/// Maximum number of favorite reactions of a user
+const MAX_FAVORITES: usize = 25;
+
+/// Number of pools listed in the cache status
+const CACHE_STATUS_LIMIT: usize = 20;
+
+/// Maximum number of reactions listed by a search
+const FIND_LIMIT: usize = 10;
+
+/// Time after which the buttons of a reaction list stop working
+const LIST_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+///
+/// Create the utility command
+///
+/// # Arguments
+///
+/// * `contexts` - Where the command can be installed and used
+/// * `max_targets` - The maximum number of targets of a reaction
+/// * `anonymous` - Whether reactions can be sent anonymously
+///
+/// # Returns
+///
+/// The command creation request
+///
+pub fn create_command(contexts: &CommandContexts, max_targets: usize, anonymous: bool) -> CreateCommand {
+    contexts.apply(CreateCommand::new(UTILITY_COMMAND))
+        .description("Utilities for reactions.")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "report", "Report the last gif in this channel as broken.")
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "menu", "Pick a reaction from a menu.")
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "list", "List all reactions.")
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "find", "Search reactions by keyword.")
+                .add_sub_option(CreateCommandOption::new(CommandOptionType::String, "keyword", "Part of the name or description of a reaction").required(true))
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "sample", "Show yourself a random gif of a reaction.")
+                .add_sub_option(CreateCommandOption::new(CommandOptionType::String, "name", "The reaction to show a gif of").required(true))
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "undo", "Delete the last reaction you sent in this channel.")
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "favorite", "Add a reaction to your favorites or remove it again.")
+                .add_sub_option(CreateCommandOption::new(CommandOptionType::String, "name", "The reaction to add or remove").required(true))
+        )
+        .add_option(
+            target_options(max_targets, anonymous).into_iter().fold(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "fav", "Perform one of your favorite reactions."),
+                |subcommand, option| subcommand.add_sub_option(option)
+            )
+        )
+        .add_option(
+            target_options(max_targets, anonymous).into_iter().fold(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "random", "Perform a random reaction."),
+                |subcommand, option| subcommand.add_sub_option(option)
+            )
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "preview", "Preview the responses of a reaction (owners only).")
+                .add_sub_option(CreateCommandOption::new(CommandOptionType::String, "name", "The reaction to preview").required(true))
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "debug", "Show the internal state of a reaction (owners only).")
+                .add_sub_option(CreateCommandOption::new(CommandOptionType::String, "name", "The reaction to inspect").required(true))
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommandGroup, "cache", "Inspect the gif cache (owners only).")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::SubCommand, "status", "Show the pools that were refreshed the longest time ago.")
+                )
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommandGroup, "server", "Manage the reactions of this server (admins only).")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::SubCommand, "allow", "Allow a reaction, limiting the server to allowed reactions.")
+                        .add_sub_option(CreateCommandOption::new(CommandOptionType::String, "name", "The reaction to allow").required(true))
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::SubCommand, "deny", "Disable a reaction in this server.")
+                        .add_sub_option(CreateCommandOption::new(CommandOptionType::String, "name", "The reaction to disable").required(true))
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::SubCommand, "reset", "Allow all reactions in this server again.")
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::SubCommand, "show", "Show the allowed and disabled reactions of this server.")
+                )
+        )
+}
+
+impl ReactionModule {
+
+    ///
+    /// Handle the utility command
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    pub(super) async fn handle_utility(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        let subcommand = cmd.data.options.first()
+            .context("no subcommand")?;
+
+        match subcommand.name.as_str() {
+            "report" => self.report(ctx, cmd).await,
+            "menu" => {
+                let (content, components) = self.menu_page(0);
+                cmd.create_response(&ctx.http, CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(components)
+                        .ephemeral(true)
+                )).await.context("failed to send response")
+            },
+            "list" => {
+                cmd.create_response(&ctx.http, CreateInteractionResponse::Message(
+                    self.list_page(0).into()
+                )).await.context("failed to send response")?;
+
+                // remember the page of the list
+                let message = cmd.get_response(&ctx.http).await
+                    .context("failed to get response")?;
+                self.list_pages.retain(|_, (_, created)| created.elapsed() < LIST_TIMEOUT);
+                self.list_pages.insert(message.id, (0, Instant::now()));
+                Ok(())
+            },
+            "find" => self.find(ctx, cmd).await,
+            "sample" => self.sample(ctx, cmd).await,
+            "undo" => self.undo(ctx, cmd).await,
+            "favorite" => self.toggle_favorite(ctx, cmd).await,
+            "fav" => self.perform_favorite(ctx, cmd).await,
+            "random" => self.perform_random(ctx, cmd).await,
+            "preview" => self.preview(ctx, cmd).await,
+            "debug" => self.debug(ctx, cmd).await,
+            "cache" => self.cache_status(ctx, cmd).await,
+            "server" => self.manage_guild(ctx, cmd).await,
+            _ => Err(anyhow!("unknown subcommand"))
+        }
+    }
+
+    ///
+    /// Search the reactions usable here by keyword and list the best matches with their commands
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    async fn find(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        let keyword = subcommand_options(&cmd).first().and_then(|opt| opt.value.as_str())
+            .context("no keyword")?
+            .trim()
+            .to_lowercase();
+        if keyword.is_empty() {
+            return respond_ephemeral(&ctx, &cmd, "Please enter a keyword to search for!").await;
+        }
+
+        // rank the matching reactions, best matches first
+        let mut matches = self.reactions.iter()
+            .filter(|r| self.guild_allows(cmd.guild_id, &r.name))
+            .filter_map(|r| match_rank(r, &keyword).map(|rank| (rank, r)))
+            .collect::<Vec<_>>();
+        matches.sort_by(|(a, ra), (b, rb)| a.cmp(b).then_with(|| ra.name.cmp(&rb.name)));
+        if matches.is_empty() {
+            return respond_ephemeral(&ctx, &cmd, format!("No reaction matches '{}'!", keyword)).await;
+        }
+
+        // list the matches with the command to use them
+        let groups = super::command_groups(&self.reactions);
+        let description = matches.iter()
+            .take(FIND_LIMIT)
+            .map(|(_, r)| {
+                let group = groups.iter().find(|(_, batch)| batch.iter().any(|member| member.name == r.name))
+                    .map_or(super::DEFAULT_GROUP, |(name, _)| name.as_str());
+                let alias = if r.alias { format!(" or /{}", r.name) } else { String::new() };
+                format!("**{}** - {}\n-# /{} {}{}", r.name, r.description, group, r.name, alias)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let embed = CreateEmbed::new()
+            .title(format!("Reactions matching '{}'", keyword))
+            .description(description)
+            .footer(CreateEmbedFooter::new(format!("Showing {} of {} matches", matches.len().min(FIND_LIMIT), matches.len())))
+            .color(crate::color::rand());
+
+        cmd.create_response(&ctx.http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .ephemeral(true)
+        )).await.context("failed to send response")
+    }
+
+    ///
+    /// Delete the last reaction the user sent in the channel
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    async fn undo(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        if self.settings.undo_window == 0 {
+            return respond_ephemeral(&ctx, &cmd, "Undoing reactions is disabled!").await;
+        }
+
+        let Some((token, sent)) = self.last_sent.remove(&(cmd.user.id, cmd.channel_id)) else {
+            return respond_ephemeral(&ctx, &cmd, "You haven't sent a reaction in this channel recently!").await;
+        };
+        if sent.elapsed() > Duration::from_secs(self.settings.undo_window) {
+            let note = format!("Reactions can only be undone within {} seconds!", self.settings.undo_window);
+            return respond_ephemeral(&ctx, &cmd, note).await;
+        }
+
+        // delete the response through its interaction, which works without channel permissions
+        let content = match ctx.http.delete_original_interaction_response(&token).await {
+            Ok(()) => {
+                info!(target: "module/reaction", "user @{} undid their last reaction in channel {}", cmd.user.name, cmd.channel_id);
+                "Deleted your last reaction."
+            },
+            Err(e) => {
+                warn!(target: "module/reaction", "failed to undo reaction of @{}: {:?}", cmd.user.name, e);
+                "Your last reaction couldn't be deleted, it may already be gone."
+            }
+        };
+
+        respond_ephemeral(&ctx, &cmd, content).await
+    }
+
+    ///
+    /// Add a reaction to the favorites of the user or remove it again
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    async fn toggle_favorite(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        let name = subcommand_options(&cmd).first().and_then(|opt| opt.value.as_str())
+            .context("no reaction name")?;
+        if !self.reactions.iter().any(|r| r.name == name) {
+            return respond_ephemeral(&ctx, &cmd, "There is no reaction with this name!").await;
+        }
+
+        // toggle the favorite
+        let favorites = self.favorites.entry(cmd.user.id).or_default();
+        let content = if favorites.remove(name) {
+            format!("Removed **{}** from your favorites.", name)
+        } else if favorites.len() >= MAX_FAVORITES {
+            return respond_ephemeral(&ctx, &cmd, format!("You can have at most {} favorite reactions!", MAX_FAVORITES)).await;
+        } else {
+            favorites.insert(name.to_string());
+            format!("Added **{}** to your favorites. Use /{} fav to perform one of them.", name, UTILITY_COMMAND)
+        };
+        if favorites.is_empty() {
+            self.favorites.remove(&cmd.user.id);
+        }
+
+        // persist the favorites of all users
+        let serialized = serde_json::to_string(&self.favorites)
+            .context("failed to serialize favorites")?;
+        self.store.set(STORE_NAMESPACE, FAVORITES_KEY, &serialized).await
+            .context("failed to save favorites")?;
+
+        respond_ephemeral(&ctx, &cmd, content).await
+    }
+
+    ///
+    /// Perform a random favorite reaction of the user
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    async fn perform_favorite(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        // pick a random favorite that can be used here
+        let favorites = self.favorites.get(&cmd.user.id).cloned().unwrap_or_default();
+        let candidates = self.reactions.iter()
+            .filter(|r| favorites.contains(&r.name) && self.guild_allows(cmd.guild_id, &r.name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if favorites.is_empty() {
+            let note = format!("You have no favorite reactions yet! Add one with /{} favorite.", UTILITY_COMMAND);
+            return respond_ephemeral(&ctx, &cmd, note).await;
+        }
+        let Some(reaction) = pick_random(&mut self.rng, &candidates).cloned() else {
+            return respond_ephemeral(&ctx, &cmd, "None of your favorite reactions can be used here!").await;
+        };
+
+        let options = subcommand_options(&cmd).to_vec();
+        self.react(ctx, &cmd, reaction, &options).await
+    }
+
+    ///
+    /// Show a random gif of a reaction to the user, without a target or response and without counting as a use
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    async fn sample(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        let name = subcommand_options(&cmd).first().and_then(|opt| opt.value.as_str())
+            .context("no reaction name")?;
+        let Some(reaction) = self.reactions.iter().find(|r| r.name == name).cloned() else {
+            return respond_ephemeral(&ctx, &cmd, "There is no reaction with this name!").await;
+        };
+
+        // samples skip the cooldown, as they don't count as a use, but not the other gates
+        if let Some(refusal) = self.check_allowed(Invocation::Command(&cmd), &reaction) {
+            return cmd.create_response(&ctx.http, CreateInteractionResponse::Message(refusal)).await
+                .context("failed to send response");
+        }
+
+        // pick a cached gif of the current backends, uncached backends like avatar backends have nothing to show
+        let reaction = self.seasonal_reaction(reaction);
+        let gif = pick_random(&mut self.rng, &reaction.backends)
+            .and_then(|info| info.split_once('/'))
+            .and_then(|(backend, endpoint)| {
+                self.backend_manager.get_cached(&mut self.rng, backend, endpoint, None).ok()
+                    .map(|gif| (backend.to_string(), gif))
+            });
+        let Some((backend, gif)) = gif else {
+            return respond_ephemeral(&ctx, &cmd, "There is no gif of this reaction to show yet, please try again later!").await;
+        };
+
+        debug!(target: "module/reaction", "user @{} sampled reaction {}", cmd.user.name, reaction.name);
+        // hide nsfw gifs behind a spoiler link like reactions do
+        let spoiler = reaction.nsfw && self.settings.nsfw_spoiler;
+        let image_url = proxied(self.backend_settings.get(&backend).and_then(|b| b.proxy.as_deref()), &gif);
+        let mut lines = Vec::new();
+        if spoiler {
+            lines.push(format!("|| {} ||", image_url));
+        }
+        if self.backend_settings.get(&backend).is_none_or(|b| b.attribution) {
+            lines.push(format!("-# From: {} • [Source](<{}>)", backend, gif));
+        }
+        let mut response = CreateInteractionResponseMessage::new()
+            .content(lines.join("\n"))
+            .ephemeral(true);
+        if !spoiler {
+            response = response.embed(CreateEmbed::new().image(image_url).color(self.category_color(&reaction)));
+        }
+
+        cmd.create_response(&ctx.http, CreateInteractionResponse::Message(response)).await
+            .context("failed to send response")
+    }
+
+    ///
+    /// Perform a random reaction usable here, picked as configured by the random selection
+    ///
+    /// Nsfw reactions are never picked, as the user didn't choose them.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    async fn perform_random(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        let candidates = self.reactions.iter()
+            .filter(|r| !r.nsfw && self.guild_allows(cmd.guild_id, &r.name))
+            .filter(|r| r.contexts.as_ref().is_none_or(|contexts| contexts.allows(cmd.context)))
+            .cloned()
+            .collect::<Vec<_>>();
+        let reaction = match self.settings.random_selection {
+            RandomSelection::Uniform => pick_random(&mut self.rng, &candidates),
+            // unused reactions keep a chance of being picked
+            RandomSelection::Popularity => pick_weighted(&mut self.rng, &candidates, |r| self.usage.get(&r.name).map_or(1.0, |uses| *uses as f64 + 1.0)),
+            RandomSelection::Weight => pick_weighted(&mut self.rng, &candidates, |r| r.random_weight)
+        };
+        let Some(reaction) = reaction.cloned() else {
+            return respond_ephemeral(&ctx, &cmd, "There is no reaction that can be used here!").await;
+        };
+
+        let options = subcommand_options(&cmd).to_vec();
+        self.react(ctx, &cmd, reaction, &options).await
+    }
+
+    ///
+    /// Preview the responses of a reaction to the owner without side effects
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    async fn preview(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        if !self.owners.contains(&cmd.user.id) {
+            return respond_ephemeral(&ctx, &cmd, "Only owners of the bot can preview reactions!").await;
+        }
+
+        let name = subcommand_options(&cmd).first().and_then(|opt| opt.value.as_str())
+            .context("no reaction name")?;
+        let Some(reaction) = self.reactions.iter().find(|r| r.name == name) else {
+            return respond_ephemeral(&ctx, &cmd, "There is no reaction with this name!").await;
+        };
+
+        // render a response of every variant, referring to the target with their pronouns
+        let pronouns = |id| self.pronouns.get(&id).copied().unwrap_or_default();
+        let bot_id = self.bot_id(cmd.application_id);
+        let user = (format!("<@{}>", cmd.user.id), cmd.user.display_name().to_string(), pronouns(cmd.user.id));
+        let bot = (format!("<@{}>", bot_id), self.bot_name.clone().unwrap_or_else(|| NAME_FALLBACK.to_string()), pronouns(bot_id));
+        let variants = [
+            ("Default", &reaction.default_responses, &user),
+            ("Self", &reaction.self_responses, &user),
+            ("Bot", &reaction.bot_responses, &bot),
+            ("DM", &reaction.dm_responses, &user),
+            ("Rare", &reaction.rare_responses, &user)
+        ];
+        let mut content = format!("Preview of **{}**:", reaction.name);
+        for (variant, responses, target) in variants {
+            if let Some(template) = pick_response(&mut self.rng, responses) {
+                let mut placeholders = vec![("user", user.0.as_str()), ("user.name", &user.1), ("target", &target.0), ("target.name", &target.1)];
+                placeholders.extend(target.2.placeholders());
+                content += &format!("\n**{}:** {}", variant, render_response(template, &placeholders, self.settings.sanitization));
+            }
+        }
+
+        // show a cached gif without marking it as used
+        let mut response = CreateInteractionResponseMessage::new()
+            .content(content)
+            .allowed_mentions(CreateAllowedMentions::new())
+            .ephemeral(true);
+        let gif = pick_random(&mut self.rng, &reaction.backends)
+            .and_then(|info| info.split_once('/'))
+            .and_then(|(backend, endpoint)| self.backend_manager.peek_cached(&mut self.rng, backend, endpoint, None).ok());
+        if let Some(gif) = gif {
+            response = response.embed(CreateEmbed::new().image(gif).color(crate::color::rand()));
+        }
+
+        cmd.create_response(&ctx.http, CreateInteractionResponse::Message(response)).await
+            .context("failed to send response")
+    }
+
+    ///
+    /// Show the usage, the last response and the pools of a reaction to the owner
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    async fn debug(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        if !self.owners.contains(&cmd.user.id) {
+            return respond_ephemeral(&ctx, &cmd, "Only owners of the bot can debug reactions!").await;
+        }
+
+        let name = subcommand_options(&cmd).first().and_then(|opt| opt.value.as_str())
+            .context("no reaction name")?;
+        let Some(reaction) = self.reactions.iter().find(|r| r.name == name) else {
+            return respond_ephemeral(&ctx, &cmd, "There is no reaction with this name!").await;
+        };
+
+        let mut content = format!("Debug info of **{}**:\nUsed {} times since the stats were created",
+            reaction.name, self.usage.get(&reaction.name).copied().unwrap_or(0));
+        match self.last_performed.get(&reaction.name) {
+            Some((url, template, performed)) => {
+                content += &format!("\nLast performed {}m ago with <{}>", performed.elapsed().as_secs() / 60, url);
+                content += &format!("\nLast response: `{}`", template.replace('`', "'"));
+            },
+            None => content += "\nNot performed since startup"
+        }
+
+        // list every endpoint of the regular and rare backends
+        content += "\n**Pools:**";
+        for (info, rare) in reaction.backends.iter().map(|info| (info, false)).chain(reaction.rare_backends.iter().map(|info| (info, true))) {
+            let Some((backend, combined)) = info.split_once('/') else {
+                continue;
+            };
+            for endpoint in combined.split('+') {
+                let state = match self.backend_manager.pool_size(backend, endpoint) {
+                    None => "not cached".to_string(),
+                    Some(size) => {
+                        let refreshed = match self.backend_manager.last_refreshed(backend, endpoint) {
+                            Some(refreshed) => format!("refreshed {}m ago", refreshed.elapsed().as_secs() / 60),
+                            None => "not refreshed since startup".to_string()
+                        };
+                        let health = if self.backend_manager.is_unhealthy(backend, endpoint) { ", unhealthy" } else { "" };
+                        format!("{} gifs, {}{}", size, refreshed, health)
+                    }
+                };
+                content += &format!("\n`{}/{}`{} - {}", backend, endpoint, if rare { " (rare)" } else { "" }, state);
+            }
+        }
+
+        respond_ephemeral(&ctx, &cmd, content).await
+    }
+
+    ///
+    /// Show the sizes and ages of the stalest cached pools to the owner
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    async fn cache_status(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        if !self.owners.contains(&cmd.user.id) {
+            return respond_ephemeral(&ctx, &cmd, "Only owners of the bot can inspect the cache!").await;
+        }
+
+        // list pools that were never refreshed first, then the oldest ones
+        let mut pools = self.backend_manager.pool_sizes().into_iter()
+            .map(|(backend, endpoint, size)| {
+                let age = self.backend_manager.last_refreshed(backend, &endpoint).map(|refreshed| refreshed.elapsed());
+                (backend, endpoint, size, age)
+            })
+            .collect::<Vec<_>>();
+        if pools.is_empty() {
+            return respond_ephemeral(&ctx, &cmd, "Nothing is cached yet!").await;
+        }
+        pools.sort_by_key(|(_, _, _, age)| std::cmp::Reverse(age.unwrap_or(Duration::MAX)));
+
+        let mut content = format!("**{}** cached pools, stalest first:", pools.len());
+        for (backend, endpoint, size, age) in pools.iter().take(CACHE_STATUS_LIMIT) {
+            let age = match age {
+                Some(age) => format!("refreshed {}m ago", age.as_secs() / 60),
+                None => "not refreshed since startup".to_string()
+            };
+            content += &format!("\n`{}/{}` - {} gifs, {}", backend, endpoint, size, age);
+        }
+        if pools.len() > CACHE_STATUS_LIMIT {
+            content += &format!("\n...and {} more", pools.len() - CACHE_STATUS_LIMIT);
+        }
+
+        respond_ephemeral(&ctx, &cmd, content).await
+    }
+
+    ///
+    /// Manage the reactions allowed and denied in the guild
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    async fn manage_guild(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        let Some(guild) = cmd.guild_id else {
+            return respond_ephemeral(&ctx, &cmd, "This command can only be used in a server!").await;
+        };
+        if !cmd.member.as_ref().and_then(|member| member.permissions).is_some_and(|permissions| permissions.manage_guild()) {
+            return respond_ephemeral(&ctx, &cmd, "Only members with the Manage Server permission can manage reactions!").await;
+        }
+
+        let Some(action) = subcommand_options(&cmd).first() else {
+            return Err(anyhow!("no subcommand"));
+        };
+        let name = match &action.value {
+            CommandDataOptionValue::SubCommand(options) => options.first().and_then(|opt| opt.value.as_str()),
+            _ => None
+        };
+        if let Some(name) = name {
+            if !self.reactions.iter().any(|r| r.name == name) {
+                return respond_ephemeral(&ctx, &cmd, "There is no reaction with this name!").await;
+            }
+        }
+
+        // update the lists of the guild
+        let reactions = self.guild_reactions.entry(guild).or_default();
+        let content = match (action.name.as_str(), name) {
+            ("allow", Some(name)) => {
+                reactions.allow(name);
+                format!("Allowed **{}** in this server. Only allowed reactions can be used now.", name)
+            },
+            ("deny", Some(name)) => {
+                reactions.deny(name);
+                format!("Disabled **{}** in this server.", name)
+            },
+            ("reset", None) => {
+                *reactions = GuildReactions::default();
+                "All reactions can be used in this server again.".to_string()
+            },
+            ("show", None) => format!("**Allowed:** {}\n**Disabled:** {}",
+                guild::format_list(&reactions.allow), guild::format_list(&reactions.deny)),
+            _ => return Err(anyhow!("unknown subcommand"))
+        };
+        if reactions.is_empty() {
+            self.guild_reactions.remove(&guild);
+        }
+
+        // persist the lists of all guilds
+        if action.name != "show" {
+            info!(target: "module/reaction", "user @{} ran /{} server {} in guild {}", cmd.user.name, UTILITY_COMMAND, action.name, guild);
+            let serialized = serde_json::to_string(&self.guild_reactions)
+                .context("failed to serialize guild reactions")?;
+            self.store.set(STORE_NAMESPACE, GUILDS_KEY, &serialized).await
+                .context("failed to save guild reactions")?;
+        }
+
+        respond_ephemeral(&ctx, &cmd, content).await
+    }
+
+    ///
+    /// Handle a component created by the utility command
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `component` - The component interaction
+    ///
+    pub(super) async fn handle_utility_component(&mut self, ctx: serenity::all::Context, component: ComponentInteraction) -> Result<(), anyhow::Error> {
+        debug!(target: "module/reaction", "handling component {} used by @{}", component.data.custom_id, component.user.name);
+        let custom_id = component.data.custom_id.strip_prefix(COMPONENT_PREFIX)
+            .context("invalid custom id")?;
+        let (action, argument) = custom_id.split_once(':')
+            .context("invalid custom id")?;
+
+        match (action, &component.data.kind) {
+            // switch the page of the menu
+            ("page", ComponentInteractionDataKind::Button) => {
+                let page = argument.parse().context("invalid page")?;
+                let (content, components) = self.menu_page(page);
+                component.create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(components)
+                )).await.context("failed to send response")
+            },
+            // switch the page of a reaction list
+            ("list", kind) => {
+                let Some((page, created)) = self.list_pages.get(&component.message.id).copied()
+                    .filter(|(_, created)| created.elapsed() < LIST_TIMEOUT) else {
+                    self.list_pages.remove(&component.message.id);
+                    return component.create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new()
+                            .content("This list has expired, please run the command again!")
+                            .components(vec![])
+                    )).await.context("failed to send response");
+                };
+
+                let page = match (argument, kind) {
+                    ("previous", ComponentInteractionDataKind::Button) => page.saturating_sub(1),
+                    ("next", ComponentInteractionDataKind::Button) => page + 1,
+                    ("jump", ComponentInteractionDataKind::StringSelect { values }) => values.first()
+                        .and_then(|value| value.parse().ok())
+                        .context("invalid page")?,
+                    _ => return Err(anyhow!("unknown component"))
+                };
+                let page = page.min(self.list_pages_count() - 1);
+                self.list_pages.insert(component.message.id, (page, created));
+
+                component.create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(
+                    self.list_page(page).into()
+                )).await.context("failed to send response")
+            },
+            // ask for the target of the picked reaction
+            ("pick", ComponentInteractionDataKind::StringSelect { values }) => {
+                let name = values.first().context("no reaction picked")?;
+                let reaction = self.reactions.iter().find(|r| &r.name == name)
+                    .context("unknown reaction")?;
+                let menu = CreateSelectMenu::new(format!("{}target:{}", COMPONENT_PREFIX, reaction.name), CreateSelectMenuKind::User { default_users: None })
+                    .placeholder("Pick a user");
+                component.create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(format!("Who do you want to use **{}** on?", reaction.name))
+                        .components(vec![CreateActionRow::SelectMenu(menu)])
+                )).await.context("failed to send response")
+            },
+            // perform the reaction on the picked target
+            ("target", ComponentInteractionDataKind::UserSelect { values }) => {
+                let target = *values.first().context("no target picked")?;
+                let reaction = self.reactions.iter().find(|r| r.name == argument)
+                    .context("unknown reaction")?
+                    .clone();
+                let reaction = self.seasonal_reaction(reaction);
+                let invocation = Invocation::Component(&component);
+                if let Some(refusal) = self.check_usable(invocation, &reaction) {
+                    return invocation.create_response(&ctx.http, CreateInteractionResponse::Message(refusal)).await
+                        .context("failed to send response");
+                }
+
+                self.perform_and_send(&ctx, invocation, &reaction, &[target], false).await
+            },
+            _ => Err(anyhow!("unknown component"))
+        }
+    }
+
+    ///
+    /// Build a page of the reaction menu
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - The index of the page
+    ///
+    /// # Returns
+    ///
+    /// The content and components of the page
+    ///
+    fn menu_page(&self, page: usize) -> (String, Vec<CreateActionRow>) {
+        let pages = self.reactions.len().div_ceil(MENU_PAGE_SIZE).max(1);
+        let page = page.min(pages - 1);
+
+        // build the select menu of the page
+        let options = self.reactions.iter()
+            .skip(page * MENU_PAGE_SIZE)
+            .take(MENU_PAGE_SIZE)
+            .map(|r| CreateSelectMenuOption::new(&r.name, &r.name).description(&r.description))
+            .collect();
+        let menu = CreateSelectMenu::new(format!("{}pick:{}", COMPONENT_PREFIX, page), CreateSelectMenuKind::String { options })
+            .placeholder("Pick a reaction");
+
+        // build the page buttons
+        let previous = CreateButton::new(format!("{}page:{}", COMPONENT_PREFIX, page.saturating_sub(1)))
+            .label("Previous")
+            .disabled(page == 0);
+        let next = CreateButton::new(format!("{}page:{}", COMPONENT_PREFIX, page + 1))
+            .label("Next")
+            .disabled(page + 1 >= pages);
+
+        let content = format!("Pick a reaction! (page {}/{})", page + 1, pages);
+        (content, vec![CreateActionRow::SelectMenu(menu), CreateActionRow::Buttons(vec![previous, next])])
+    }
+
+    ///
+    /// Get the number of pages of the reaction list
+    ///
+    /// # Returns
+    ///
+    /// The number of pages, at least 1
+    ///
+    fn list_pages_count(&self) -> usize {
+        self.reactions.len().div_ceil(LIST_PAGE_SIZE).max(1)
+    }
+
+    ///
+    /// Build a page of the reaction list
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - The index of the page
+    ///
+    /// # Returns
+    ///
+    /// The embed and components of the page
+    ///
+    fn list_page(&self, page: usize) -> ListPage {
+        let pages = self.list_pages_count();
+        let page = page.min(pages - 1);
+
+        // list the reactions of the page
+        let description = self.reactions.iter()
+            .skip(page * LIST_PAGE_SIZE)
+            .take(LIST_PAGE_SIZE)
+            .map(|r| format!("**{}** - {}", r.name, r.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let embed = CreateEmbed::new()
+            .title("Reactions")
+            .description(description)
+            .footer(CreateEmbedFooter::new(format!("Page {}/{}", page + 1, pages)))
+            .color(crate::color::rand());
+
+        // build the page buttons
+        let previous = CreateButton::new(format!("{}list:previous", COMPONENT_PREFIX))
+            .label("Previous")
+            .disabled(page == 0);
+        let next = CreateButton::new(format!("{}list:next", COMPONENT_PREFIX))
+            .label("Next")
+            .disabled(page + 1 >= pages);
+        let mut components = vec![CreateActionRow::Buttons(vec![previous, next])];
+
+        // build the jump menu around the current page
+        if pages > 1 {
+            let first = page.saturating_sub(MENU_PAGE_SIZE / 2).min(pages.saturating_sub(MENU_PAGE_SIZE));
+            let options = (first..pages.min(first + MENU_PAGE_SIZE))
+                .map(|p| CreateSelectMenuOption::new(format!("Page {}", p + 1), p.to_string()).default_selection(p == page))
+                .collect();
+            let jump = CreateSelectMenu::new(format!("{}list:jump", COMPONENT_PREFIX), CreateSelectMenuKind::String { options })
+                .placeholder("Jump to page");
+            components.push(CreateActionRow::SelectMenu(jump));
+        }
+
+        ListPage { embed, components }
+    }
+
+    ///
+    /// Report the last gif served in the channel as broken
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context
+    /// * `cmd` - The command interaction
+    ///
+    async fn report(&mut self, ctx: serenity::all::Context, cmd: CommandInteraction) -> Result<(), anyhow::Error> {
+        // rate limit reports
+        let now = Instant::now();
+        let cooldown = Duration::from_secs(self.settings.report_cooldown);
+        if self.last_report.get(&cmd.user.id).is_some_and(|last| now.duration_since(*last) < cooldown) {
+            return respond_ephemeral(&ctx, &cmd, "You're reporting gifs too quickly, please try again later!").await;
+        }
+
+        // find the last gif served in the channel
+        let Some((backend, url)) = self.last_served.remove(&cmd.channel_id) else {
+            return respond_ephemeral(&ctx, &cmd, "There is no gif to report in this channel!").await;
+        };
+        self.last_report.insert(cmd.user.id, now);
+
+        // remove the gif from the pool
+        let removed = self.backend_manager.remove_gif(&backend, &url);
+        warn!(target: "module/reaction", "user @{} reported gif from backend '{}' as broken (cached: {}): {}", cmd.user.name, backend, removed, url);
+
+        // notify owners
+        if self.settings.notify_owners_on_report {
+            for owner in &self.owners {
+                let message = CreateMessage::new()
+                    .content(format!("<@{}> reported a gif from {} as broken: <{}>", cmd.user.id, backend, url));
+                if let Err(e) = owner.dm(&ctx.http, message).await {
+                    warn!(target: "module/reaction", "failed to notify owner <@{}> about report: {:?}", owner, e);
+                }
+            }
+            info!(target: "module/reaction", "notified {} owners about report", self.owners.len());
+        }
+
+        respond_ephemeral(&ctx, &cmd, "Thank you for the report! This gif won't be shown again.").await
+    }
+
+}
+
+///
+/// Get the options of the subcommand or subcommand group of a command
+///
+/// # Arguments
+///
+/// * `cmd` - The command interaction
+///
+/// # Returns
+///
+/// The options of the subcommand or the subcommands of the group, empty if there is none
+///
+fn subcommand_options(cmd: &CommandInteraction) -> &[CommandDataOption] {
+    match cmd.data.options.first().map(|opt| &opt.value) {
+        Some(CommandDataOptionValue::SubCommand(options) | CommandDataOptionValue::SubCommandGroup(options)) => options,
+        _ => &[]
+    }
+}
+
+///
+/// Rank how well a reaction matches a search keyword
+///
+/// # Arguments
+///
+/// * `reaction` - The reaction
+/// * `keyword` - The lowercase keyword
+///
+/// # Returns
+///
+/// The rank of the match, lower is better, or None if the reaction doesn't match. Exact names rank first,
+/// followed by names starting with or containing the keyword, descriptions containing the keyword and
+/// names containing the letters of the keyword in order.
+///
+fn match_rank(reaction: &Reaction, keyword: &str) -> Option<u8> {
+    let name = reaction.name.to_lowercase();
+    if name == keyword {
+        Some(0)
+    } else if name.starts_with(keyword) {
+        Some(1)
+    } else if name.contains(keyword) {
+        Some(2)
+    } else if reaction.description.to_lowercase().contains(keyword) {
+        Some(3)
+    } else {
+        let mut letters = name.chars();
+        keyword.chars().all(|c| letters.any(|l| l == c)).then_some(4)
+    }
+}
+
+/// A page of the reaction list
+struct ListPage {
+    /// The embed listing the reactions
+    embed: CreateEmbed,
+    /// The page buttons and jump menu
+    components: Vec<CreateActionRow>
+}
+
+impl From<ListPage> for CreateInteractionResponseMessage {
+    fn from(page: ListPage) -> Self {
+        CreateInteractionResponseMessage::new()
+            .embed(page.embed)
+            .components(page.components)
+    }
+}
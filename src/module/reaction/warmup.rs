@@ -0,0 +1,55 @@
+use std::{sync::Arc, time::Duration};
+
+use log::warn;
+use serenity::all::{ChannelId, CreateMessage, EditMessage, Http};
+use tokio::sync::watch;
+
+/// Minimum time between edits of the progress message
+const EDIT_INTERVAL: Duration = Duration::from_secs(2);
+
+///
+/// Report the progress of the cache warmup in a channel until the warmup is done
+///
+/// # Arguments
+///
+/// * `http` - The http client of the bot
+/// * `channel` - The channel to report the progress in
+/// * `updates` - Receiver of the number of warmed and total endpoints
+///
+pub async fn report(http: Arc<Http>, channel: ChannelId, mut updates: watch::Receiver<(usize, usize)>) {
+    let mut message = None;
+    while updates.changed().await.is_ok() {
+        let (warmed, total) = *updates.borrow_and_update();
+        let content = format!("Warming up the gif cache: {}/{} endpoints", warmed, total);
+        update(&http, channel, &mut message, content).await;
+
+        tokio::time::sleep(EDIT_INTERVAL).await;
+    }
+
+    let (warmed, total) = *updates.borrow();
+    let status = if warmed == total { "complete" } else { "stopped" };
+    let content = format!("Warming up the gif cache {}: {}/{} endpoints", status, warmed, total);
+    update(&http, channel, &mut message, content).await;
+}
+
+///
+/// Post or edit the progress message
+///
+/// # Arguments
+///
+/// * `http` - The http client of the bot
+/// * `channel` - The channel to post the progress message in
+/// * `message` - The progress message, if already posted
+/// * `content` - The new content of the progress message
+///
+async fn update(http: &Http, channel: ChannelId, message: &mut Option<serenity::all::Message>, content: String) {
+    let result = match message {
+        Some(message) => message.edit(http, EditMessage::new().content(content)).await,
+        None => channel.send_message(http, CreateMessage::new().content(content)).await
+            .map(|posted| *message = Some(posted))
+    };
+
+    if let Err(e) = result {
+        warn!(target: "module/reaction", "failed to report cache warmup progress: {:?}", e);
+    }
+}
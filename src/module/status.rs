@@ -1,15 +1,14 @@
 use anyhow::anyhow;
-use log::{debug, info};
-use rand::seq::SliceRandom;
+use log::{debug, info, warn};
 use serenity::{all::{ActivityData, CommandInteraction, CreateCommand, OnlineStatus}, async_trait};
+use crate::{task::BackgroundTask, Configuration};
 
-use crate::Configuration;
-
-use super::Module;
+use super::{pick_random, Module};
 
 /// Status module
 pub struct StatusModule {
-
+    /// Task updating the status
+    task: Option<BackgroundTask>
 }
 
 impl StatusModule {
@@ -24,11 +23,17 @@ impl StatusModule {
     pub fn new() -> Self {
         info!(target: "module/status", "creating status module");
 
-        Self {}
+        Self { task: None }
     }
 
 }
 
+impl Default for StatusModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[async_trait]
 impl Module for StatusModule {
 
@@ -38,16 +43,23 @@ impl Module for StatusModule {
 
         // schedule timer to update status
         let ctx = ctx.clone();
-        tokio::spawn(async move {
+        self.task = Some(BackgroundTask::spawn(|cancel| async move {
             loop {
-                let status = status_messages.choose(&mut rand::thread_rng()).unwrap();
+                let Some(status) = pick_random(&mut rand::thread_rng(), &status_messages) else {
+                    warn!(target: "module/status", "no status messages configured");
+                    return;
+                };
 
+                // presence updates are queued by the shard, so they survive reconnects
                 debug!(target: "module/status", "setting status to: {}", status);
                 ctx.set_presence(Some(ActivityData::custom(status)), OnlineStatus::Online);
 
-                tokio::time::sleep(tokio::time::Duration::from_secs(60 * 30)).await;
+                tokio::select! {
+                    _ = cancel.cancelled() => return,
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(60 * 30)) => {}
+                }
             }
-        });
+        }));
 
         Ok(vec![])
     }
@@ -60,4 +72,13 @@ impl Module for StatusModule {
         Err(anyhow!("not implemented"))
     }
 
+    async fn shutdown(&mut self) -> Result<(), anyhow::Error> {
+        info!(target: "module/status", "shutting down status module");
+        if let Some(task) = self.task.take() {
+            task.stop("module/status").await;
+        }
+
+        Ok(())
+    }
+
 }
\ No newline at end of file
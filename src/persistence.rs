@@ -0,0 +1,83 @@
+use std::{fs::{self, File}, io::{ErrorKind, Write}, path::Path};
+
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Serialize};
+
+///
+/// Atomically write a file by writing to a temporary file first and renaming it afterwards
+///
+/// # Arguments
+///
+/// * `path` - The path of the file to write
+/// * `contents` - The contents of the file
+///
+/// # Errors
+///
+/// If the file cannot be written or renamed
+///
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), anyhow::Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context("failed to create parent directory")?;
+    }
+
+    // write to temporary file
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)
+        .context("failed to create temporary file")?;
+    file.write_all(contents)
+        .context("failed to write temporary file")?;
+    file.sync_all()
+        .context("failed to flush temporary file")?;
+
+    // replace the original file
+    fs::rename(&tmp_path, path)
+        .context("failed to replace file")?;
+
+    Ok(())
+}
+
+///
+/// Atomically save a value as json
+///
+/// # Arguments
+///
+/// * `path` - The path of the file to write
+/// * `value` - The value to save
+///
+/// # Errors
+///
+/// If the value fails to serialize or the file cannot be written
+///
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<(), anyhow::Error> {
+    let contents = serde_json::to_vec(value)
+        .context("failed to serialize value")?;
+    write_atomic(path, &contents)
+}
+
+///
+/// Load a value from a json file
+///
+/// # Arguments
+///
+/// * `path` - The path of the file to read
+///
+/// # Returns
+///
+/// The value, or None if the file does not exist
+///
+/// # Errors
+///
+/// If the file cannot be read or fails to deserialize
+///
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, anyhow::Error> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("failed to read file")
+    };
+
+    let value = serde_json::from_slice(&contents)
+        .context("failed to deserialize file")?;
+    Ok(Some(value))
+}
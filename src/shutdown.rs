@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use anyhow::anyhow;
+
+/// Write end of the pipe the signal handler notifies
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+/// Signal handler writing a single byte to the signal pipe
+extern "C" fn on_signal(_: libc::c_int) {
+    let fd = SIGNAL_PIPE.load(Ordering::SeqCst);
+    if fd >= 0 {
+        // only async-signal-safe calls are allowed here
+        unsafe { libc::write(fd, [1u8].as_ptr() as *const libc::c_void, 1) };
+    }
+}
+
+///
+/// Wait for a termination signal (SIGINT or SIGTERM)
+///
+/// # Errors
+///
+/// If the signal handlers cannot be installed
+///
+pub async fn wait_for_signal() -> Result<(), anyhow::Error> {
+    // create the signal pipe
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(anyhow!("failed to create signal pipe"));
+    }
+    let [read_fd, write_fd] = fds;
+    SIGNAL_PIPE.store(write_fd, Ordering::SeqCst);
+
+    // install signal handlers
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        if unsafe { libc::signal(signal, on_signal as *const () as libc::sighandler_t) } == libc::SIG_ERR {
+            return Err(anyhow!("failed to install signal handler"));
+        }
+    }
+
+    // wait for the signal handler to write to the pipe
+    tokio::task::spawn_blocking(move || {
+        let mut buf = [0u8; 1];
+        loop {
+            let read = unsafe { libc::read(read_fd, buf.as_mut_ptr() as *mut libc::c_void, 1) };
+            if read >= 0 || std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+                break;
+            }
+        }
+    }).await?;
+
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use std::{path::{Path, PathBuf}, sync::Arc};
+
+use anyhow::Context;
+use log::info;
+use serde::Deserialize;
+use serenity::async_trait;
+
+mod json;
+mod memory;
+pub(crate) mod redis;
+#[cfg(feature = "sqlite-store")]
+mod sqlite;
+
+/// Settings of the store persisting the module state
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StoreSettings {
+    /// Keep the state in memory only
+    Memory,
+    /// Persist the state to a json file
+    Json {
+        /// Path of the json file
+        path: PathBuf
+    },
+    /// Persist the state to a redis server
+    Redis {
+        /// Url of the redis server, e.g. 'redis://127.0.0.1:6379'
+        url: String
+    },
+    /// Persist the state to a sqlite database, requires the 'sqlite-store' feature
+    Sqlite {
+        /// Path of the database file
+        path: PathBuf
+    }
+}
+
+///
+/// Key-value store for namespaced keys shared by all modules
+///
+#[async_trait]
+pub trait Store: Send + Sync {
+
+    ///
+    /// Get the value of a key
+    ///
+    /// # Arguments
+    ///
+    /// * `namespace` - The namespace of the key, usually the module name
+    /// * `key` - The key
+    ///
+    /// # Returns
+    ///
+    /// The value of the key or None if the key is not set
+    ///
+    /// # Errors
+    ///
+    /// If the store cannot be read
+    ///
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<String>, anyhow::Error>;
+
+    ///
+    /// Set the value of a key
+    ///
+    /// # Arguments
+    ///
+    /// * `namespace` - The namespace of the key, usually the module name
+    /// * `key` - The key
+    /// * `value` - The new value of the key
+    ///
+    /// # Errors
+    ///
+    /// If the store cannot be written
+    ///
+    async fn set(&self, namespace: &str, key: &str, value: &str) -> Result<(), anyhow::Error>;
+
+    ///
+    /// Increment the integer value of a key, treating unset keys as 0
+    ///
+    /// # Arguments
+    ///
+    /// * `namespace` - The namespace of the key, usually the module name
+    /// * `key` - The key
+    /// * `by` - The amount to increment the value by
+    ///
+    /// # Returns
+    ///
+    /// The incremented value
+    ///
+    /// # Errors
+    ///
+    /// If the value is not an integer or the store cannot be written
+    ///
+    async fn increment(&self, namespace: &str, key: &str, by: i64) -> Result<i64, anyhow::Error>;
+
+    ///
+    /// Get the binary value of a key
+    ///
+    /// Stores without native binary values keep them hex-encoded in a string value.
+    ///
+    /// # Arguments
+    ///
+    /// * `namespace` - The namespace of the key, usually the module name
+    /// * `key` - The key
+    ///
+    /// # Returns
+    ///
+    /// The binary value of the key or None if the key is not set
+    ///
+    /// # Errors
+    ///
+    /// If the store cannot be read or the value is not binary
+    ///
+    async fn get_bytes(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        self.get(namespace, key).await?
+            .map(|value| decode_hex(&value))
+            .transpose()
+    }
+
+    ///
+    /// Set the binary value of a key
+    ///
+    /// # Arguments
+    ///
+    /// * `namespace` - The namespace of the key, usually the module name
+    /// * `key` - The key
+    /// * `value` - The new binary value of the key
+    ///
+    /// # Errors
+    ///
+    /// If the store cannot be written
+    ///
+    async fn set_bytes(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), anyhow::Error> {
+        self.set(namespace, key, &encode_hex(value)).await
+    }
+
+    ///
+    /// Write pending changes to the underlying storage
+    ///
+    /// # Errors
+    ///
+    /// If the store cannot be written
+    ///
+    async fn flush(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+}
+
+///
+/// Encode a binary value as a hex string
+///
+/// # Arguments
+///
+/// * `value` - The binary value
+///
+/// # Returns
+///
+/// The lowercase hex string
+///
+fn encode_hex(value: &[u8]) -> String {
+    value.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+///
+/// Decode a hex string into a binary value
+///
+/// # Arguments
+///
+/// * `value` - The hex string
+///
+/// # Returns
+///
+/// The binary value
+///
+/// # Errors
+///
+/// If the string is not valid hex
+///
+fn decode_hex(value: &str) -> Result<Vec<u8>, anyhow::Error> {
+    if !value.len().is_multiple_of(2) || !value.is_ascii() {
+        return Err(anyhow::anyhow!("value is not binary"));
+    }
+    (0..value.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).context("value is not binary"))
+        .collect()
+}
+
+/// Name of the json store file in the data directory
+const STORE_FILE: &str = "store.json";
+
+///
+/// Open the store described by the settings
+///
+/// Without settings, the state is persisted to a json file in the data directory
+/// or kept in memory if no data directory is set.
+///
+/// # Arguments
+///
+/// * `settings` - The store settings
+/// * `data_directory` - The data directory
+///
+/// # Returns
+///
+/// The opened store
+///
+/// # Errors
+///
+/// If the store cannot be opened
+///
+pub fn open(settings: Option<&StoreSettings>, data_directory: Option<&Path>) -> Result<Arc<dyn Store>, anyhow::Error> {
+    let settings = match (settings, data_directory) {
+        (Some(settings), _) => settings.clone(),
+        (None, Some(data_directory)) => StoreSettings::Json { path: data_directory.join(STORE_FILE) },
+        (None, None) => StoreSettings::Memory
+    };
+
+    Ok(match settings {
+        StoreSettings::Memory => {
+            info!(target: "store", "using in-memory store");
+            Arc::new(memory::MemoryStore::new())
+        },
+        StoreSettings::Json { path } => {
+            info!(target: "store", "using json store at {}", path.display());
+            Arc::new(json::JsonStore::open(path)?)
+        },
+        StoreSettings::Redis { url } => {
+            info!(target: "store", "using redis store at {}", redis::redact_url(&url));
+            Arc::new(redis::RedisStore::new(&url)?)
+        },
+        #[cfg(feature = "sqlite-store")]
+        StoreSettings::Sqlite { path } => {
+            info!(target: "store", "using sqlite store at {}", path.display());
+            Arc::new(sqlite::SqliteStore::open(&path)?)
+        },
+        #[cfg(not(feature = "sqlite-store"))]
+        StoreSettings::Sqlite { .. } => return Err(anyhow::anyhow!("the sqlite store requires the 'sqlite-store' feature"))
+    })
+}
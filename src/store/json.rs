@@ -0,0 +1,125 @@
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use anyhow::Context;
+use serenity::async_trait;
+
+use crate::persistence;
+
+use super::{memory::MemoryStore, Store};
+
+///
+/// Store keeping all values in memory and persisting them to a json file when flushed
+///
+/// Binary values are persisted to their own files next to the json file, named '<stem>.<namespace>.<key>.bin'.
+///
+pub struct JsonStore {
+    /// Path of the json file
+    path: PathBuf,
+    /// Values of the store
+    values: MemoryStore
+}
+
+impl JsonStore {
+
+    ///
+    /// Open a json store, loading the values from the file if it exists
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the json file
+    ///
+    /// # Returns
+    ///
+    /// The json store
+    ///
+    /// # Errors
+    ///
+    /// If the file exists but cannot be read
+    ///
+    pub fn open(path: PathBuf) -> Result<Self, anyhow::Error> {
+        let values = persistence::load_json(&path)?.unwrap_or_default();
+        Ok(Self { path, values: MemoryStore::with_values(values) })
+    }
+
+    ///
+    /// Get the path of the file a binary value is persisted to
+    ///
+    /// # Arguments
+    ///
+    /// * `namespace` - The namespace of the key
+    /// * `key` - The key
+    ///
+    /// # Returns
+    ///
+    /// The path next to the json file
+    ///
+    fn bytes_path(&self, namespace: &str, key: &str) -> PathBuf {
+        let stem = self.path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("store");
+        self.path.with_file_name(format!("{}.{}.{}.bin", stem, namespace, key))
+    }
+
+}
+
+#[async_trait]
+impl Store for JsonStore {
+
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<String>, anyhow::Error> {
+        self.values.get(namespace, key).await
+    }
+
+    async fn set(&self, namespace: &str, key: &str, value: &str) -> Result<(), anyhow::Error> {
+        self.values.set(namespace, key, value).await
+    }
+
+    async fn increment(&self, namespace: &str, key: &str, by: i64) -> Result<i64, anyhow::Error> {
+        self.values.increment(namespace, key, by).await
+    }
+
+    async fn get_bytes(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        if let Some(value) = self.values.get_bytes(namespace, key).await? {
+            return Ok(Some(value));
+        }
+
+        // binary values are only loaded when first read
+        match fs::read(self.bytes_path(namespace, key)) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context("failed to read binary value")
+        }
+    }
+
+    async fn set_bytes(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), anyhow::Error> {
+        self.values.set_bytes(namespace, key, value).await
+    }
+
+    async fn flush(&self) -> Result<(), anyhow::Error> {
+        for ((namespace, key), value) in self.values.bytes().await {
+            persistence::write_atomic(&self.bytes_path(&namespace, &key), &value)
+                .context("failed to save binary value")?;
+        }
+        persistence::save_json(&self.path, &self.values.values().await)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn persists_binary_values_to_their_own_file() {
+        let directory = std::env::temp_dir().join(format!("puppy-json-store-{}", std::process::id()));
+        let path = directory.join("store.json");
+
+        let store = JsonStore::open(path.clone()).unwrap();
+        store.set_bytes("reaction", "cache_binary", &[0, 1, 255]).await.unwrap();
+        store.flush().await.unwrap();
+        assert_eq!(fs::read(directory.join("store.reaction.cache_binary.bin")).unwrap(), [0, 1, 255]);
+
+        let reopened = JsonStore::open(path).unwrap();
+        assert_eq!(reopened.get_bytes("reaction", "cache_binary").await.unwrap(), Some(vec![0, 1, 255]));
+        assert_eq!(reopened.get_bytes("reaction", "missing").await.unwrap(), None);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use serenity::async_trait;
+use tokio::sync::Mutex;
+
+use super::Store;
+
+///
+/// Store keeping all values in memory
+///
+pub struct MemoryStore {
+    /// Values by namespace and key
+    values: Mutex<HashMap<String, HashMap<String, String>>>,
+    /// Binary values by namespace and key
+    bytes: Mutex<HashMap<(String, String), Vec<u8>>>
+}
+
+impl MemoryStore {
+
+    ///
+    /// Create a new empty in-memory store
+    ///
+    /// # Returns
+    ///
+    /// The in-memory store
+    ///
+    pub fn new() -> Self {
+        Self::with_values(HashMap::new())
+    }
+
+    ///
+    /// Create a new in-memory store with initial values
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - The values by namespace and key
+    ///
+    /// # Returns
+    ///
+    /// The in-memory store
+    ///
+    pub fn with_values(values: HashMap<String, HashMap<String, String>>) -> Self {
+        Self { values: Mutex::new(values), bytes: Mutex::new(HashMap::new()) }
+    }
+
+    ///
+    /// Get a copy of all values
+    ///
+    /// # Returns
+    ///
+    /// The values by namespace and key
+    ///
+    pub async fn values(&self) -> HashMap<String, HashMap<String, String>> {
+        self.values.lock().await.clone()
+    }
+
+    ///
+    /// Get a copy of all binary values
+    ///
+    /// # Returns
+    ///
+    /// The binary values by namespace and key
+    ///
+    pub async fn bytes(&self) -> HashMap<(String, String), Vec<u8>> {
+        self.bytes.lock().await.clone()
+    }
+
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<String>, anyhow::Error> {
+        Ok(self.values.lock().await.get(namespace).and_then(|values| values.get(key)).cloned())
+    }
+
+    async fn set(&self, namespace: &str, key: &str, value: &str) -> Result<(), anyhow::Error> {
+        self.values.lock().await.entry(namespace.to_string()).or_default()
+            .insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    async fn increment(&self, namespace: &str, key: &str, by: i64) -> Result<i64, anyhow::Error> {
+        let mut values = self.values.lock().await;
+        let value = values.entry(namespace.to_string()).or_default()
+            .entry(key.to_string()).or_insert_with(|| "0".to_string());
+
+        let incremented = value.parse::<i64>()
+            .context("value is not an integer")? + by;
+        *value = incremented.to_string();
+        Ok(incremented)
+    }
+
+    async fn get_bytes(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        Ok(self.bytes.lock().await.get(&(namespace.to_string(), key.to_string())).cloned())
+    }
+
+    async fn set_bytes(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), anyhow::Error> {
+        self.bytes.lock().await.insert((namespace.to_string(), key.to_string()), value.to_vec());
+        Ok(())
+    }
+
+}
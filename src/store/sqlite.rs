@@ -0,0 +1,338 @@
+use std::{ffi::{c_char, c_int, c_void, CStr, CString}, path::Path, ptr, sync::Mutex};
+
+use anyhow::{anyhow, Context};
+use serenity::async_trait;
+
+use super::Store;
+
+/// Bindings to the system sqlite library
+mod ffi {
+    use std::ffi::{c_char, c_int, c_void};
+
+    /// Opaque database connection
+    #[repr(C)]
+    pub struct Sqlite3 {
+        _private: [u8; 0]
+    }
+
+    /// Opaque prepared statement
+    #[repr(C)]
+    pub struct Sqlite3Stmt {
+        _private: [u8; 0]
+    }
+
+    pub const SQLITE_OK: c_int = 0;
+    pub const SQLITE_ROW: c_int = 100;
+    pub const SQLITE_DONE: c_int = 101;
+    pub const SQLITE_OPEN_READWRITE: c_int = 0x02;
+    pub const SQLITE_OPEN_CREATE: c_int = 0x04;
+    pub const SQLITE_OPEN_FULLMUTEX: c_int = 0x10000;
+
+    /// Destructor making sqlite copy bound values
+    pub const SQLITE_TRANSIENT: isize = -1;
+
+    #[link(name = "sqlite3")]
+    extern "C" {
+        pub fn sqlite3_open_v2(filename: *const c_char, db: *mut *mut Sqlite3, flags: c_int, vfs: *const c_char) -> c_int;
+        pub fn sqlite3_close_v2(db: *mut Sqlite3) -> c_int;
+        pub fn sqlite3_errmsg(db: *mut Sqlite3) -> *const c_char;
+        pub fn sqlite3_exec(db: *mut Sqlite3, sql: *const c_char, callback: *const c_void, arg: *mut c_void, errmsg: *mut *mut c_char) -> c_int;
+        pub fn sqlite3_prepare_v2(db: *mut Sqlite3, sql: *const c_char, len: c_int, stmt: *mut *mut Sqlite3Stmt, tail: *mut *const c_char) -> c_int;
+        pub fn sqlite3_bind_text(stmt: *mut Sqlite3Stmt, index: c_int, value: *const c_char, len: c_int, destructor: isize) -> c_int;
+        pub fn sqlite3_bind_blob(stmt: *mut Sqlite3Stmt, index: c_int, value: *const c_void, len: c_int, destructor: isize) -> c_int;
+        pub fn sqlite3_step(stmt: *mut Sqlite3Stmt) -> c_int;
+        pub fn sqlite3_column_blob(stmt: *mut Sqlite3Stmt, column: c_int) -> *const c_void;
+        pub fn sqlite3_column_bytes(stmt: *mut Sqlite3Stmt, column: c_int) -> c_int;
+        pub fn sqlite3_finalize(stmt: *mut Sqlite3Stmt) -> c_int;
+    }
+}
+
+/// Statements creating the tables of the store
+const SCHEMA: &str = "PRAGMA journal_mode = WAL;
+CREATE TABLE IF NOT EXISTS store (namespace TEXT NOT NULL, key TEXT NOT NULL, value TEXT NOT NULL, PRIMARY KEY (namespace, key));
+CREATE TABLE IF NOT EXISTS store_bytes (namespace TEXT NOT NULL, key TEXT NOT NULL, value BLOB NOT NULL, PRIMARY KEY (namespace, key));";
+
+///
+/// Connection to a sqlite database
+///
+struct Connection(*mut ffi::Sqlite3);
+
+// the connection is opened in serialized mode and only used behind a mutex
+unsafe impl Send for Connection {}
+
+impl Connection {
+
+    ///
+    /// Open a database, creating it if it doesn't exist
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the database file
+    ///
+    /// # Returns
+    ///
+    /// The connection
+    ///
+    /// # Errors
+    ///
+    /// If the database cannot be opened
+    ///
+    fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        let path = CString::new(path.to_str().context("database path is not utf-8")?)
+            .context("database path contains a nul byte")?;
+        let flags = ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE | ffi::SQLITE_OPEN_FULLMUTEX;
+
+        let mut db = ptr::null_mut();
+        let status = unsafe { ffi::sqlite3_open_v2(path.as_ptr(), &mut db, flags, ptr::null()) };
+
+        // the handle is set even if opening fails, so it's closed either way
+        let connection = Self(db);
+        if status != ffi::SQLITE_OK {
+            return Err(anyhow!("failed to open database: {}", connection.error()));
+        }
+        Ok(connection)
+    }
+
+    ///
+    /// Get the message of the last error
+    ///
+    /// # Returns
+    ///
+    /// The error message
+    ///
+    fn error(&self) -> String {
+        if self.0.is_null() {
+            return "out of memory".to_string();
+        }
+        unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.0)) }.to_string_lossy().into_owned()
+    }
+
+    ///
+    /// Run statements without parameters
+    ///
+    /// # Arguments
+    ///
+    /// * `sql` - The statements
+    ///
+    /// # Errors
+    ///
+    /// If a statement fails
+    ///
+    fn execute_batch(&self, sql: &str) -> Result<(), anyhow::Error> {
+        let sql = CString::new(sql).context("statement contains a nul byte")?;
+        let status = unsafe { ffi::sqlite3_exec(self.0, sql.as_ptr(), ptr::null(), ptr::null_mut(), ptr::null_mut()) };
+        if status != ffi::SQLITE_OK {
+            return Err(anyhow!("failed to run statement: {}", self.error()));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Run a statement binding its parameters
+    ///
+    /// # Arguments
+    ///
+    /// * `sql` - The statement
+    /// * `params` - The parameters in order
+    ///
+    /// # Returns
+    ///
+    /// The first column of the first row, if the statement returned any
+    ///
+    /// # Errors
+    ///
+    /// If the statement fails
+    ///
+    fn query(&self, sql: &str, params: &[Param]) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        let mut stmt = ptr::null_mut();
+        let status = unsafe { ffi::sqlite3_prepare_v2(self.0, sql.as_ptr() as *const c_char, sql.len() as c_int, &mut stmt, ptr::null_mut()) };
+        if status != ffi::SQLITE_OK {
+            return Err(anyhow!("failed to prepare statement: {}", self.error()));
+        }
+        let stmt = Statement(stmt);
+
+        for (index, param) in params.iter().enumerate() {
+            let index = index as c_int + 1;
+            let status = match param {
+                Param::Text(value) => unsafe {
+                    ffi::sqlite3_bind_text(stmt.0, index, value.as_ptr() as *const c_char, value.len() as c_int, ffi::SQLITE_TRANSIENT)
+                },
+                Param::Blob(value) => unsafe {
+                    ffi::sqlite3_bind_blob(stmt.0, index, value.as_ptr() as *const c_void, value.len() as c_int, ffi::SQLITE_TRANSIENT)
+                }
+            };
+            if status != ffi::SQLITE_OK {
+                return Err(anyhow!("failed to bind parameter: {}", self.error()));
+            }
+        }
+
+        match unsafe { ffi::sqlite3_step(stmt.0) } {
+            ffi::SQLITE_ROW => {
+                // the column is copied before the statement is finalized
+                let len = unsafe { ffi::sqlite3_column_bytes(stmt.0, 0) } as usize;
+                let data = unsafe { ffi::sqlite3_column_blob(stmt.0, 0) } as *const u8;
+                let value = if data.is_null() { Vec::new() } else { unsafe { std::slice::from_raw_parts(data, len) }.to_vec() };
+                Ok(Some(value))
+            },
+            ffi::SQLITE_DONE => Ok(None),
+            _ => Err(anyhow!("failed to run statement: {}", self.error()))
+        }
+    }
+
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe { ffi::sqlite3_close_v2(self.0) };
+    }
+}
+
+/// Prepared statement, finalized when dropped
+struct Statement(*mut ffi::Sqlite3Stmt);
+
+impl Drop for Statement {
+    fn drop(&mut self) {
+        unsafe { ffi::sqlite3_finalize(self.0) };
+    }
+}
+
+/// Parameter of a statement
+enum Param<'a> {
+    Text(&'a str),
+    Blob(&'a [u8])
+}
+
+///
+/// Store persisting all values to a sqlite database
+///
+/// Every write is persisted right away, string values are kept in the 'store' table and binary values in 'store_bytes'.
+///
+pub struct SqliteStore {
+    /// Connection to the database
+    connection: Mutex<Connection>
+}
+
+impl SqliteStore {
+
+    ///
+    /// Open a sqlite store, creating the database and its tables if they don't exist
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the database file
+    ///
+    /// # Returns
+    ///
+    /// The sqlite store
+    ///
+    /// # Errors
+    ///
+    /// If the database cannot be opened or its tables cannot be created
+    ///
+    pub fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .context("failed to create parent directory")?;
+        }
+
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)
+            .context("failed to create tables")?;
+        Ok(Self { connection: Mutex::new(connection) })
+    }
+
+    ///
+    /// Run a statement on the database
+    ///
+    /// # Arguments
+    ///
+    /// * `sql` - The statement
+    /// * `params` - The parameters in order
+    ///
+    /// # Returns
+    ///
+    /// The first column of the first row, if the statement returned any
+    ///
+    /// # Errors
+    ///
+    /// If the statement fails
+    ///
+    fn query(&self, sql: &str, params: &[Param]) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner()).query(sql, params)
+    }
+
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<String>, anyhow::Error> {
+        self.query("SELECT value FROM store WHERE namespace = ? AND key = ?", &[Param::Text(namespace), Param::Text(key)])?
+            .map(|value| String::from_utf8(value).context("value is not utf-8"))
+            .transpose()
+    }
+
+    async fn set(&self, namespace: &str, key: &str, value: &str) -> Result<(), anyhow::Error> {
+        self.query("INSERT OR REPLACE INTO store (namespace, key, value) VALUES (?, ?, ?)",
+            &[Param::Text(namespace), Param::Text(key), Param::Text(value)])?;
+        Ok(())
+    }
+
+    async fn increment(&self, namespace: &str, key: &str, by: i64) -> Result<i64, anyhow::Error> {
+        // read and write under one lock, so concurrent increments aren't lost
+        let connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let value = connection.query("SELECT value FROM store WHERE namespace = ? AND key = ?", &[Param::Text(namespace), Param::Text(key)])?;
+        let value = match value {
+            Some(value) => std::str::from_utf8(&value).ok().and_then(|value| value.parse::<i64>().ok())
+                .context("value is not an integer")?,
+            None => 0
+        };
+
+        let incremented = value + by;
+        connection.query("INSERT OR REPLACE INTO store (namespace, key, value) VALUES (?, ?, ?)",
+            &[Param::Text(namespace), Param::Text(key), Param::Text(&incremented.to_string())])?;
+        Ok(incremented)
+    }
+
+    async fn get_bytes(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        self.query("SELECT value FROM store_bytes WHERE namespace = ? AND key = ?", &[Param::Text(namespace), Param::Text(key)])
+    }
+
+    async fn set_bytes(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), anyhow::Error> {
+        self.query("INSERT OR REPLACE INTO store_bytes (namespace, key, value) VALUES (?, ?, ?)",
+            &[Param::Text(namespace), Param::Text(key), Param::Blob(value)])?;
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn persists_values_across_connections() {
+        let directory = std::env::temp_dir().join(format!("puppy-rs-sqlite-{}", std::process::id()));
+        let path = directory.join("store.db");
+        {
+            let store = SqliteStore::open(&path).unwrap();
+            assert!(store.get("reaction", "usage").await.unwrap().is_none());
+            store.set("reaction", "usage", "{\"hug\":1}").await.unwrap();
+            store.set("reaction", "usage", "{\"hug\":2}").await.unwrap();
+            assert_eq!(store.increment("stats", "total", 5).await.unwrap(), 5);
+            assert_eq!(store.increment("stats", "total", -2).await.unwrap(), 3);
+            store.set_bytes("reaction", "cache", &[0, 159, 255]).await.unwrap();
+            store.set_bytes("reaction", "empty", &[]).await.unwrap();
+        }
+
+        let store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.get("reaction", "usage").await.unwrap().as_deref(), Some("{\"hug\":2}"));
+        assert_eq!(store.get("stats", "total").await.unwrap().as_deref(), Some("3"));
+        assert_eq!(store.get_bytes("reaction", "cache").await.unwrap(), Some(vec![0, 159, 255]));
+        assert_eq!(store.get_bytes("reaction", "empty").await.unwrap(), Some(Vec::new()));
+        assert!(store.get_bytes("reaction", "usage").await.unwrap().is_none());
+        assert!(store.increment("reaction", "usage", 1).await.is_err());
+        drop(store);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use std::future::Future;
+
+use log::warn;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// A long-running task that is stopped through a cancellation token
+pub struct BackgroundTask {
+    /// Token cancelled to stop the task
+    cancel: CancellationToken,
+    /// Handle of the spawned task
+    task: JoinHandle<()>
+}
+
+impl BackgroundTask {
+
+    ///
+    /// Spawn a background task
+    ///
+    /// # Arguments
+    ///
+    /// * `run` - Creates the future of the task, which should return soon after the token is cancelled
+    ///
+    /// # Returns
+    ///
+    /// The handle to the background task
+    ///
+    pub fn spawn<F, Fut>(run: F) -> Self
+        where F: FnOnce(CancellationToken) -> Fut, Fut: Future<Output = ()> + Send + 'static {
+        let cancel = CancellationToken::new();
+        let task = tokio::spawn(run(cancel.clone()));
+        Self { cancel, task }
+    }
+
+    ///
+    /// Stop the background task, waiting for it to return
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The log target to report a failed task to
+    ///
+    pub async fn stop(self, target: &str) {
+        self.cancel.cancel();
+        if let Err(e) = self.task.await {
+            warn!(target: target, "background task failed: {:?}", e);
+        }
+    }
+
+}
+
+///
+/// Run a single iteration of a loop in its own task, so neither an error nor a panic ends the loop
+///
+/// # Arguments
+///
+/// * `target` - The log target to report failures to
+/// * `name` - What the iteration does, used in the log
+/// * `iteration` - The iteration
+///
+/// # Returns
+///
+/// Whether the iteration succeeded
+///
+pub async fn run_iteration<Fut>(target: &str, name: &str, iteration: Fut) -> bool
+    where Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static {
+    match tokio::spawn(iteration).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            warn!(target: target, "failed to {}: {:?}", name, e);
+            false
+        },
+        Err(e) => {
+            warn!(target: target, "failed to {}, the task panicked: {:?}", name, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn iterations_report_errors_and_panics() {
+        assert!(run_iteration("task", "succeed", async { Ok(()) }).await);
+        assert!(!run_iteration("task", "fail", async { Err(anyhow!("gateway disconnected")) }).await);
+        assert!(!run_iteration("task", "panic", async { panic!("http call failed") }).await);
+    }
+
+    #[tokio::test]
+    async fn loops_survive_failed_iterations() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let task = BackgroundTask::spawn(move |cancel| async move {
+            while !cancel.is_cancelled() {
+                let counter = counter.clone();
+                run_iteration("task", "iterate", async move {
+                    match counter.fetch_add(1, Ordering::SeqCst) {
+                        0 => Err(anyhow!("gateway disconnected")),
+                        1 => panic!("http call failed"),
+                        _ => Ok(())
+                    }
+                }).await;
+                tokio::task::yield_now().await;
+            }
+        });
+
+        // the loop keeps going after the error and the panic until it's stopped
+        while runs.load(Ordering::SeqCst) < 5 {
+            tokio::task::yield_now().await;
+        }
+        task.stop("task").await;
+        let stopped = runs.load(Ordering::SeqCst);
+        tokio::task::yield_now().await;
+        assert_eq!(runs.load(Ordering::SeqCst), stopped);
+    }
+}
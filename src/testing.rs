@@ -0,0 +1,270 @@
+use anyhow::Context;
+use serde_json::{json, Value};
+use serenity::all::{ChannelId, CommandInteraction, CreateCommand, GuildId, UserId};
+
+use crate::Configuration;
+
+/// Configuration shipped with the repository, used as the sample configuration
+const SAMPLE_CONFIG: &str = include_str!("../config.yml");
+
+///
+/// Load the sample configuration shipped with the repository
+///
+/// # Returns
+///
+/// The parsed configuration
+///
+/// # Errors
+///
+/// If the sample configuration fails to parse
+///
+pub fn sample_config() -> Result<Configuration, anyhow::Error> {
+    Configuration::parse(SAMPLE_CONFIG)
+        .map(|(config, _)| config)
+        .context("failed to parse sample configuration")
+}
+
+/// Summary of a command creation request for assertions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandSummary {
+    /// Name of the command
+    pub name: String,
+    /// Names of the top-level options or subcommands
+    pub options: Vec<String>,
+    /// Integration types as discord ids, 0 for guild and 1 for user installs
+    pub integration_types: Vec<u64>,
+    /// Interaction contexts as discord ids, 0 for guilds, 1 for bot dms and 2 for private channels
+    pub contexts: Vec<u64>
+}
+
+///
+/// Summarize a command creation request
+///
+/// # Arguments
+///
+/// * `command` - The command creation request
+///
+/// # Returns
+///
+/// The name, option names and contexts of the command
+///
+/// # Errors
+///
+/// If the command fails to serialize or has no name
+///
+pub fn summarize(command: &CreateCommand) -> Result<CommandSummary, anyhow::Error> {
+    let value = serde_json::to_value(command)
+        .context("failed to serialize command")?;
+    let ids = |key: &str| value.get(key).and_then(Value::as_array)
+        .map(|ids| ids.iter().filter_map(Value::as_u64).collect())
+        .unwrap_or_default();
+
+    Ok(CommandSummary {
+        name: value.get("name").and_then(Value::as_str).context("command has no name")?.to_string(),
+        options: value.get("options").and_then(Value::as_array)
+            .map(|options| options.iter().filter_map(|option| option.get("name")?.as_str().map(str::to_string)).collect())
+            .unwrap_or_default(),
+        integration_types: ids("integration_types"),
+        contexts: ids("contexts")
+    })
+}
+
+///
+/// Builder for synthetic command interactions
+///
+/// Interactions are deserialized from the payload discord would send, so they behave like real ones,
+/// except that responding to them fails as their tokens are made up.
+///
+pub struct CommandInteractionBuilder {
+    /// Name of the command
+    name: String,
+    /// Name of the subcommand group holding the subcommand, if any
+    group: Option<String>,
+    /// Name of the subcommand, if any
+    subcommand: Option<String>,
+    /// The invoking user
+    user: UserId,
+    /// The targeted user of the 'user' option, if any
+    target: Option<UserId>,
+    /// The guild the command is used in, if any
+    guild: Option<GuildId>,
+    /// The channel the command is used in
+    channel: ChannelId
+}
+
+impl CommandInteractionBuilder {
+
+    ///
+    /// Create a builder for a command used by a user in a bot dm
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the command
+    ///
+    /// # Returns
+    ///
+    /// The builder
+    ///
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            group: None,
+            subcommand: None,
+            user: UserId::new(1),
+            target: None,
+            guild: None,
+            channel: ChannelId::new(1)
+        }
+    }
+
+    ///
+    /// Use a subcommand of the command
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subcommand
+    ///
+    /// # Returns
+    ///
+    /// The builder
+    ///
+    pub fn subcommand(mut self, name: impl Into<String>) -> Self {
+        self.subcommand = Some(name.into());
+        self
+    }
+
+    ///
+    /// Nest the subcommand in a subcommand group
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the subcommand group
+    ///
+    /// # Returns
+    ///
+    /// The builder
+    ///
+    pub fn group(mut self, name: impl Into<String>) -> Self {
+        self.group = Some(name.into());
+        self
+    }
+
+    ///
+    /// Set the invoking user
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - The user id
+    ///
+    /// # Returns
+    ///
+    /// The builder
+    ///
+    pub fn user(mut self, user: UserId) -> Self {
+        self.user = user;
+        self
+    }
+
+    ///
+    /// Target a user with the 'user' option
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The user id of the target
+    ///
+    /// # Returns
+    ///
+    /// The builder
+    ///
+    pub fn target(mut self, target: UserId) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    ///
+    /// Use the command in a guild
+    ///
+    /// # Arguments
+    ///
+    /// * `guild` - The guild id
+    ///
+    /// # Returns
+    ///
+    /// The builder
+    ///
+    pub fn guild(mut self, guild: GuildId) -> Self {
+        self.guild = Some(guild);
+        self
+    }
+
+    ///
+    /// Set the channel the command is used in
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel id
+    ///
+    /// # Returns
+    ///
+    /// The builder
+    ///
+    pub fn channel(mut self, channel: ChannelId) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    ///
+    /// Build the interaction
+    ///
+    /// # Returns
+    ///
+    /// The synthetic command interaction
+    ///
+    /// # Errors
+    ///
+    /// If the payload fails to deserialize
+    ///
+    pub fn build(self) -> Result<CommandInteraction, anyhow::Error> {
+        let user = |id: UserId| json!({ "id": id.to_string(), "username": format!("user{}", id), "discriminator": "0", "avatar": null });
+
+        let mut options = Vec::new();
+        let mut users = serde_json::Map::new();
+        if let Some(target) = self.target {
+            options.push(json!({ "name": "user", "type": 6, "value": target.to_string() }));
+            users.insert(target.to_string(), user(target));
+        }
+        let options = match self.subcommand {
+            Some(subcommand) => vec![json!({ "name": subcommand, "type": 1, "options": options })],
+            None => options
+        };
+        let options = match self.group {
+            Some(group) => vec![json!({ "name": group, "type": 2, "options": options })],
+            None => options
+        };
+
+        let mut payload = json!({
+            "id": "1",
+            "application_id": "1",
+            "type": 2,
+            "data": { "id": "1", "name": self.name, "type": 1, "options": options, "resolved": { "users": users } },
+            "channel_id": self.channel.to_string(),
+            "token": "synthetic",
+            "version": 1,
+            "locale": "en-US",
+            "entitlements": [],
+            "attachment_size_limit": 8388608,
+            "context": if self.guild.is_some() { 0 } else { 1 }
+        });
+        match self.guild {
+            Some(guild) => {
+                payload["guild_id"] = json!(guild.to_string());
+                payload["member"] = json!({ "user": user(self.user), "roles": [], "joined_at": "2020-01-01T00:00:00Z", "deaf": false, "mute": false, "flags": 0 });
+            },
+            None => payload["user"] = user(self.user)
+        }
+
+        serde_json::from_value(payload)
+            .context("failed to deserialize synthetic command interaction")
+    }
+
+}